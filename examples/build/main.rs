@@ -1,7 +1,7 @@
 use pretty::{Doc, RcDoc};
 use sol_lang::parser::{
     self,
-    ast::{Scene, Script},
+    ast::{Module, Scene},
 };
 
 fn main() {
@@ -17,11 +17,11 @@ fn main() {
     std::fs::write("./examples/dist/main2.sol", formatted).unwrap();
 }
 
-fn q_script(script: &Script) -> RcDoc {
+fn q_script(script: &Module) -> RcDoc<'_> {
     RcDoc::intersperse(script.scenes.iter().map(q_scene), Doc::line())
 }
 
-fn q_scene(scene: &Scene) -> RcDoc {
+fn q_scene(scene: &Scene) -> RcDoc<'_> {
     RcDoc::text("scene ")
         .append(scene.name.clone())
         .append("\n")
@@ -58,19 +58,31 @@ pub fn x_script(script: &Module) -> (Catalog, Catalog) {
 
 pub fn x_scene_part(mut strings: &mut Vec<String>, scene_part: &ScenePart) {
     match scene_part {
-        ScenePart::Dialogue(dialogue) => x_dialogue(&mut strings, dialogue.parts.iter()),
-        ScenePart::Narration(narration) => x_dialogue(&mut strings, narration.parts.iter()),
+        ScenePart::Dialogue(dialogue) => x_dialogue(strings, dialogue.parts.iter()),
+        ScenePart::Narration(narration) => x_dialogue(strings, narration.parts.iter()),
         ScenePart::Prompt(prompt) => {
             prompt.options.iter().for_each(|option| {
-                x_dialogue(&mut strings, option.text.iter());
+                x_dialogue(strings, option.text.iter());
                 option
                     .content
                     .iter()
-                    .for_each(|scene_part| x_scene_part(&mut strings, scene_part));
+                    .for_each(|scene_part| x_scene_part(strings, scene_part));
             });
         }
+        ScenePart::Conditional(conditional) => {
+            x_expression(strings, &conditional.condition);
+            conditional
+                .content
+                .iter()
+                .chain(conditional.otherwise.iter())
+                .for_each(|scene_part| x_scene_part(strings, scene_part));
+        }
         ScenePart::Expression(expression) => {
-            x_expression(&mut strings, expression);
+            x_expression(strings, expression);
+        }
+
+        ScenePart::Assignment(assignment) => {
+            x_expression(strings, &assignment.value);
         }
 
         // Nothing for these...
@@ -88,7 +100,7 @@ where
             TextPart::Text(text) => Some(text.clone()),
             TextPart::Expression(expression) => {
                 *expression_idx += 1;
-                x_expression(&mut strings, expression);
+                x_expression(strings, expression);
                 Some(format!("${}", expression_idx))
             }
         })
@@ -98,6 +110,6 @@ where
 }
 
 #[allow(unused)]
-pub fn x_expression(strings: &mut Vec<String>, expression: &Expression) {
+pub fn x_expression(strings: &mut [String], expression: &Expression) {
     // Nothing for expressions...
 }
@@ -21,10 +21,10 @@ pub fn r_script(script: &Module, catalog: &Catalog) -> Module {
     output
 }
 
-pub fn r_scene_part<'a>(
+pub fn r_scene_part(
     scene_part: &mut ScenePart,
     catalog: &Catalog,
-    scene_name: &'a str,
+    scene_name: &str,
     text_idx: &mut usize,
 ) {
     match scene_part {
@@ -43,10 +43,22 @@ pub fn r_scene_part<'a>(
                     .for_each(|scene_part| r_scene_part(scene_part, catalog, scene_name, text_idx));
             });
         }
+        ScenePart::Conditional(conditional) => {
+            r_expression(&mut conditional.condition);
+            conditional
+                .content
+                .iter_mut()
+                .chain(conditional.otherwise.iter_mut())
+                .for_each(|scene_part| r_scene_part(scene_part, catalog, scene_name, text_idx));
+        }
         ScenePart::Expression(expression) => {
             r_expression(expression);
         }
 
+        ScenePart::Assignment(assignment) => {
+            r_expression(&mut assignment.value);
+        }
+
         // Nothing for these...
         ScenePart::SpeakerChangeMarker(_) => {}
         ScenePart::Comment(_) => {}
@@ -67,7 +79,7 @@ where
 
     let message = catalog
         .find_message(None, key.as_str(), None)
-        .expect(format!("Message {} not found!", key).as_str());
+        .unwrap_or_else(|| panic!("Message {} not found!", key));
     let text = message.msgstr().expect("Was not 'singular' message...");
 
     let expressions = text_parts
@@ -108,7 +120,7 @@ fn p_text_part<'src>(
                     .map(|num| num.parse::<usize>().unwrap()),
             )
             .map(move |expr_idx| TextPart::Expression(expressions[expr_idx - 1].clone())),
-        p_raw_text().map(|i| TextPart::Text(i)),
+        p_raw_text().map(TextPart::Text),
     ))
 }
 
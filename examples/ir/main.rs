@@ -1,5 +1,6 @@
 use sol_lang::introspec::repr::{
-    AssetModule, Function, LazyExpression, LexicalBinding, LexicallyBoundValue, Value,
+    AssetModule, Function, LazyExpression, LexicalBinding, LexicallyBoundValue, ModuleItem,
+    ModuleItemValue, ModuleItemVisibility, Value,
 };
 use std::collections::HashMap;
 
@@ -12,21 +13,27 @@ end
 */
 
 fn main() {
-    let mut items: HashMap<String, Value> = HashMap::new();
+    let mut items: HashMap<String, ModuleItem> = HashMap::new();
 
-    let add = LexicalBinding {
+    let _add = LexicalBinding {
         name: String::from("add"),
         value: LexicallyBoundValue::Inline(Box::new(Value::Int(0))),
     };
 
-    let efn_params = HashMap::new();
+    let efn_params = Vec::new();
     let body = LazyExpression::Literal(Value::Int(0));
     let execute_fn = Function {
         parameters: efn_params,
         body,
     };
 
-    items.insert("execute".to_string(), Value::Function(Box::new(execute_fn)));
+    items.insert(
+        "execute".to_string(),
+        ModuleItem {
+            value: ModuleItemValue::Static(Value::Function(Box::new(execute_fn))),
+            visibility: ModuleItemVisibility::Public,
+        },
+    );
 
     let asset = AssetModule { items };
 
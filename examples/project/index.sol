@@ -0,0 +1,2 @@
+name = "project"
+version = "0.1.0"
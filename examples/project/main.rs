@@ -1,6 +1,11 @@
-#![allow(unused)]
 use sol_lang::introspec::project::Project;
 
-fn main() {
-    let p = Project::load_from_directory("./examples/project".into());
+fn main() -> miette::Result<()> {
+    let project = Project::discover("./examples/project".as_ref())?;
+
+    for module in project.modules.iter() {
+        println!("{} ({})", module.path, module.file.display());
+    }
+
+    Ok(())
 }
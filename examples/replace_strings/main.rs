@@ -48,10 +48,22 @@ fn x_scene_part(strings: &mut Vec<String>, scene_part: &ScenePart) {
                     .for_each(|scene_part| x_scene_part(strings, scene_part));
             });
         }
+        ScenePart::Conditional(conditional) => {
+            x_expression(strings, &conditional.condition);
+            conditional
+                .content
+                .iter()
+                .chain(conditional.otherwise.iter())
+                .for_each(|scene_part| x_scene_part(strings, scene_part));
+        }
         ScenePart::Expression(expression) => {
             x_expression(strings, expression);
         }
 
+        ScenePart::Assignment(assignment) => {
+            x_expression(strings, &assignment.value);
+        }
+
         // Nothing for these...
         ScenePart::SpeakerChangeMarker(_) => {}
         ScenePart::Comment(_) => {}
@@ -19,5 +19,5 @@ fn main() {
         nums: vec![0, 1, 2, 3, 4, 5, 6]
     };
 
-    let first_nums = nums.take(3).collect::<Vec<_>>();
+    let _first_nums = nums.take(3).collect::<Vec<_>>();
 }
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- A character of the game, like the player, or an enemy.
+-- The game supplies them, and what they do.
+model Character
+    name: Text
+end
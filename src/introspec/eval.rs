@@ -0,0 +1,472 @@
+//! Evaluation of [`LazyExpression`]s, as used to work out the values of fields at build time.
+//!
+//! Arithmetic is written as calls to `+`, `-`, `*` and `/`, and comparisons as calls
+//! to `==`, `<` and so on, or `and` and `or`, which are built in. Comparisons give
+//! `::True` or `::False`. Any other call is to a [`Function`] defined in the same module.
+use super::repr::{Function, LazyExpression, ModuleItem, ModuleItemValue, Value};
+use crate::parser::ast::Operator;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// How deep calls can nest before evaluation gives up, assuming they would never end.
+const MAX_DEPTH: usize = 256;
+
+/// Why an expression couldn't be evaluated, and the calls and fields it happened in.
+#[derive(Error, Debug)]
+#[error("{message}")]
+pub struct EvaluationError {
+    pub message: String,
+    /// The innermost frame first, as in "in `double(2)`", then "in the field `damage`".
+    pub trace: Vec<String>,
+}
+
+impl EvaluationError {
+    fn new(message: impl Into<String>) -> Self {
+        EvaluationError {
+            message: message.into(),
+            trace: Vec::new(),
+        }
+    }
+
+    fn within(mut self, frame: String) -> Self {
+        self.trace.push(frame);
+        self
+    }
+}
+
+/// Evaluates expressions against the items of a module.
+///
+/// `fields` are the fields that haven't been evaluated yet. Each of them is
+/// evaluated the first time it's referred to, then remembered.
+pub struct Evaluator<'m> {
+    items: &'m HashMap<String, ModuleItem>,
+    fields: &'m HashMap<String, LazyExpression>,
+    values: HashMap<String, Value>,
+    evaluating: Vec<String>,
+    depth: usize,
+}
+
+impl<'m> Evaluator<'m> {
+    pub fn new(
+        items: &'m HashMap<String, ModuleItem>,
+        fields: &'m HashMap<String, LazyExpression>,
+    ) -> Self {
+        Evaluator {
+            items,
+            fields,
+            values: HashMap::new(),
+            evaluating: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    /// Returns the value of the item called `name`, evaluating it if it's a field.
+    pub fn item(&mut self, name: &str) -> Result<Value, EvaluationError> {
+        if let Some(value) = self.values.get(name) {
+            return Ok(value.clone());
+        }
+
+        if let Some(expression) = self.fields.get(name) {
+            if self.evaluating.iter().any(|field| field == name) {
+                return Err(EvaluationError::new(format!(
+                    "The field `{}` depends on its own value.",
+                    name
+                )));
+            }
+            self.evaluating.push(name.to_string());
+            let value = self.evaluate(expression, &HashMap::new());
+            self.evaluating.pop();
+            let value = value.map_err(|error| error.within(format!("in the field `{}`", name)))?;
+            self.values.insert(name.to_string(), value.clone());
+            return Ok(value);
+        }
+
+        match self.items.get(name).map(|item| &item.value) {
+            Some(ModuleItemValue::Static(value) | ModuleItemValue::Dynamic(value)) => {
+                Ok(value.clone())
+            }
+            Some(ModuleItemValue::Extern(_)) => Err(EvaluationError::new(format!(
+                "`{}` is only known once the game runs.",
+                name
+            ))),
+            None => Err(EvaluationError::new(format!(
+                "There's no item called `{}`.",
+                name
+            ))),
+        }
+    }
+
+    /// Evaluates `expression`, where `bindings` are the values of the parameters in scope.
+    pub fn evaluate(
+        &mut self,
+        expression: &LazyExpression,
+        bindings: &HashMap<String, Value>,
+    ) -> Result<Value, EvaluationError> {
+        match expression {
+            LazyExpression::Literal(value) => Ok(value.clone()),
+            LazyExpression::ParameterRef(name) => bindings.get(name).cloned().ok_or_else(|| {
+                EvaluationError::new(format!("The parameter `{}` isn't bound.", name))
+            }),
+            LazyExpression::ItemRef(path) => match path.as_slice() {
+                [name] => self.item(name),
+                _ => Err(EvaluationError::new(format!(
+                    "`::{}` is in another module, which can't be evaluated at build time yet.",
+                    path.join("::")
+                ))),
+            },
+            LazyExpression::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.evaluate(arg, bindings))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call(name, args)
+            }
+            LazyExpression::Interpolate(parts) => {
+                let mut text = String::new();
+                for part in parts {
+                    text.push_str(&interpolate(&self.evaluate(part, bindings)?)?);
+                }
+                Ok(Value::Text(text))
+            }
+            LazyExpression::Sequence(expressions) => {
+                let mut value = Value::Void(());
+                for expression in expressions {
+                    value = self.evaluate(expression, bindings)?;
+                }
+                Ok(value)
+            }
+            LazyExpression::Conditional(branches) => {
+                let [condition, content, otherwise] = branches.as_ref();
+                if condition_holds(&self.evaluate(condition, bindings)?)? {
+                    self.evaluate(content, bindings)
+                } else {
+                    self.evaluate(otherwise, bindings)
+                }
+            }
+        }
+    }
+
+    /// Calls the function called `name`, either an operator or a function of this module.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, EvaluationError> {
+        if Operator::from_symbol(name).is_some() {
+            return match args.as_slice() {
+                [left, right] => operate(name, left, right),
+                _ => Err(EvaluationError::new(format!(
+                    "`{}` takes two values, but was given {}.",
+                    name,
+                    args.len()
+                ))),
+            };
+        }
+
+        let function = match self.item(name)? {
+            Value::Function(function) => function,
+            _ => {
+                return Err(EvaluationError::new(format!(
+                    "`{}` isn't a function, so it can't be called.",
+                    name
+                )))
+            }
+        };
+        let frame = format!(
+            "in `{}({})`",
+            name,
+            args.iter().map(show).collect::<Vec<_>>().join(", ")
+        );
+        self.invoke(&function, args)
+            .map_err(|error| error.within(frame))
+    }
+
+    fn invoke(&mut self, function: &Function, args: Vec<Value>) -> Result<Value, EvaluationError> {
+        if args.len() != function.parameters.len() {
+            return Err(EvaluationError::new(format!(
+                "This takes {} arguments, but was given {}.",
+                function.parameters.len(),
+                args.len()
+            )));
+        }
+        if self.depth == MAX_DEPTH {
+            return Err(EvaluationError::new(
+                "Calls are nested too deeply. Does a function call itself forever?",
+            ));
+        }
+
+        let bindings = function
+            .parameters
+            .iter()
+            .map(|parameter| parameter.name.clone())
+            .zip(args)
+            .collect();
+        self.depth += 1;
+        let value = self.evaluate(&function.body, &bindings);
+        self.depth -= 1;
+        value
+    }
+}
+
+/// Applies an operator, as in `+` or `<=`.
+pub(crate) fn operate(
+    operator: &str,
+    left: &Value,
+    right: &Value,
+) -> Result<Value, EvaluationError> {
+    if matches!(operator, "+" | "-" | "*" | "/") {
+        return arithmetic(operator, left, right);
+    }
+    let holds = match operator {
+        "==" => equal(left, right),
+        "!=" => !equal(left, right),
+        "and" => condition_holds(left)? && condition_holds(right)?,
+        "or" => condition_holds(left)? || condition_holds(right)?,
+        _ => {
+            let (Some(left), Some(right)) = (number(left), number(right)) else {
+                return Err(EvaluationError::new(format!(
+                    "Can't compare {} and {} with `{}`.",
+                    show(left),
+                    show(right),
+                    operator
+                )));
+            };
+            match operator {
+                "<" => left < right,
+                "<=" => left <= right,
+                ">" => left > right,
+                _ => left >= right,
+            }
+        }
+    };
+    Ok(truth(holds))
+}
+
+/// `::True` or `::False`.
+pub(crate) fn truth(holds: bool) -> Value {
+    Value::Variant(if holds { "True" } else { "False" }.to_string(), None)
+}
+
+/// Whether `value`, a condition, is `::True`. Anything but `::True` or `::False` is an error.
+pub(crate) fn condition_holds(value: &Value) -> Result<bool, EvaluationError> {
+    match value {
+        Value::Variant(name, None) if name == "True" => Ok(true),
+        Value::Variant(name, None) if name == "False" => Ok(false),
+        _ => Err(EvaluationError::new(format!(
+            "Conditions are `::True` or `::False`, but this is {}.",
+            show(value)
+        ))),
+    }
+}
+
+/// Applies an arithmetic operator. Natural numbers stay natural, unless subtracted.
+/// Integers and natural numbers mix into integers. Floats only mix with floats.
+pub(crate) fn arithmetic(
+    operator: &str,
+    left: &Value,
+    right: &Value,
+) -> Result<Value, EvaluationError> {
+    let overflow = || EvaluationError::new(format!("`{}` overflows here.", operator));
+    let divide_by_zero = || EvaluationError::new("Division by zero.");
+    let integer = |value: &Value| match value {
+        Value::Nat(value) => i32::try_from(*value).ok(),
+        Value::Int(value) => Some(*value),
+        _ => None,
+    };
+
+    match (left, right) {
+        (Value::Nat(left), Value::Nat(right)) if operator != "-" => match operator {
+            "+" => left.checked_add(*right),
+            "*" => left.checked_mul(*right),
+            _ if *right == 0 => return Err(divide_by_zero()),
+            _ => left.checked_div(*right),
+        }
+        .map(Value::Nat)
+        .ok_or_else(overflow),
+        (Value::Float(left), Value::Float(right)) => Ok(Value::Float(match operator {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            _ if *right == 0.0 => return Err(divide_by_zero()),
+            _ => left / right,
+        })),
+        (Value::Nat(_) | Value::Int(_), Value::Nat(_) | Value::Int(_)) => {
+            let (Some(left), Some(right)) = (integer(left), integer(right)) else {
+                return Err(overflow());
+            };
+            match operator {
+                "+" => left.checked_add(right),
+                "-" => left.checked_sub(right),
+                "*" => left.checked_mul(right),
+                _ if right == 0 => return Err(divide_by_zero()),
+                _ => left.checked_div(right),
+            }
+            .map(Value::Int)
+            .ok_or_else(overflow)
+        }
+        _ => Err(EvaluationError::new(format!(
+            "Can't apply `{}` to {} and {}.",
+            operator,
+            show(left),
+            show(right)
+        ))),
+    }
+}
+
+/// Renders a value as it appears when interpolated into text.
+pub(crate) fn interpolate(value: &Value) -> Result<String, EvaluationError> {
+    Ok(match value {
+        Value::Text(text) => text.clone(),
+        Value::Nat(_) | Value::Int(_) | Value::Float(_) | Value::Variant(..) => show(value),
+        _ => {
+            return Err(EvaluationError::new(format!(
+                "{} can't be written as text.",
+                show(value)
+            )))
+        }
+    })
+}
+
+/// Whether two values are the same. Numbers are compared by their value, whatever their type,
+/// and values that aren't data, like functions, are never the same.
+pub(crate) fn equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Text(left), Value::Text(right)) => left == right,
+        (Value::Variant(left, left_payload), Value::Variant(right, right_payload)) => {
+            left == right
+                && match (left_payload, right_payload) {
+                    (Some(left), Some(right)) => equal(left, right),
+                    (left, right) => left.is_none() && right.is_none(),
+                }
+        }
+        (Value::AssetRef(left), Value::AssetRef(right)) => left == right,
+        (Value::Host(left), Value::Host(right)) => left == right,
+        (Value::Void(()), Value::Void(())) => true,
+        _ => match (number(left), number(right)) {
+            (Some(left), Some(right)) => left == right,
+            _ => false,
+        },
+    }
+}
+
+/// The value of a number, whatever its type.
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Nat(value) => Some(f64::from(*value)),
+        Value::Int(value) => Some(f64::from(*value)),
+        Value::Float(value) => Some(f64::from(*value)),
+        _ => None,
+    }
+}
+
+/// Writes a value roughly as it would be written in Sol, for error messages.
+pub(crate) fn show(value: &Value) -> String {
+    match value {
+        Value::Nat(value) => value.to_string(),
+        Value::Int(value) => value.to_string(),
+        Value::Float(value) => format!("{:?}", value),
+        Value::Text(text) => format!("{:?}", text),
+        Value::Variant(name, None) => format!("::{}", name),
+        Value::Variant(name, Some(payload)) => format!("{}({})", name, show(payload)),
+        Value::Void(()) => "nothing".to_string(),
+        Value::Function(_) => "a function".to_string(),
+        Value::Model(model) => format!("the model `{}`", model.name),
+        Value::Asset(_) => "an asset".to_string(),
+        Value::AssetRef(path) => format!("::{}", path.join("::")),
+        Value::Host(handle) => format!("the game's object {}", handle),
+        Value::BultinType(_) => "a type".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Evaluator;
+    use crate::introspec::repr::{
+        FnParameter, Function, LazyExpression, ModuleItem, ModuleItemValue, ModuleItemVisibility,
+        Value,
+    };
+    use std::{collections::HashMap, rc::Rc};
+
+    fn int(value: i32) -> LazyExpression {
+        LazyExpression::Literal(Value::Int(value))
+    }
+
+    fn call(name: &str, args: Vec<LazyExpression>) -> LazyExpression {
+        LazyExpression::Call(name.to_string(), args)
+    }
+
+    #[test]
+    fn folds_fields() {
+        let items = HashMap::new();
+        let fields = HashMap::from([
+            (
+                "damage".to_string(),
+                call("*", vec![call("+", vec![int(2), int(3)]), int(4)]),
+            ),
+            (
+                "description".to_string(),
+                LazyExpression::Interpolate(vec![
+                    LazyExpression::Literal(Value::Text("Deals ".to_string())),
+                    LazyExpression::ItemRef(vec!["damage".to_string()]),
+                    LazyExpression::Literal(Value::Text(" damage.".to_string())),
+                ]),
+            ),
+        ]);
+
+        let mut evaluator = Evaluator::new(&items, &fields);
+        assert!(matches!(
+            evaluator.item("description").unwrap(),
+            Value::Text(text) if text == "Deals 20 damage."
+        ));
+        assert!(matches!(
+            evaluator
+                .call("-", vec![Value::Nat(2), Value::Nat(3)])
+                .unwrap(),
+            Value::Int(-1)
+        ));
+    }
+
+    #[test]
+    fn traces_errors_through_calls() {
+        let parameter = |name: &str| {
+            Rc::new(FnParameter {
+                name: name.to_string(),
+                concrete_type: Value::Void(()),
+            })
+        };
+        let split = Function {
+            parameters: vec![parameter("amount"), parameter("ways")],
+            body: call(
+                "/",
+                vec![
+                    LazyExpression::ParameterRef("amount".to_string()),
+                    LazyExpression::ParameterRef("ways".to_string()),
+                ],
+            ),
+        };
+        let items = HashMap::from([(
+            "split".to_string(),
+            ModuleItem {
+                value: ModuleItemValue::Static(Value::Function(Box::new(split))),
+                visibility: ModuleItemVisibility::Public,
+            },
+        )]);
+        let fields = HashMap::from([
+            ("share".to_string(), call("split", vec![int(10), int(0)])),
+            (
+                "loop".to_string(),
+                LazyExpression::ItemRef(vec!["loop".to_string()]),
+            ),
+        ]);
+
+        let mut evaluator = Evaluator::new(&items, &fields);
+        let error = evaluator.item("share").unwrap_err();
+        assert_eq!(error.message, "Division by zero.");
+        assert_eq!(
+            error.trace,
+            vec!["in `split(10, 0)`", "in the field `share`"]
+        );
+
+        let error = evaluator.item("loop").unwrap_err();
+        assert_eq!(error.message, "The field `loop` depends on its own value.");
+
+        let error = evaluator.call("split", vec![Value::Int(1)]).unwrap_err();
+        assert_eq!(error.message, "This takes 2 arguments, but was given 1.");
+    }
+}
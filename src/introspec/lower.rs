@@ -0,0 +1,750 @@
+//! Lowering of parsed modules into [`repr`](super::repr), the form they are checked and run in.
+//!
+//! Fields become [`ModuleItem`]s, which are dynamic when they're declared `dynamic` or the module's model says so,
+//! actions become [`Function`]s, models become [`Model`]s, and externs are left for the game.
+//! The values of fields are worked out here, by [`Evaluator`].
+use super::{
+    eval::Evaluator,
+    repr::{
+        AssetModule, BuiltinType, Either, FnParameter, FnType, Function, LazyExpression, Model,
+        ModuleItem, ModuleItemValue, ModuleItemVisibility, Value,
+    },
+};
+use crate::{
+    parser::ast::{self, Expression, ScenePart, Span, TextPart, TypeExpression, Visibility},
+    workspace::{upgrade, ParsedModule},
+};
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use std::{collections::HashMap, rc::Rc};
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("Some of this module can't be built.")]
+#[diagnostic(code(sol::lower))]
+pub struct LoweringError {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[related]
+    pub problems: Vec<LoweringProblem>,
+}
+
+/// A single construct that can't be lowered, pointing at where it is.
+#[derive(Error, Debug, Diagnostic)]
+#[error("{message}")]
+pub struct LoweringProblem {
+    pub message: String,
+    #[label("{label}")]
+    pub span: SourceSpan,
+    pub label: String,
+    /// The calls and fields evaluation went through, when it failed.
+    #[help]
+    pub trace: Option<String>,
+}
+
+/// Lowers `module`, an asset already upgraded to its model's current version,
+/// or a module with no model. Types are looked up among `models`.
+pub fn lower(
+    module: &ParsedModule,
+    models: &[&ParsedModule],
+) -> Result<AssetModule, LoweringError> {
+    let mut lowering = Lowering {
+        models,
+        problems: Vec::new(),
+    };
+    let model = module
+        .ast
+        .model
+        .as_ref()
+        .and_then(|directive| upgrade::find_model(models, &directive.name))
+        .map(|(_, model)| model);
+    let mut items = HashMap::new();
+    let mut fields = HashMap::new();
+
+    let mut declared_fields = module.ast.fields.iter().collect::<Vec<_>>();
+    declared_fields.sort_by_key(|(_, field)| field.name_span.start);
+    let mut evaluated = Vec::new();
+    for (name, field) in declared_fields {
+        let declared =
+            model.and_then(|model| model.fields.iter().find(|declared| &declared.name == name));
+        let declared_type =
+            declared.and_then(|declared| builtin_type(&declared.field_type, models).ok());
+        let Some(value) = lowering.field(&field.value, field.value_span, declared_type.as_ref())
+        else {
+            continue;
+        };
+        let dynamic = field.dynamic || declared.is_some_and(|declared| declared.dynamic);
+        fields.insert(name.clone(), value);
+        evaluated.push((name, field, dynamic, declared_type));
+    }
+
+    for action in module.ast.actions.iter() {
+        let declared = model
+            .and_then(|model| model.fields.iter().find(|field| field.name == action.name))
+            .map(|field| &field.field_type);
+        if let Some(function) = lowering.action(action, declared) {
+            lowering.insert(
+                &mut items,
+                &fields,
+                &action.name,
+                action.name_span,
+                action.visibility,
+                ModuleItemValue::Static(Value::Function(Box::new(function))),
+            );
+        }
+    }
+
+    for model in module.ast.models.iter() {
+        if let Some(lowered) = lowering.model(model) {
+            lowering.insert(
+                &mut items,
+                &fields,
+                &model.name,
+                model.span,
+                model.visibility,
+                ModuleItemValue::Static(Value::Model(Box::new(lowered))),
+            );
+        }
+    }
+
+    for declaration in module.ast.externs.iter() {
+        if let Some(Value::BultinType(extern_type)) =
+            lowering.type_expression(&declaration.extern_type, declaration.span)
+        {
+            lowering.insert(
+                &mut items,
+                &fields,
+                &declaration.name,
+                declaration.name_span,
+                declaration.visibility,
+                ModuleItemValue::Extern(extern_type),
+            );
+        }
+    }
+
+    let mut evaluator = Evaluator::new(&items, &fields);
+    let mut values = Vec::new();
+    for (name, field, dynamic, declared_type) in evaluated {
+        match evaluator.item(name) {
+            Ok(value) => values.push((
+                name.clone(),
+                declared_number(value, declared_type.as_ref()),
+                field.visibility,
+                dynamic,
+            )),
+            Err(error) => lowering.problems.push(LoweringProblem {
+                message: error.message,
+                span: field.value_span.into(),
+                label: "while working out this value".to_string(),
+                trace: Some(error.trace.join("\n")),
+            }),
+        }
+    }
+    for (name, value, visibility, dynamic) in values {
+        items.insert(
+            name,
+            item(
+                if dynamic {
+                    ModuleItemValue::Dynamic(value)
+                } else {
+                    ModuleItemValue::Static(value)
+                },
+                visibility,
+            ),
+        );
+    }
+
+    if lowering.problems.is_empty() {
+        Ok(AssetModule { items })
+    } else {
+        Err(LoweringError {
+            src: module.named_source(),
+            problems: lowering.problems,
+        })
+    }
+}
+
+/// `value` as the number type its field is declared with, as in `durability: dynamic Nat`,
+/// so the state keeps it that type when it's set. Other values are left as they are.
+fn declared_number(value: Value, declared: Option<&BuiltinType>) -> Value {
+    match (declared, value) {
+        (Some(BuiltinType::Nat), Value::Int(number)) if number >= 0 => Value::Nat(number as u32),
+        (Some(BuiltinType::Int), Value::Nat(number)) if i32::try_from(number).is_ok() => {
+            Value::Int(number as i32)
+        }
+        (Some(BuiltinType::Float), Value::Int(number)) => Value::Float(number as f32),
+        (Some(BuiltinType::Float), Value::Nat(number)) => Value::Float(number as f32),
+        (_, value) => value,
+    }
+}
+
+/// Lowers `expression`, found at `span`, as if it were the value of the field `name` of
+/// `module`, the way [`lower`] lowers the module's own fields. Evaluate it against the
+/// items of the lowered module.
+pub fn lower_field(
+    module: &ParsedModule,
+    models: &[&ParsedModule],
+    name: &str,
+    expression: &Expression,
+    span: Span,
+) -> Result<LazyExpression, LoweringError> {
+    let mut lowering = Lowering {
+        models,
+        problems: Vec::new(),
+    };
+    let declared_type = module
+        .ast
+        .model
+        .as_ref()
+        .and_then(|directive| upgrade::find_model(models, &directive.name))
+        .and_then(|(_, model)| model.fields.iter().find(|declared| declared.name == name))
+        .and_then(|declared| builtin_type(&declared.field_type, models).ok());
+    match lowering.field(expression, span, declared_type.as_ref()) {
+        Some(value) if lowering.problems.is_empty() => Ok(value),
+        _ => Err(LoweringError {
+            src: module.named_source(),
+            problems: lowering.problems,
+        }),
+    }
+}
+
+fn item(value: ModuleItemValue, visibility: Visibility) -> ModuleItem {
+    ModuleItem {
+        value,
+        visibility: match visibility {
+            Visibility::Public => ModuleItemVisibility::Public,
+            Visibility::Private => ModuleItemVisibility::Private,
+            Visibility::Children => ModuleItemVisibility::Children,
+        },
+    }
+}
+
+struct Lowering<'m> {
+    models: &'m [&'m ParsedModule],
+    problems: Vec<LoweringProblem>,
+}
+
+impl Lowering<'_> {
+    fn problem(&mut self, span: Span, message: impl Into<String>, label: impl Into<String>) {
+        self.problems.push(LoweringProblem {
+            message: message.into(),
+            span: span.into(),
+            label: label.into(),
+            trace: None,
+        });
+    }
+
+    /// Adds an item, unless an item or field of the same name was already declared.
+    fn insert(
+        &mut self,
+        items: &mut HashMap<String, ModuleItem>,
+        fields: &HashMap<String, LazyExpression>,
+        name: &str,
+        span: Span,
+        visibility: Visibility,
+        value: ModuleItemValue,
+    ) {
+        if items.contains_key(name) || fields.contains_key(name) {
+            self.problem(
+                span,
+                format!("`{}` is declared more than once.", name),
+                "already declared in this module",
+            );
+        } else {
+            items.insert(name.to_string(), item(value, visibility));
+        }
+    }
+
+    /// Lowers the value of a field, at `span`, to be evaluated once every item is lowered.
+    /// Symbols name variants when the field is declared as an `Either`.
+    fn field(
+        &mut self,
+        expression: &Expression,
+        span: Span,
+        declared: Option<&BuiltinType>,
+    ) -> Option<LazyExpression> {
+        if let Some(BuiltinType::Either(either)) = declared {
+            match expression {
+                Expression::Symbol(symbol) => {
+                    if let [name] = symbol.path.as_slice() {
+                        if let Some(Value::Void(())) = either.fields.get(name) {
+                            return Some(LazyExpression::Literal(Value::Variant(
+                                name.clone(),
+                                None,
+                            )));
+                        }
+                    }
+                }
+                Expression::Call { name, args, .. } => {
+                    if let (Some(Value::BultinType(payload)), [arg]) =
+                        (either.fields.get(name), args.as_slice())
+                    {
+                        let LazyExpression::Literal(value) =
+                            self.field(arg, span, Some(payload))?
+                        else {
+                            self.problem(
+                                span,
+                                "Variants can only hold literal values, for now.",
+                                "not a literal",
+                            );
+                            return None;
+                        };
+                        return Some(LazyExpression::Literal(Value::Variant(
+                            name.clone(),
+                            Some(Box::new(value)),
+                        )));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.expression(expression, &[], span)
+    }
+
+    /// Lowers an expression found at `span`, where `parameters` are in scope.
+    /// Problems are pointed at the expression itself, when it has a span of its own.
+    fn expression(
+        &mut self,
+        expression: &Expression,
+        parameters: &[String],
+        span: Span,
+    ) -> Option<LazyExpression> {
+        let span = match expression {
+            Expression::Symbol(symbol) => symbol.span,
+            Expression::Call { name_span, .. } | Expression::Member { name_span, .. } => *name_span,
+            Expression::Binary { operator_span, .. } => *operator_span,
+            _ => span,
+        };
+        Some(match expression {
+            Expression::Int(value) => LazyExpression::Literal(Value::Int(*value)),
+            Expression::Float(value) => LazyExpression::Literal(Value::Float(*value)),
+            Expression::Text(parts) => self.text(parts, parameters, span)?,
+            Expression::Call { name, args, .. } => LazyExpression::Call(
+                name.clone(),
+                args.iter()
+                    .map(|arg| self.expression(arg, parameters, span))
+                    .collect::<Option<Vec<_>>>()?,
+            ),
+            Expression::Binary {
+                operator,
+                left,
+                right,
+                ..
+            } => LazyExpression::Call(
+                operator.symbol().to_string(),
+                vec![
+                    self.expression(left, parameters, span)?,
+                    self.expression(right, parameters, span)?,
+                ],
+            ),
+            Expression::Symbol(symbol) => match symbol.path.as_slice() {
+                [name] if parameters.contains(name) => LazyExpression::ParameterRef(name.clone()),
+                path => LazyExpression::ItemRef(path.to_vec()),
+            },
+            // `self.durability` is the asset's own field.
+            Expression::Member {
+                object,
+                name,
+                args: None,
+                ..
+            } if matches!(&**object, Expression::Symbol(symbol) if symbol.path == ["self"]) => {
+                LazyExpression::ItemRef(vec![name.clone()])
+            }
+            Expression::Member {
+                object,
+                name,
+                args: Some(args),
+                ..
+            } => LazyExpression::Call(
+                name.clone(),
+                std::iter::once(&**object)
+                    .chain(args)
+                    .map(|arg| self.expression(arg, parameters, span))
+                    .collect::<Option<Vec<_>>>()?,
+            ),
+            Expression::Member { name, .. } => {
+                self.problem(
+                    span,
+                    format!(
+                        "Fields of other values, as in `.{}`, can't be built yet, only the asset's own, as in `self.{}`.",
+                        name, name
+                    ),
+                    "a field",
+                );
+                return None;
+            }
+            Expression::Boolean(_) => {
+                self.problem(span, "Booleans can't be built yet.", "a boolean");
+                return None;
+            }
+            Expression::List { .. } => {
+                self.problem(span, "Lists can't be built yet.", "a list");
+                return None;
+            }
+            Expression::Unit(_, unit) => {
+                self.problem(
+                    span,
+                    format!("Units, as in `{}`, can't be built yet.", unit),
+                    "a value with a unit",
+                );
+                return None;
+            }
+        })
+    }
+
+    /// Lowers text, which is only a literal when it doesn't interpolate anything.
+    fn text(
+        &mut self,
+        parts: &[TextPart],
+        parameters: &[String],
+        span: Span,
+    ) -> Option<LazyExpression> {
+        let mut lowered = Vec::new();
+        for part in parts {
+            lowered.push(match part {
+                TextPart::Text(text) => LazyExpression::Literal(Value::Text(ast::unescape(text))),
+                TextPart::Expression(expression) => {
+                    self.expression(expression, parameters, span)?
+                }
+            });
+        }
+
+        if parts.iter().all(|part| matches!(part, TextPart::Text(_))) {
+            let text = parts
+                .iter()
+                .map(|part| match part {
+                    TextPart::Text(text) => ast::unescape(text),
+                    TextPart::Expression(_) => unreachable!("Only text is joined."),
+                })
+                .collect();
+            return Some(LazyExpression::Literal(Value::Text(text)));
+        }
+        Some(LazyExpression::Interpolate(lowered))
+    }
+
+    /// Lowers an action. Its parameters are typed by the field of the model it implements, if any.
+    fn action(
+        &mut self,
+        action: &ast::Action,
+        declared: Option<&TypeExpression>,
+    ) -> Option<Function> {
+        let types = match declared {
+            Some(declared) => match self.type_expression(declared, action.name_span)? {
+                Value::BultinType(BuiltinType::Fn(function))
+                    if function.parameters.len() == action.parameters.len() =>
+                {
+                    function.parameters
+                }
+                _ => {
+                    self.problem(
+                        action.name_span,
+                        format!(
+                            "The model says `{}` is a `{}`, which this action doesn't match.",
+                            action.name, declared.name
+                        ),
+                        "doesn't match the model",
+                    );
+                    return None;
+                }
+            },
+            None if action.parameters.is_empty() => Vec::new(),
+            None => {
+                self.problem(
+                    action.name_span,
+                    format!(
+                        "Can't tell the types of the parameters of `{}`.",
+                        action.name
+                    ),
+                    "declare it in the model, as in `on_use: Action(Character)`",
+                );
+                return None;
+            }
+        };
+
+        let body = self.action_parts(action, &action.body)?;
+        Some(Function {
+            parameters: action
+                .parameters
+                .iter()
+                .zip(types)
+                .map(|(name, concrete_type)| {
+                    Rc::new(FnParameter {
+                        name: name.clone(),
+                        concrete_type,
+                    })
+                })
+                .collect(),
+            body,
+        })
+    }
+
+    /// Lowers `parts` of the body of `action`, into a sequence.
+    fn action_parts(
+        &mut self,
+        action: &ast::Action,
+        parts: &[ScenePart],
+    ) -> Option<LazyExpression> {
+        let mut body = Vec::new();
+        for part in parts {
+            match part {
+                ScenePart::Expression(expression) => {
+                    body.push(self.expression(expression, &action.parameters, action.name_span)?)
+                }
+                ScenePart::Conditional(conditional) => {
+                    body.push(LazyExpression::Conditional(Box::new([
+                        self.expression(
+                            &conditional.condition,
+                            &action.parameters,
+                            action.name_span,
+                        )?,
+                        self.action_parts(action, &conditional.content)?,
+                        self.action_parts(action, &conditional.otherwise)?,
+                    ])))
+                }
+                // Assignments are built as the calls to `set` they stand for.
+                ScenePart::Assignment(assignment) => {
+                    let target =
+                        self.expression(&assignment.target, &action.parameters, assignment.span)?;
+                    let value =
+                        self.expression(&assignment.value, &action.parameters, assignment.span)?;
+                    let value = match assignment.operator {
+                        Some(operator) => LazyExpression::Call(
+                            operator.symbol().to_string(),
+                            vec![target.clone(), value],
+                        ),
+                        None => value,
+                    };
+                    body.push(LazyExpression::Call("set".to_string(), vec![target, value]))
+                }
+                // Lines are played by the runtime. Built, they're their text.
+                ScenePart::Dialogue(ast::Dialogue { parts })
+                | ScenePart::Narration(ast::Narration { parts }) => {
+                    body.push(self.text(parts, &action.parameters, action.name_span)?)
+                }
+                ScenePart::SpeakerChangeMarker(_) | ScenePart::Comment(_) => {}
+                ScenePart::Prompt(_) => {
+                    self.problem(
+                        action.name_span,
+                        format!(
+                            "`{}` has a prompt, which actions can't have yet.",
+                            action.name
+                        ),
+                        "move its prompt into a scene",
+                    );
+                    return None;
+                }
+            }
+        }
+        Some(LazyExpression::Sequence(body))
+    }
+
+    fn model(&mut self, model: &ast::Model) -> Option<Model> {
+        let mut fields = HashMap::new();
+        for field in model.fields.iter() {
+            let field_type = self.type_expression(&field.field_type, field.span);
+            fields.insert(field.name.clone(), field_type?);
+        }
+        Some(Model {
+            name: model.name.clone(),
+            fields,
+        })
+    }
+
+    /// Lowers a type, found at `span`.
+    fn type_expression(&mut self, expression: &TypeExpression, span: Span) -> Option<Value> {
+        match builtin_type(expression, self.models) {
+            Ok(builtin) => Some(Value::BultinType(builtin)),
+            Err(message) => {
+                self.problem(span, message, "expected a builtin type or a model");
+                None
+            }
+        }
+    }
+}
+
+/// Lowers a type, looking up models among `models`, or says what's wrong with it.
+///
+/// `Action(A, B)` is a function of `A` and `B`, and `Signal` a function of nothing.
+/// `Either(Up, Down, Some(Nat))` has a variant for each argument, which can hold a value.
+/// Other models are `Asset`s.
+pub fn builtin_type(
+    expression: &TypeExpression,
+    models: &[&ParsedModule],
+) -> Result<BuiltinType, String> {
+    let lower = |expression| builtin_type(expression, models).map(Value::BultinType);
+    Ok(
+        match (expression.name.as_str(), expression.args.as_slice()) {
+            ("Nat", []) => BuiltinType::Nat,
+            ("Int", []) => BuiltinType::Int,
+            ("Float", []) => BuiltinType::Float,
+            ("Text", []) => BuiltinType::Text,
+            ("Signal", []) => BuiltinType::Fn(Box::new(FnType {
+                parameters: Vec::new(),
+                output: Value::Void(()),
+            })),
+            ("Action", args) => BuiltinType::Fn(Box::new(FnType {
+                parameters: args.iter().map(lower).collect::<Result<Vec<_>, _>>()?,
+                output: Value::Void(()),
+            })),
+            ("Either", variants) if !variants.is_empty() => {
+                let mut fields = HashMap::new();
+                for variant in variants {
+                    let payload = match variant.args.as_slice() {
+                        [] => Value::Void(()),
+                        [payload] => lower(payload)?,
+                        _ => {
+                            return Err(format!(
+                                "The variant `{}` can only hold one value.",
+                                variant.name
+                            ))
+                        }
+                    };
+                    fields.insert(variant.name.clone(), payload);
+                }
+                BuiltinType::Either(Box::new(Either { fields }))
+            }
+            (name, []) if upgrade::find_model(models, name).is_some() => BuiltinType::Asset,
+            (name, _) => return Err(format!("Unknown type `{}`.", name)),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lower;
+    use crate::{
+        introspec::repr::{BuiltinType, LazyExpression, ModuleItemValue, Value},
+        parser,
+        workspace::ParsedModule,
+    };
+
+    fn module(file_name: &str, source: &str) -> ParsedModule {
+        ParsedModule {
+            file_name: file_name.to_string(),
+            source: source.to_string(),
+            ast: parser::parse_module(file_name, source).unwrap(),
+        }
+    }
+
+    const MODELS: &str = r#"model Character
+    name: Text
+end
+
+model Item
+    name: Text
+    durability: dynamic Nat
+    on_use: Action(Character, Character)
+end
+"#;
+
+    #[test]
+    fn lowers_assets() {
+        let models = module("models.sol", MODELS);
+        let sword = module(
+            "sword.sol",
+            "@model Item\nname = \"Sword\"\ndurability = 20\n\naction on_use(user, target)\n    damage(::target, 5)\nend\n",
+        );
+
+        let lowered = lower(&sword, &[&models]).unwrap();
+        assert!(matches!(
+            lowered.items["name"].value,
+            ModuleItemValue::Static(Value::Text(ref name)) if name == "Sword"
+        ));
+        assert!(matches!(
+            lowered.items["durability"].value,
+            ModuleItemValue::Dynamic(Value::Nat(20))
+        ));
+        let ModuleItemValue::Static(Value::Function(on_use)) = &lowered.items["on_use"].value
+        else {
+            panic!("Actions are functions.");
+        };
+        assert_eq!(on_use.parameters[1].name, "target");
+        assert!(matches!(
+            on_use.parameters[1].concrete_type,
+            Value::BultinType(BuiltinType::Asset)
+        ));
+        let LazyExpression::Sequence(body) = &on_use.body else {
+            panic!("Action bodies are sequences.");
+        };
+        assert!(matches!(
+            &body[0],
+            LazyExpression::Call(name, args)
+                if name == "damage" && matches!(&args[0], LazyExpression::ParameterRef(arg) if arg == "target")
+        ));
+
+        let lowered = lower(&models, &[&models]).unwrap();
+        let ModuleItemValue::Static(Value::Model(item)) = &lowered.items["Item"].value else {
+            panic!("Models are values.");
+        };
+        assert!(matches!(
+            item.fields["durability"],
+            Value::BultinType(BuiltinType::Nat)
+        ));
+    }
+
+    #[test]
+    fn evaluates_fields() {
+        let models = module(
+            "models.sol",
+            "model Loot\n    damage: Nat\n    split: Action(Nat, Nat)\nend\n",
+        );
+        let loot = module(
+            "loot.sol",
+            "@model Loot\ndamage = (2 + 3) * 4\nlabel = \"Deals {::damage}\"\nshare = split(10, 0)\n\naction split(amount, ways)\n    ::amount / ::ways\nend\n",
+        );
+
+        let error = lower(&loot, &[&models]).unwrap_err();
+        let [problem] = error.problems.as_slice() else {
+            panic!("Only `share` can't be worked out.");
+        };
+        assert_eq!(problem.message, "Division by zero.");
+        assert_eq!(
+            problem.trace.as_deref(),
+            Some("in `split(10, 0)`\nin the field `share`")
+        );
+
+        let loot = module(
+            "loot.sol",
+            "@model Loot\ndamage = (2 + 3) * 4\nlabel = \"Deals {::damage}\"\n",
+        );
+        let lowered = lower(&loot, &[&models]).unwrap();
+        assert!(matches!(
+            lowered.items["label"].value,
+            ModuleItemValue::Static(Value::Text(ref label)) if label == "Deals 20"
+        ));
+    }
+
+    #[test]
+    fn reports_what_cant_be_lowered() {
+        let broken = module(
+            "broken.sol",
+            "model Thing\n    size: Huge\nend\n\nsizes = [1, 2]\n\naction shout(volume)\n    - Hey!\nend\n",
+        );
+
+        let error = lower(&broken, &[&broken]).unwrap_err();
+        let messages = error
+            .problems
+            .iter()
+            .map(|problem| problem.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "Lists can't be built yet.",
+                "Can't tell the types of the parameters of `shout`.",
+                "Unknown type `Huge`.",
+            ]
+        );
+
+        // Problems in actions are pointed at what's wrong, not at the action.
+        let source = "model Thing\n    poke: Action(Thing)\nend\n";
+        let model = module("Thing.sol", source);
+        let source = "@model Thing\n\naction poke(other)\n    other.size -= 1\nend\n";
+        let thing = module("thing.sol", source);
+        let error = lower(&thing, &[&model]).unwrap_err();
+        let [problem] = error.problems.as_slice() else {
+            panic!("There's one problem.");
+        };
+        assert_eq!(problem.span, (source.find("size").unwrap(), 4).into());
+    }
+}
@@ -1,2 +1,5 @@
+pub mod eval;
+pub mod lower;
 pub mod project;
-pub mod repr;
\ No newline at end of file
+pub mod repr;
+pub mod types;
@@ -1,34 +1,294 @@
+use crate::workspace::{
+    manifest::{ManifestError, WorkspaceManifest},
+    SOL_MANIFEST_FILENAME,
+};
 use miette::Diagnostic;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
 use thiserror::Error;
 
 /// Compilation of `.sol` can only happen within
 /// a valid project.
 pub struct Project {
     pub index_directory: PathBuf,
+    pub manifest: WorkspaceManifest,
+    /// Modules found in the manifest's source directories, sorted by path.
+    pub modules: Vec<ProjectModule>,
+    /// Sub-workspaces nested inside the source directories.
+    ///
+    /// Their modules belong to them, and not to this project.
+    pub packages: Vec<Project>,
+}
+
+/// A `.sol` file that is part of a project.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectModule {
+    pub path: ModulePath,
+    pub file: PathBuf,
+}
+
+/// The dotted name a module is referred to by, as in `weapons.iron_sword`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ModulePath(pub Vec<String>);
+
+impl ModulePath {
+    /// Derives a module path from a file path relative to a source directory,
+    /// so that `weapons/iron_sword.sol` becomes `weapons.iron_sword`.
+    pub fn from_relative_file(path: &Path) -> Option<ModulePath> {
+        if path.extension()? != "sol" {
+            return None;
+        }
+
+        let mut nodes = path
+            .parent()?
+            .components()
+            .map(|component| component.as_os_str().to_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()?;
+        nodes.push(path.file_stem()?.to_str()?.to_string());
+        Some(ModulePath(nodes))
+    }
+
+    /// Parses a dotted module path, as in `weapons.iron_sword`.
+    pub fn parse(path: &str) -> Option<ModulePath> {
+        let path = ModulePath(path.split('.').map(str::to_string).collect());
+        path.is_valid().then_some(path)
+    }
+
+    /// Whether every node of the path is an identifier.
+    pub fn is_valid(&self) -> bool {
+        !self.0.is_empty()
+            && self.0.iter().all(|node| {
+                let mut chars = node.chars();
+                chars
+                    .next()
+                    .is_some_and(|first| first.is_alphabetic() || first == '_')
+                    && chars.all(|char| char.is_alphanumeric() || char == '_')
+            })
+    }
+}
+
+impl Display for ModulePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.join("."))
+    }
+}
+
+/// What a `using` refers to.
+pub enum Import<'p> {
+    /// A whole package, as in `using shared_assets`.
+    Package(&'p Project),
+    /// A single module of a package, as in `using shared_assets.icons`.
+    Module(&'p Project, &'p ProjectModule),
 }
 
 #[derive(Error, Debug, Diagnostic)]
-#[error("Error loading project.")]
+#[error("Error loading project: {0}")]
 pub enum ProjectLoadError {
     IO(#[from] std::io::Error),
+    #[diagnostic(transparent)]
+    Manifest(#[from] ManifestError),
+    #[diagnostic(transparent)]
+    NotFound(#[from] WorkspaceNotFound),
+    #[diagnostic(transparent)]
+    InvalidModulePath(#[from] InvalidModulePath),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("Could not find an `index.sol` in {start:?} or any of its parents.")]
+#[diagnostic(help("Run `sol init` to create a new workspace."))]
+pub struct WorkspaceNotFound {
+    pub start: PathBuf,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("{file:?} can't be used as a module.")]
+#[diagnostic(help(
+    "Module file and directory names must be identifiers, as in `weapons/iron_sword.sol`."
+))]
+pub struct InvalidModulePath {
+    pub file: PathBuf,
+}
+
+/// Walks up from `start` looking for the nearest directory with an `index.sol`.
+pub fn find_workspace_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|directory| directory.join(SOL_MANIFEST_FILENAME).is_file())
+        .map(Path::to_path_buf)
 }
 
 impl Project {
-    pub fn load_from_directory(path: PathBuf) -> Result<(), ProjectLoadError> {
-        let mut dir = fs::read_dir(path)?;
-
-        if dir.any(|entry| {
-            entry
-                .map(|entry| entry.file_name() == "index.sol")
-                .unwrap_or(false)
-        }) {
-            println!("Some index.sol");
-        } else {
-            println!("No index.sol");
+    /// Loads the project owning `start`, which can be any directory inside it.
+    pub fn discover(start: &Path) -> Result<Project, ProjectLoadError> {
+        let start = start.canonicalize()?;
+        let root = find_workspace_root(&start).ok_or(WorkspaceNotFound { start })?;
+        Self::load_from_directory(root)
+    }
+
+    pub fn load_from_directory(path: PathBuf) -> Result<Project, ProjectLoadError> {
+        let manifest = WorkspaceManifest::load(&path.join(SOL_MANIFEST_FILENAME))?;
+
+        let mut project = Project {
+            index_directory: path,
+            manifest,
+            modules: Vec::new(),
+            packages: Vec::new(),
+        };
+
+        for source in project.manifest.sources.clone() {
+            let root = project.index_directory.join(source);
+            if root.is_dir() {
+                project.collect(&root, &root)?;
+            }
+        }
+        project.modules.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(project)
+    }
+
+    fn collect(&mut self, source_root: &Path, directory: &Path) -> Result<(), ProjectLoadError> {
+        let mut entries = fs::read_dir(directory)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+
+        for entry in entries {
+            if entry.is_dir() {
+                if entry.join(SOL_MANIFEST_FILENAME).is_file() {
+                    self.packages.push(Project::load_from_directory(entry)?);
+                } else {
+                    self.collect(source_root, &entry)?;
+                }
+            } else if entry
+                .extension()
+                .is_some_and(|extension| extension == "sol")
+                && entry != self.index_directory.join(SOL_MANIFEST_FILENAME)
+            {
+                let relative = entry
+                    .strip_prefix(source_root)
+                    .expect("Entries are read from inside the source root.");
+                let path = ModulePath::from_relative_file(relative)
+                    .filter(ModulePath::is_valid)
+                    .ok_or_else(|| InvalidModulePath {
+                        file: entry.clone(),
+                    })?;
+                self.modules.push(ProjectModule { path, file: entry });
+            }
         }
 
         Ok(())
     }
+
+    /// Finds one of this project's own modules by its path.
+    pub fn module(&self, path: &ModulePath) -> Option<&ProjectModule> {
+        self.modules.iter().find(|module| &module.path == path)
+    }
+
+    /// Resolves the path of a `using` in one of this project's modules.
+    ///
+    /// Paths starting with the name of one of `dependencies` (or of a nested
+    /// package) import from it, anything else is a module of this project.
+    pub fn resolve_import<'p>(
+        &'p self,
+        dependencies: &[&'p Project],
+        path: &[String],
+    ) -> Option<Import<'p>> {
+        let (first, rest) = path.split_first()?;
+        let package = dependencies
+            .iter()
+            .copied()
+            .chain(self.packages.iter())
+            .find(|package| &package.manifest.name == first);
+
+        match package {
+            Some(package) if rest.is_empty() => Some(Import::Package(package)),
+            Some(package) => package
+                .module(&ModulePath(rest.to_vec()))
+                .map(|module| Import::Module(package, module)),
+            None => self
+                .module(&ModulePath(path.to_vec()))
+                .map(|module| Import::Module(self, module)),
+        }
+    }
+
+    /// This project and all of its nested packages, depth-first.
+    pub fn all_packages(&self) -> Vec<&Project> {
+        let mut packages = vec![self];
+        for package in self.packages.iter() {
+            packages.extend(package.all_packages());
+        }
+        packages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_workspace_root, ModulePath, Project};
+    use std::{fs, path::Path};
+    use tempfile::tempdir;
+
+    #[test]
+    fn module_path_from_file() {
+        assert_eq!(
+            ModulePath::from_relative_file(Path::new("weapons/iron_sword.sol"))
+                .unwrap()
+                .to_string(),
+            "weapons.iron_sword"
+        );
+        assert_eq!(ModulePath::from_relative_file(Path::new("notes.txt")), None);
+        assert!(
+            !ModulePath::from_relative_file(Path::new("my weapons/sword.sol"))
+                .unwrap()
+                .is_valid()
+        );
+    }
+
+    #[test]
+    fn discovers_nested_workspaces() {
+        let temp = tempdir().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        let write = |path: &str, contents: &str| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        };
+        write("index.sol", "name = \"game\"\nversion = \"0.1.0\"\n");
+        write("src/main.sol", "");
+        write("src/weapons/iron_sword.sol", "");
+        write(
+            "src/dlc/index.sol",
+            "name = \"dlc\"\nversion = \"0.1.0\"\nsources = [\"\"]\n",
+        );
+        write("src/dlc/boss.sol", "");
+
+        let deep = root.join("src/weapons");
+        assert_eq!(find_workspace_root(&deep), Some(root.clone()));
+
+        let project = Project::discover(&deep).unwrap();
+        let modules = project
+            .modules
+            .iter()
+            .map(|module| module.path.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(modules, vec!["main", "weapons.iron_sword"]);
+        assert_eq!(project.packages.len(), 1);
+        assert_eq!(project.packages[0].manifest.name, "dlc");
+        assert_eq!(project.packages[0].modules[0].path.to_string(), "boss");
+
+        let nested = Project::discover(&root.join("src/dlc")).unwrap();
+        assert_eq!(nested.manifest.name, "dlc");
+
+        let empty = tempdir().unwrap();
+        let Err(error) = Project::discover(empty.path()) else {
+            panic!("There's no workspace to discover.");
+        };
+        assert!(error
+            .to_string()
+            .starts_with("Error loading project: Could not find an `index.sol`"));
+    }
 }
@@ -8,14 +8,14 @@ use std::{collections::HashMap, rc::Rc};
 /// as well as declarations of assets.
 #[derive(Debug, Clone)]
 pub struct AssetModule {
-    pub items: HashMap<String, Value>,
+    pub items: HashMap<String, ModuleItem>,
 }
 
 /// An item in a module.
 #[derive(Debug, Clone)]
 pub struct ModuleItem {
-    value: ModuleItemValue,
-    visibility: ModuleItemVisibility,
+    pub value: ModuleItemValue,
+    pub visibility: ModuleItemVisibility,
 }
 
 #[derive(Debug, Clone)]
@@ -26,7 +26,8 @@ pub enum ModuleItemValue {
     Dynamic(Value),
     /// Extern — No value supplied by the `.sol` side, so it's up to the
     /// compilation target plugin to interpret what to do here.
-    Extern,
+    /// Holds the type the value must have, as in `extern player_name: Text`.
+    Extern(BuiltinType),
 }
 
 #[derive(Debug, Clone)]
@@ -78,11 +79,19 @@ pub enum Value {
     Model(Box<Model>),
     Asset(Box<AssetModule>),
     Function(Box<Function>),
+    /// One of the variants of an `Either`, as in `::Up`, or `Some(3)` when it holds a value.
+    Variant(String, Option<Box<Value>>),
+    /// An asset the game refers to, by its path, as in `::chars::slime`.
+    AssetRef(Vec<String>),
+    /// An object of the game's own, by the handle the game knows it by.
+    Host(u64),
 }
 
 /// A Model is a schematic for an Asset.
 #[derive(Debug, Clone)]
 pub struct Model {
+    pub name: String,
+    /// The type of each field.
     pub fields: HashMap<String, Value>,
 }
 
@@ -100,18 +109,20 @@ pub struct Either {
 
 #[derive(Debug, Clone)]
 pub struct FnType {
-    pub input: Value,
+    pub parameters: Vec<Value>,
     pub output: Value,
 }
 
 #[derive(Debug, Clone)]
 pub struct Function {
-    pub parameters: HashMap<String, Rc<FnParameter>>,
+    /// In the order arguments are passed in.
+    pub parameters: Vec<Rc<FnParameter>>,
     pub body: LazyExpression,
 }
 
 #[derive(Debug, Clone)]
 pub struct FnParameter {
+    pub name: String,
     pub concrete_type: Value,
 }
 
@@ -124,6 +135,14 @@ pub enum LazyExpression {
     ParameterRef(String),
     /// Calls a function, by name, with a set of parameters.
     Call(String, Vec<LazyExpression>),
+    /// Returns the value of an item, as in `::weapons::sword::name`.
+    ItemRef(Vec<String>),
+    /// Joins the text of every part, as in `"Hello, {player_name()}!"`.
+    Interpolate(Vec<LazyExpression>),
+    /// Evaluates every expression in order, returning the value of the last one.
+    Sequence(Vec<LazyExpression>),
+    /// Evaluates the second expression when the first is `::True`, and the third otherwise.
+    Conditional(Box<[LazyExpression; 3]>),
 }
 
 /// A binding to some value.
@@ -0,0 +1,696 @@
+//! Type checking of modules, against the types of [`repr`](super::repr).
+//!
+//! Every expression is given a [`BuiltinType`], except those whose type can't be known yet,
+//! like calls to functions provided by the game. Fields of assets must have the type their
+//! model declares, `Nat`s can't be negative, and calls to actions and signals must pass
+//! arguments of the right types. `Either`s are given one of their variants, as in `::Up`,
+//! or `Some(3)` for variants that hold a value.
+use super::{
+    lower::builtin_type,
+    repr::{BuiltinType, Either, FnType, Value},
+};
+use crate::{
+    parser::ast::{self, Expression, Module, Operator, ScenePart, Span, TextPart},
+    workspace::{upgrade, ParsedModule},
+};
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("This module has type errors.")]
+#[diagnostic(code(sol::type_error))]
+pub struct TypeError {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[related]
+    pub problems: Vec<TypeProblem>,
+}
+
+/// A single type error, pointing at the expression it's about.
+#[derive(Error, Debug, Diagnostic)]
+#[error("{message}")]
+pub struct TypeProblem {
+    pub message: String,
+    #[label("{label}")]
+    pub span: SourceSpan,
+    pub label: String,
+}
+
+/// A type, or `None` if it can't be known yet.
+type Type = Option<BuiltinType>;
+
+/// Checks the types of every expression of `module`, an asset already upgraded to
+/// its model's current version, or a module with no model.
+pub fn check(module: &ParsedModule, models: &[&ParsedModule]) -> Result<(), TypeError> {
+    let mut checker = Checker {
+        module: &module.ast,
+        models,
+        model: module
+            .ast
+            .model
+            .as_ref()
+            .and_then(|directive| upgrade::find_model(models, &directive.name))
+            .map(|(_, model)| model),
+        visiting: Vec::new(),
+        problems: Vec::new(),
+    };
+
+    let mut fields = module.ast.fields.iter().collect::<Vec<_>>();
+    fields.sort_by_key(|(_, field)| field.name_span.start);
+    for (name, field) in fields {
+        match checker.declared(name) {
+            Some(declared) => checker.expect(&field.value, &declared, &[], field.value_span),
+            None => {
+                checker.infer(&field.value, &[], field.value_span);
+            }
+        }
+    }
+
+    for action in module.ast.actions.iter() {
+        let types = match checker.declared(&action.name) {
+            Some(BuiltinType::Fn(function)) => function
+                .parameters
+                .into_iter()
+                .map(|parameter| match parameter {
+                    Value::BultinType(builtin) => Some(builtin),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let scope = action
+            .parameters
+            .iter()
+            .cloned()
+            .zip(types.into_iter().chain(std::iter::repeat(None)))
+            .collect::<Vec<_>>();
+        checker.parts(&action.body, &scope, action.name_span);
+    }
+
+    for scene in module.ast.scenes.iter() {
+        checker.parts(&scene.content, &[], Span::default());
+    }
+
+    for handler in module.ast.handlers.iter() {
+        let model = upgrade::find_model(models, &handler.model)
+            .map(|(_, model)| model)
+            .or_else(|| {
+                module
+                    .ast
+                    .models
+                    .iter()
+                    .find(|model| model.name == handler.model)
+            });
+        match model {
+            None => checker.problem(
+                handler.span,
+                format!("There's no model `{}`.", handler.model),
+                "handled here",
+            ),
+            Some(model)
+                if !model.fields.iter().any(|field| {
+                    field.name == handler.signal && field.field_type.name == "Signal"
+                }) =>
+            {
+                checker.problem(
+                    handler.span,
+                    format!("`{}` has no signal `{}`.", model.name, handler.signal),
+                    format!("signals are declared as `{}: Signal`", handler.signal),
+                )
+            }
+            Some(_) => {}
+        }
+        checker.parts(&handler.body, &[], handler.span);
+    }
+
+    if checker.problems.is_empty() {
+        Ok(())
+    } else {
+        Err(TypeError {
+            src: module.named_source(),
+            problems: checker.problems,
+        })
+    }
+}
+
+/// How a type is written in Sol.
+pub fn describe(builtin: &BuiltinType) -> String {
+    let value = |value: &Value| match value {
+        Value::BultinType(builtin) => describe(builtin),
+        _ => "?".to_string(),
+    };
+    match builtin {
+        BuiltinType::BuiltinType => "Type".to_string(),
+        BuiltinType::Nat => "Nat".to_string(),
+        BuiltinType::Int => "Int".to_string(),
+        BuiltinType::Float => "Float".to_string(),
+        BuiltinType::Text => "Text".to_string(),
+        BuiltinType::Model(model) => model.name.clone(),
+        BuiltinType::Struct => "Struct".to_string(),
+        BuiltinType::Either(either) => {
+            let variants = variants(either)
+                .into_iter()
+                .map(|(name, payload)| match payload {
+                    Value::Void(()) => name.to_string(),
+                    payload => format!("{}({})", name, value(payload)),
+                })
+                .collect::<Vec<_>>();
+            format!("Either({})", variants.join(", "))
+        }
+        BuiltinType::Fn(function) => format!(
+            "Action({})",
+            function
+                .parameters
+                .iter()
+                .map(value)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        BuiltinType::Asset => "Asset".to_string(),
+    }
+}
+
+/// Whether `expression` is `self`, the asset the module is.
+fn is_self(expression: &Expression) -> bool {
+    matches!(expression, Expression::Symbol(symbol) if symbol.path == ["self"])
+}
+
+/// The variants of `either`, sorted by name.
+fn variants(either: &Either) -> Vec<(&String, &Value)> {
+    let mut variants = either.fields.iter().collect::<Vec<_>>();
+    variants.sort_by_key(|(name, _)| *name);
+    variants
+}
+
+/// Whether a value of type `actual` can be used where `expected` is.
+fn fits(actual: &BuiltinType, expected: &BuiltinType) -> bool {
+    match (actual, expected) {
+        (BuiltinType::Nat, BuiltinType::Nat | BuiltinType::Int) => true,
+        (BuiltinType::Model(actual), BuiltinType::Model(expected)) => actual.name == expected.name,
+        (BuiltinType::Either(actual), BuiltinType::Either(expected)) => {
+            let names = |either: &Either| {
+                variants(either)
+                    .into_iter()
+                    .map(|(name, _)| name.clone())
+                    .collect::<Vec<_>>()
+            };
+            names(actual) == names(expected)
+        }
+        (BuiltinType::Fn(actual), BuiltinType::Fn(expected)) => {
+            describe(&BuiltinType::Fn(actual.clone()))
+                == describe(&BuiltinType::Fn(expected.clone()))
+        }
+        (actual, expected) => std::mem::discriminant(actual) == std::mem::discriminant(expected),
+    }
+}
+
+struct Checker<'m> {
+    module: &'m Module,
+    models: &'m [&'m ParsedModule],
+    /// The model the module is an asset of.
+    model: Option<&'m ast::Model>,
+    /// Fields whose types are being inferred, so that fields referring to each other terminate.
+    visiting: Vec<String>,
+    problems: Vec<TypeProblem>,
+}
+
+impl Checker<'_> {
+    fn problem(&mut self, span: Span, message: impl Into<String>, label: impl Into<String>) {
+        self.problems.push(TypeProblem {
+            message: message.into(),
+            span: span.into(),
+            label: label.into(),
+        });
+    }
+
+    /// The type the module's model declares `name` as.
+    fn declared(&self, name: &str) -> Type {
+        let field = self.model?.fields.iter().find(|field| field.name == name)?;
+        builtin_type(&field.field_type, self.models).ok()
+    }
+
+    /// The type of an item of this module.
+    fn item(&mut self, name: &str) -> Type {
+        if let Some(declared) = self.declared(name) {
+            return Some(declared);
+        }
+        if let Some(field) = self.module.fields.get(name) {
+            if self.visiting.iter().any(|visiting| visiting == name) {
+                return None;
+            }
+            self.visiting.push(name.to_string());
+            // Problems are reported where the field is declared, and not where it's used.
+            let problems = self.problems.len();
+            let inferred = self.infer(&field.value, &[], field.value_span);
+            self.problems.truncate(problems);
+            self.visiting.pop();
+            return inferred;
+        }
+        if let Some(action) = self
+            .module
+            .actions
+            .iter()
+            .find(|action| action.name == name)
+        {
+            return Some(BuiltinType::Fn(Box::new(FnType {
+                parameters: action.parameters.iter().map(|_| Value::Void(())).collect(),
+                output: Value::Void(()),
+            })));
+        }
+        if self.module.models.iter().any(|model| model.name == name) {
+            return Some(BuiltinType::BuiltinType);
+        }
+        if let Some(declaration) = self
+            .module
+            .externs
+            .iter()
+            .find(|declaration| declaration.name == name)
+        {
+            return builtin_type(&declaration.extern_type, self.models).ok();
+        }
+        None
+    }
+
+    /// Checks every expression of a scene or action, where `scope` is in scope.
+    /// `at` is where problems are pointed at when expressions have no span of their own.
+    fn parts(&mut self, parts: &[ScenePart], scope: &[(String, Type)], at: Span) {
+        for part in parts {
+            match part {
+                ScenePart::Expression(expression) => {
+                    self.infer(expression, scope, at);
+                }
+                ScenePart::Dialogue(ast::Dialogue { parts })
+                | ScenePart::Narration(ast::Narration { parts }) => self.text(parts, scope, at),
+                ScenePart::Prompt(prompt) => {
+                    if let Some(text) = prompt.text.as_ref() {
+                        self.text(text, scope, at);
+                    }
+                    for option in prompt.options.iter() {
+                        self.text(&option.text, scope, at);
+                        if let Some(condition) = &option.condition {
+                            self.infer(condition, scope, at);
+                        }
+                        self.parts(&option.content, scope, at);
+                    }
+                }
+                ScenePart::Conditional(conditional) => {
+                    self.infer(&conditional.condition, scope, at);
+                    self.parts(&conditional.content, scope, at);
+                    self.parts(&conditional.otherwise, scope, at);
+                }
+                ScenePart::Assignment(assignment) => self.assignment(assignment, scope),
+                ScenePart::SpeakerChangeMarker(_) | ScenePart::Comment(_) => {}
+            }
+        }
+    }
+
+    /// Checks that an assignment sets a dynamic field, to a value it can hold.
+    fn assignment(&mut self, assignment: &ast::Assignment, scope: &[(String, Type)]) {
+        let field = match &assignment.target {
+            Expression::Symbol(symbol) => match symbol.path.as_slice() {
+                [name] if !scope.iter().any(|(parameter, _)| parameter == name) => Some(name),
+                _ => None,
+            },
+            Expression::Member { object, name, .. } if is_self(object) => Some(name),
+            _ => None,
+        };
+        if let Some(name) = field.filter(|name| self.dynamic(name) == Some(false)) {
+            self.problem(
+                assignment.span,
+                format!("`{}` isn't dynamic, so it can't be set.", name),
+                "set here",
+            );
+        }
+        match assignment.operator {
+            // `self.durability -= 1` is checked as `self.durability - 1`, which is converted back
+            // when it's set, as long as it fits.
+            Some(operator) => {
+                self.infer(
+                    &Expression::Binary {
+                        operator,
+                        left: Box::new(assignment.target.clone()),
+                        right: Box::new(assignment.value.clone()),
+                        operator_span: assignment.span,
+                    },
+                    scope,
+                    assignment.span,
+                );
+            }
+            None => match self.infer(&assignment.target, scope, assignment.span) {
+                Some(target) => self.expect(&assignment.value, &target, scope, assignment.span),
+                None => {
+                    self.infer(&assignment.value, scope, assignment.span);
+                }
+            },
+        }
+    }
+
+    /// Whether the field `name` of the module is dynamic, if it's a field of the module.
+    fn dynamic(&self, name: &str) -> Option<bool> {
+        let field = self.module.fields.get(name)?;
+        let declared = self
+            .model
+            .and_then(|model| model.fields.iter().find(|declared| declared.name == name));
+        Some(field.dynamic || declared.is_some_and(|declared| declared.dynamic))
+    }
+
+    /// Checks the interpolations of text. They can be of any type.
+    fn text(&mut self, parts: &[TextPart], scope: &[(String, Type)], at: Span) {
+        for part in parts {
+            if let TextPart::Expression(expression) = part {
+                self.infer(expression, scope, at);
+            }
+        }
+    }
+
+    /// Finds the type of `expression`, checking the expressions inside it.
+    fn infer(&mut self, expression: &Expression, scope: &[(String, Type)], at: Span) -> Type {
+        match expression {
+            Expression::Int(value) if *value >= 0 => Some(BuiltinType::Nat),
+            Expression::Int(_) => Some(BuiltinType::Int),
+            Expression::Float(_) => Some(BuiltinType::Float),
+            Expression::Text(parts) => {
+                self.text(parts, scope, at);
+                Some(BuiltinType::Text)
+            }
+            Expression::Unit(expression, _) => self.infer(expression, scope, at),
+            Expression::Binary {
+                operator,
+                left,
+                right,
+                operator_span,
+            } => {
+                let left = self.infer(left, scope, at);
+                let right = self.infer(right, scope, at);
+                if !operator.is_arithmetic() {
+                    // Comparisons give `::True` or `::False`, which have no type of their own yet.
+                    let ordered = !matches!(
+                        operator,
+                        Operator::Equal | Operator::NotEqual | Operator::And | Operator::Or
+                    );
+                    let number = |operand: &Type| {
+                        operand.as_ref().is_none_or(|operand| {
+                            matches!(
+                                operand,
+                                BuiltinType::Nat | BuiltinType::Int | BuiltinType::Float
+                            )
+                        })
+                    };
+                    if ordered && !(number(&left) && number(&right)) {
+                        self.problem(
+                            *operator_span,
+                            format!("Only numbers can be compared with `{}`.", operator.symbol()),
+                            "expected two numbers",
+                        );
+                    }
+                    return None;
+                }
+                let (left, right) = (left?, right?);
+                match (&left, &right) {
+                    (BuiltinType::Nat, BuiltinType::Nat) if *operator != Operator::Subtract => {
+                        Some(BuiltinType::Nat)
+                    }
+                    (BuiltinType::Nat | BuiltinType::Int, BuiltinType::Nat | BuiltinType::Int) => {
+                        Some(BuiltinType::Int)
+                    }
+                    (BuiltinType::Float, BuiltinType::Float) => Some(BuiltinType::Float),
+                    _ => {
+                        self.problem(
+                            *operator_span,
+                            format!(
+                                "Can't apply `{}` to `{}` and `{}`.",
+                                operator.symbol(),
+                                describe(&left),
+                                describe(&right)
+                            ),
+                            "expected two numbers of the same kind",
+                        );
+                        None
+                    }
+                }
+            }
+            Expression::List { items, .. } => {
+                for item in items {
+                    self.infer(item, scope, at);
+                }
+                None
+            }
+            Expression::Boolean(_) => None,
+            Expression::Member {
+                object,
+                name,
+                args: None,
+                ..
+            } if is_self(object) => self.item(name),
+            Expression::Member {
+                object,
+                args,
+                name_span,
+                ..
+            } => {
+                self.infer(object, scope, at);
+                for arg in args.iter().flatten() {
+                    self.infer(arg, scope, *name_span);
+                }
+                None
+            }
+            Expression::Symbol(symbol) => match symbol.path.as_slice() {
+                [name] => match scope.iter().find(|(parameter, _)| parameter == name) {
+                    Some((_, parameter)) => parameter.clone(),
+                    None => self.item(name),
+                },
+                _ => None,
+            },
+            Expression::Call {
+                name,
+                args,
+                name_span,
+            } => {
+                let function = match scope.iter().find(|(parameter, _)| parameter == name) {
+                    Some((_, parameter)) => parameter.clone(),
+                    None => self.item(name),
+                };
+                match function {
+                    Some(BuiltinType::Fn(function)) => {
+                        self.arguments(name, &function, args, scope, *name_span);
+                        match function.output {
+                            Value::BultinType(output) => Some(output),
+                            _ => None,
+                        }
+                    }
+                    Some(other) => {
+                        self.problem(
+                            *name_span,
+                            format!("`{}` is a {}, and can't be called.", name, describe(&other)),
+                            "not an action",
+                        );
+                        None
+                    }
+                    None => {
+                        for arg in args {
+                            self.infer(arg, scope, *name_span);
+                        }
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks the arguments of a call to `function`, whose name is at `span`.
+    fn arguments(
+        &mut self,
+        name: &str,
+        function: &FnType,
+        args: &[Expression],
+        scope: &[(String, Type)],
+        span: Span,
+    ) {
+        if args.len() != function.parameters.len() {
+            self.problem(
+                span,
+                format!(
+                    "`{}` takes {} arguments, but is given {}.",
+                    name,
+                    function.parameters.len(),
+                    args.len()
+                ),
+                "wrong number of arguments",
+            );
+        }
+        for (arg, parameter) in args.iter().zip(function.parameters.iter()) {
+            match parameter {
+                Value::BultinType(parameter) => self.expect(arg, parameter, scope, span),
+                _ => {
+                    self.infer(arg, scope, span);
+                }
+            }
+        }
+    }
+
+    /// Checks that `expression` is of the `expected` type.
+    fn expect(
+        &mut self,
+        expression: &Expression,
+        expected: &BuiltinType,
+        scope: &[(String, Type)],
+        at: Span,
+    ) {
+        let span = match expression {
+            Expression::Symbol(symbol) => symbol.span,
+            Expression::Call { name_span, .. } => *name_span,
+            Expression::Binary { operator_span, .. } => *operator_span,
+            _ => at,
+        };
+
+        match (expression, expected) {
+            (Expression::Int(value), BuiltinType::Nat) if *value < 0 => {
+                return self.problem(span, "A `Nat` can't be negative.", "negative");
+            }
+            (Expression::Int(_), BuiltinType::Nat | BuiltinType::Float) => return,
+            (Expression::Symbol(_) | Expression::Call { .. }, BuiltinType::Either(either))
+                if self.variant(expression, either, scope, at) =>
+            {
+                return;
+            }
+            _ => {}
+        }
+
+        let Some(actual) = self.infer(expression, scope, at) else {
+            return;
+        };
+        if !fits(&actual, expected) {
+            let message = match (&actual, expected) {
+                (BuiltinType::Int, BuiltinType::Nat) => {
+                    "Expected a `Nat`, but this `Int` might be negative.".to_string()
+                }
+                _ => format!(
+                    "Expected `{}`, but this is `{}`.",
+                    describe(expected),
+                    describe(&actual)
+                ),
+            };
+            self.problem(span, message, format!("expected `{}`", describe(expected)));
+        }
+    }
+
+    /// Checks a use of a variant of `either`, as in `::Up` or `Some(3)`.
+    /// Returns false if `expression` isn't meant as a variant, but as some other value.
+    fn variant(
+        &mut self,
+        expression: &Expression,
+        either: &Either,
+        scope: &[(String, Type)],
+        at: Span,
+    ) -> bool {
+        let (name, args, span) = match expression {
+            Expression::Symbol(symbol) => match symbol.path.as_slice() {
+                [name] => (name, None, symbol.span),
+                _ => return false,
+            },
+            Expression::Call {
+                name,
+                args,
+                name_span,
+            } => (name, Some(args), *name_span),
+            _ => return false,
+        };
+        let in_scope = scope.iter().any(|(parameter, _)| parameter == name);
+        if !either.fields.contains_key(name) && (in_scope || self.item(name).is_some()) {
+            return false;
+        }
+
+        let expected = describe(&BuiltinType::Either(Box::new(either.clone())));
+        match (either.fields.get(name), args.map(Vec::as_slice)) {
+            (Some(Value::Void(())), None) => {}
+            (Some(Value::BultinType(payload)), Some([arg])) => self.expect(arg, payload, scope, at),
+            (Some(Value::Void(())), Some(_)) => self.problem(
+                span,
+                format!("The variant `{}` doesn't hold a value.", name),
+                format!("write it as `::{}`", name),
+            ),
+            (Some(_), _) => self.problem(
+                span,
+                format!("The variant `{}` holds one value.", name),
+                format!("as in `{}(...)`", name),
+            ),
+            (None, _) => self.problem(
+                span,
+                format!("`{}` isn't a variant of `{}`.", name, expected),
+                "unknown variant",
+            ),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+    use crate::{parser, workspace::ParsedModule};
+
+    fn module(file_name: &str, source: &str) -> ParsedModule {
+        ParsedModule {
+            file_name: file_name.to_string(),
+            source: source.to_string(),
+            ast: parser::parse_module(file_name, source).unwrap(),
+        }
+    }
+
+    const MODELS: &str = r#"model Character
+    name: Text
+end
+
+model Item
+    name: Text
+    durability: dynamic Nat
+    facing: Either(Up, Down, Angle(Float))
+    on_use: Action(Character, Nat)
+    destroyed: Signal
+end
+"#;
+
+    fn problems(source: &str) -> Vec<String> {
+        let models = module("models.sol", MODELS);
+        let asset = module("asset.sol", source);
+        match check(&asset, &[&models]) {
+            Ok(()) => Vec::new(),
+            Err(error) => error
+                .problems
+                .into_iter()
+                .map(|problem| problem.message)
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_well_typed_assets() {
+        assert_eq!(
+            problems(
+                "@model Item\nname = \"Sword\"\ndurability = 20\nfacing = Angle(45)\n\naction on_use(user, damage)\n    hit(::user, ::damage)\n    on_use(::user, 1)\n    self.durability -= damage\n    if self.durability <= 0 then\n        destroyed()\n    end\nend\n\non Item.destroyed\n    * It breaks.\nend\n"
+            ),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn reports_type_errors() {
+        assert_eq!(
+            problems(
+                "@model Item\nname = 3\ndurability = -1\nfacing = ::Sideways\n\naction on_use(user, damage)\n    on_use(::user, \"lots\")\n    destroyed(::damage)\n    name()\n    self.name = \"Axe\"\n    durability = \"lots\"\nend\n\non Item.broken\nend\non Weapon.destroyed\nend\n"
+            ),
+            vec![
+                "Expected `Text`, but this is `Nat`.",
+                "A `Nat` can't be negative.",
+                "`Sideways` isn't a variant of `Either(Angle(Float), Down, Up)`.",
+                "Expected `Nat`, but this is `Text`.",
+                "`destroyed` takes 0 arguments, but is given 1.",
+                "`name` is a Text, and can't be called.",
+                "`name` isn't dynamic, so it can't be set.",
+                "Expected `Nat`, but this is `Text`.",
+                "`Item` has no signal `broken`.",
+                "There's no model `Weapon`.",
+            ]
+        );
+    }
+}
@@ -1,7 +1,18 @@
 #![allow(unused)]
 use clap::{CommandFactory, Parser, Subcommand};
 use miette::{Diagnostic, IntoDiagnostic};
-use sol_lang::workspace::{self, BuildArgs, InitArgs, WorkspaceError};
+use pretty::termcolor::{ColorChoice, StandardStream};
+use sol_lang::{
+    introspec::project::{find_workspace_root, Project, WorkspaceNotFound},
+    workspace::{
+        self,
+        dependency::{self, AddArgs, RemoveArgs, UpdateArgs},
+        migrate::{self, MigrateArgs, MigrateError, Migration},
+        play::{self, PlayArgs},
+        test::{self, SnapshotStatus, Snapshots, TestArgs, TestsFailed},
+        vendor, BuildArgs, InitArgs, WorkspaceError,
+    },
+};
 use std::path::PathBuf;
 use thiserror::Error;
 
@@ -14,7 +25,7 @@ fn main() -> miette::Result<()> {
             let path = args
                 .path
                 .unwrap_or(std::env::current_dir().into_diagnostic()?);
-            workspace::init(path).map_err(WorkspaceError::Init)?;
+            workspace::init(path, args.template, args.yes).map_err(WorkspaceError::Init)?;
         }
         PrimaryAction::Build(args) => {
             let dir = args
@@ -22,11 +33,122 @@ fn main() -> miette::Result<()> {
                 .unwrap_or(std::env::current_dir().into_diagnostic()?);
             workspace::build(dir).map_err(WorkspaceError::Build)?;
         }
-        PrimaryAction::Test => Err(Unimplemented)?,
+        PrimaryAction::Test(args) => {
+            let dir = std::env::current_dir().into_diagnostic()?;
+            let snapshots = match (args.snapshot, args.update) {
+                (false, _) => Snapshots::Skip,
+                (true, false) => Snapshots::Check,
+                (true, true) => Snapshots::Update,
+            };
+            let run = test::run_tests(dir, args.filter.as_deref(), snapshots)
+                .map_err(WorkspaceError::Build)?;
+
+            for outcome in run.outcomes.iter() {
+                let result = if outcome.failure.is_some() {
+                    "FAILED"
+                } else {
+                    "ok"
+                };
+                println!("test {} {:?} ... {}", outcome.module, outcome.name, result);
+            }
+            for snapshot in run.snapshots.iter() {
+                let result = match snapshot.status {
+                    SnapshotStatus::Matched => "ok",
+                    SnapshotStatus::Recorded => "recorded",
+                    SnapshotStatus::Updated => "updated",
+                    SnapshotStatus::Changed { .. } => "FAILED",
+                };
+                println!("snapshot {} ... {}", snapshot.module, result);
+            }
+            let total = run.total();
+            let failed = run.failed();
+            print!(
+                "\n{} passed; {} failed; {} filtered out",
+                total - failed,
+                failed,
+                run.filtered_out
+            );
+            if run.filtered_snapshots > 0 {
+                print!("; {} snapshots filtered out", run.filtered_snapshots);
+            }
+            println!(".");
+
+            for failure in run
+                .outcomes
+                .into_iter()
+                .filter_map(|outcome| outcome.failure)
+            {
+                eprintln!("{:?}", miette::Report::new(failure));
+            }
+            let mut changed = false;
+            for snapshot in run.snapshots.iter() {
+                if let SnapshotStatus::Changed { diff } = &snapshot.status {
+                    eprint!("\n{}", diff);
+                    changed = true;
+                }
+            }
+            if changed {
+                eprintln!("\nRun `sol test --snapshot --update` to record the new transcripts.\n");
+            }
+            if failed > 0 {
+                Err(TestsFailed { failed, total })?;
+            }
+        }
+        PrimaryAction::Play(args) => {
+            let dir = std::env::current_dir().into_diagnostic()?;
+            let mut out = StandardStream::stdout(ColorChoice::Auto);
+            play::play(&dir, &args, &mut out)?;
+        }
         PrimaryAction::Clean => Err(Unimplemented)?,
-        PrimaryAction::Migrate => Err(Unimplemented)?,
-        PrimaryAction::Add => Err(Unimplemented)?,
-        PrimaryAction::Remove => Err(Unimplemented)?,
+        PrimaryAction::Migrate(args) => {
+            let project = Project::discover(&std::env::current_dir().into_diagnostic()?)
+                .map_err(MigrateError::from)
+                .map_err(WorkspaceError::Migrate)?;
+            let migration = Migration::load(&args.migration).map_err(WorkspaceError::Migrate)?;
+            let changes = migrate::plan(&project, &migration).map_err(WorkspaceError::Migrate)?;
+
+            if args.dry_run {
+                for change in changes.iter() {
+                    print!("{}", change.diff(&project.index_directory));
+                }
+                println!("{} files would change.", changes.len());
+            } else {
+                migrate::apply_changes(&changes).into_diagnostic()?;
+                println!("Migrated {} files.", changes.len());
+            }
+        }
+        PrimaryAction::Add(args) => {
+            let cwd = std::env::current_dir().into_diagnostic()?;
+            let root = workspace_root(&cwd)?;
+            let name =
+                dependency::add(&root, &cwd, &args.spec).map_err(WorkspaceError::Dependency)?;
+            println!("Added `{}` to the workspace dependencies.", name);
+        }
+        PrimaryAction::Remove(args) => {
+            let root = workspace_root(&std::env::current_dir().into_diagnostic()?)?;
+            dependency::remove(&root, &args.name).map_err(WorkspaceError::Dependency)?;
+            println!("Removed `{}` from the workspace dependencies.", args.name);
+        }
+        PrimaryAction::Update(args) => {
+            let root = workspace_root(&std::env::current_dir().into_diagnostic()?)?;
+            let updated =
+                dependency::update(&root, &args.names).map_err(WorkspaceError::Dependency)?;
+            for package in updated.iter() {
+                println!("Updated `{}` to {}.", package.name, package.version);
+            }
+            if updated.is_empty() {
+                println!("Everything is up to date.");
+            }
+        }
+        PrimaryAction::Vendor => {
+            let root = workspace_root(&std::env::current_dir().into_diagnostic()?)?;
+            let vendored = vendor::vendor(&root).map_err(WorkspaceError::Dependency)?;
+            println!(
+                "Vendored {} packages into `{}`.",
+                vendored.len(),
+                vendor::SOL_VENDOR_DIRECTORY
+            );
+        }
         PrimaryAction::Completions { shell } => {
             shell.generate(&mut CliArgs::command(), &mut std::io::stdout())
         }
@@ -53,23 +175,36 @@ enum PrimaryAction {
     Init(InitArgs),
     /// Build an existing workspace's assets, so that they can be imported in an app.
     Build(BuildArgs),
-    /// Runs the internal tests availabe in the library.
-    Test,
+    /// Runs the `test` blocks of the workspace's modules.
+    Test(TestArgs),
+    /// Plays a scene in the terminal, to review its dialogue.
+    Play(PlayArgs),
     /// Cleans the build artifacts generated in a build, freeing memory.
     Clean,
 
     /// Applies a migration file to your repository, transforming your assets if necessary.
-    Migrate,
+    Migrate(MigrateArgs),
 
     /// Adds a new library dependency to the workspace.
-    Add,
+    Add(AddArgs),
     /// Removes a previously added dependency from the workspace.
-    Remove,
+    Remove(RemoveArgs),
+    /// Moves locked dependencies forward, as in to the latest commit of a git branch.
+    Update(UpdateArgs),
+    /// Copies every locked dependency into `vendor/`, so that builds don't need the network.
+    Vendor,
 
     /// Query completions for the given shell.
     Completions { shell: clap_complete_command::Shell },
 }
 
+/// Finds the workspace that `cwd` is in.
+fn workspace_root(cwd: &std::path::Path) -> Result<PathBuf, WorkspaceNotFound> {
+    find_workspace_root(cwd).ok_or_else(|| WorkspaceNotFound {
+        start: cwd.to_path_buf(),
+    })
+}
+
 #[derive(Error, Debug, Diagnostic)]
 #[error(transparent)]
 pub enum CliError {
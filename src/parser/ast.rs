@@ -1,25 +1,250 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+/// A byte range into the source a node was parsed from.
+///
+/// Spans never take part in equality, so trees parsed from
+/// differently formatted sources still compare equal.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl From<Span> for miette::SourceSpan {
+    fn from(span: Span) -> Self {
+        (span.start, span.len()).into()
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Module {
+    /// The model this module is an asset of, as in `@model Item`.
+    pub model: Option<ModelDirective>,
+    pub usings: Vec<Using>,
+    pub models: Vec<Model>,
     pub scenes: Vec<Scene>,
-    pub fields: HashMap<String, Expression>,
+    pub actions: Vec<Action>,
+    pub externs: Vec<Extern>,
+    pub fields: HashMap<String, Field>,
+    pub tests: Vec<Test>,
+    pub handlers: Vec<Handler>,
+}
+
+/// A named value at the top level of a module, as in `name = "Wooden Sword"`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Field {
+    pub value: Expression,
+    pub visibility: Visibility,
+    /// Whether the value changes as the game runs, as in `dynamic gold = 0`.
+    pub dynamic: bool,
+    #[serde(skip)]
+    pub name_span: Span,
+    #[serde(skip)]
+    pub value_span: Span,
+}
+
+/// An item the game supplies, rather than Sol, as in `extern player_name: Text`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Extern {
+    pub name: String,
+    pub visibility: Visibility,
+    pub extern_type: TypeExpression,
+    #[serde(skip)]
+    pub name_span: Span,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+/// Which modules can refer to an item, as in `private damage = 3`.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy, Default)]
+pub enum Visibility {
+    /// Any module, which is what items are unless they say otherwise.
+    #[default]
+    Public,
+    /// Only the module it's declared in, as in `private`.
+    Private,
+    /// The module it's declared in and the modules under it,
+    /// as in `private(children)`, so `weapons` and `weapons.iron_sword`.
+    Children,
+}
+
+impl Visibility {
+    /// The modifier that declares this visibility, if any.
+    pub fn modifier(&self) -> Option<&'static str> {
+        match self {
+            Visibility::Public => None,
+            Visibility::Private => Some("private"),
+            Visibility::Children => Some("private(children)"),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct ModuleItem {}
 
+/// Makes a module an asset of a model, as in `@model Item version 2`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ModelDirective {
+    pub name: String,
+    /// The version of the model the asset was authored against.
+    ///
+    /// Assets that don't say are taken to be authored against version 1.
+    pub version: Option<u32>,
+    #[serde(skip)]
+    pub span: Span,
+}
+
 pub enum ScriptPart {
+    ModelDirective(ModelDirective),
+    Using(Vec<Using>),
+    Model(Model),
     Scene(Scene),
+    Action(Action),
+    Extern(Extern),
     Comment(String),
-    Field(String, Expression),
+    Field(String, Field),
+    Test(Test),
+    Handler(Handler),
+}
+
+/// An import of another module, as in `using weapons.iron_sword`.
+///
+/// `using Chars.(Echo, Jude)` is parsed as one `Using` per module.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Using {
+    pub path: Vec<String>,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+/// A schematic for assets, as in `model Item ... end`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Model {
+    pub name: String,
+    pub visibility: Visibility,
+    /// As in `model Item version 2`. Models that don't say are at version 1.
+    pub version: u32,
+    pub fields: Vec<ModelField>,
+    pub upgrades: Vec<Upgrade>,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+/// How to upgrade assets from an older version of their model to the
+/// next one, as in `upgrade 1 = [rename_field("durability", "uses")]`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Upgrade {
+    /// The version this upgrades from.
+    pub from: u32,
+    pub steps: Vec<Expression>,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+/// A field assets of a model must have, as in `durability: dynamic Nat`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ModelField {
+    pub name: String,
+    pub field_type: TypeExpression,
+    /// Whether the field's value might change as the game runs.
+    pub dynamic: bool,
+    #[serde(skip)]
+    pub name_span: Span,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+/// A reference to a type, as in `Nat` or `Action(Character, Character)`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct TypeExpression {
+    pub name: String,
+    pub args: Vec<TypeExpression>,
+}
+
+impl std::fmt::Display for TypeExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.args.is_empty() {
+            let args = self
+                .args
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            write!(f, "({})", args.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A test of a module's scenes and assets, as in `test "greets the player" ... end`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Test {
+    pub name: String,
+    pub statements: Vec<TestStatement>,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+/// A step of a test, as in `choose("Outside")` or `expect_end()`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct TestStatement {
+    pub expression: Expression,
+    #[serde(skip)]
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Scene {
     pub name: String,
     pub content: Vec<ScenePart>,
+    #[serde(skip)]
+    pub name_span: Span,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+/// Behaviour of an asset, run when the game calls it, as in `action on_use(user, target) ... end`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Action {
+    pub name: String,
+    pub visibility: Visibility,
+    pub parameters: Vec<String>,
+    pub body: Vec<ScenePart>,
+    #[serde(skip)]
+    pub name_span: Span,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+/// Behaviour run whenever assets of a model emit a signal, as in `on Item.destroyed ... end`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Handler {
+    pub model: String,
+    pub signal: String,
+    pub body: Vec<ScenePart>,
+    #[serde(skip)]
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -29,6 +254,8 @@ pub enum ScenePart {
     Dialogue(Dialogue),
     Narration(Narration),
     Prompt(Prompt),
+    Conditional(Conditional),
+    Assignment(Assignment),
     Expression(Expression),
     Comment(String),
 }
@@ -37,6 +264,8 @@ pub enum ScenePart {
 pub struct SpeakerChangeMarker {
     pub speaker_id: String,
     pub modifiers: Vec<String>,
+    #[serde(skip)]
+    pub speaker_span: Span,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -47,10 +276,27 @@ pub struct Dialogue {
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 #[serde(tag = "t", content = "c")]
 pub enum TextPart {
+    /// Text as it's written, with its escapes, as in `Say \"hi\"`. See [`unescape`].
     Text(String),
     Expression(Expression),
 }
 
+/// Text as it's read, with the escapes of `\"`, `\\` and `\{` resolved.
+pub fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some(escaped @ ('"' | '\\' | '{'))) => {
+                unescaped.push(escaped);
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Narration {
     pub parts: Vec<TextPart>,
@@ -65,22 +311,155 @@ pub struct Prompt {
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct PromptOption {
     pub text: Vec<TextPart>,
+    /// The option is only shown when this holds, as in `option Ask again [if seen("- Hi!")]`.
+    pub condition: Option<Expression>,
     pub content: Vec<ScenePart>,
 }
 
+/// Parts played only when a condition holds, as in `if ::gold >= 10 then ... else ... end`.
+/// The condition is `::True` or `::False`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Conditional {
+    pub condition: Expression,
+    pub content: Vec<ScenePart>,
+    /// The parts played when the condition doesn't hold, after `else`.
+    pub otherwise: Vec<ScenePart>,
+}
+
+/// A variable, or a field of an asset, being set, as in `self.durability -= 1`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Assignment {
+    /// A symbol, as in `::gold`, or a field, as in `self.durability`.
+    pub target: Expression,
+    /// The operator applied to the value it held and `value`, as in `-=`, if any.
+    pub operator: Option<Operator>,
+    pub value: Expression,
+    #[serde(skip)]
+    pub span: Span,
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 #[serde(tag = "t", content = "c")]
 pub enum Expression {
-    Call { name: String, args: Vec<Expression> },
+    Call {
+        name: String,
+        args: Vec<Expression>,
+        #[serde(skip)]
+        name_span: Span,
+    },
     Unit(Box<Expression>, String),
+    /// A field of a value, as in `self.durability`, or a call on it, as in `target.damage(3)`,
+    /// which is a call with the value first, as in `damage(target, 3)`.
+    Member {
+        object: Box<Expression>,
+        name: String,
+        /// The arguments of the call, unless the field is only read.
+        args: Option<Vec<Expression>>,
+        #[serde(skip)]
+        name_span: Span,
+    },
+    /// Arithmetic, as in `attack_damage * 2`, or a comparison, as in `::gold >= 10`.
+    Binary {
+        operator: Operator,
+        left: Box<Expression>,
+        right: Box<Expression>,
+        #[serde(skip)]
+        operator_span: Span,
+    },
     Int(i32),
     Float(f32),
     Boolean(bool),
     Text(Vec<TextPart>),
+    List {
+        items: Vec<Expression>,
+        /// Where each item is, in order.
+        #[serde(skip)]
+        item_spans: Vec<Span>,
+    },
     Symbol(Symbol),
 }
 
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    And,
+    Or,
+}
+
+impl Operator {
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        Some(match symbol {
+            "+" => Operator::Add,
+            "-" => Operator::Subtract,
+            "*" => Operator::Multiply,
+            "/" => Operator::Divide,
+            "==" => Operator::Equal,
+            "!=" => Operator::NotEqual,
+            "<" => Operator::Less,
+            "<=" => Operator::LessOrEqual,
+            ">" => Operator::Greater,
+            ">=" => Operator::GreaterOrEqual,
+            "and" => Operator::And,
+            "or" => Operator::Or,
+            _ => return None,
+        })
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessOrEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterOrEqual => ">=",
+            Operator::And => "and",
+            Operator::Or => "or",
+        }
+    }
+
+    /// Operators of higher precedence are applied first.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Operator::Or => 1,
+            Operator::And => 2,
+            Operator::Equal
+            | Operator::NotEqual
+            | Operator::Less
+            | Operator::LessOrEqual
+            | Operator::Greater
+            | Operator::GreaterOrEqual => 3,
+            Operator::Add | Operator::Subtract => 4,
+            Operator::Multiply | Operator::Divide => 5,
+        }
+    }
+
+    /// Whether the operator is arithmetic, as opposed to giving `::True` or `::False`.
+    pub fn is_arithmetic(&self) -> bool {
+        matches!(
+            self,
+            Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide
+        )
+    }
+}
+
+/// A name, as in `::weapons::sword`, or `attack_damage`, which is `::attack_damage`.
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Symbol {
     pub path: Vec<String>,
+    #[serde(skip)]
+    pub span: Span,
 }
@@ -1,6 +1,51 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use thiserror::Error;
 
 pub mod ast;
 //pub mod chumsky;
 pub mod nom;
+
+/// Parses a whole `.sol` source into a [`ast::Module`].
+///
+/// `name` is only used to label the diagnostic if parsing fails.
+pub fn parse_module(name: &str, source: &str) -> Result<ast::Module, ParseError> {
+    parse_all(name, source, nom::p_module)
+}
+
+/// Parses a type on its own, as in `Action(Item, Nat)`.
+///
+/// `name` is only used to label the diagnostic if parsing fails.
+pub fn parse_type(name: &str, source: &str) -> Result<ast::TypeExpression, ParseError> {
+    parse_all(name, source, nom::p_type)
+}
+
+/// Runs `parser`, which must consume all of `source`.
+fn parse_all<O>(
+    name: &str,
+    source: &str,
+    parser: impl FnOnce(nom::Input) -> ::nom::IResult<nom::Input, O>,
+) -> Result<O, ParseError> {
+    let error_at = |offset: usize| ParseError {
+        src: NamedSource::new(name, source.to_string()),
+        span: (offset, 0).into(),
+    };
+
+    match parser(nom::Input::new(source)) {
+        Ok((rest, output)) if rest.fragment().is_empty() => Ok(output),
+        Ok((rest, _)) => Err(error_at(rest.location_offset())),
+        Err(::nom::Err::Error(err) | ::nom::Err::Failure(err)) => {
+            Err(error_at(err.input.location_offset()))
+        }
+        Err(::nom::Err::Incomplete(_)) => Err(error_at(source.len())),
+    }
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("Failed to parse `.sol` source.")]
+#[diagnostic(code(sol::parse))]
+pub struct ParseError {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("Unexpected input here.")]
+    pub span: SourceSpan,
+}
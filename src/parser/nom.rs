@@ -1,33 +1,54 @@
 use std::collections::HashMap;
 
 use super::ast::{
-    Dialogue, Expression, Module, Narration, Prompt, PromptOption, Scene, ScenePart, ScriptPart,
-    SpeakerChangeMarker, Symbol, TextPart,
+    Action, Assignment, Conditional, Dialogue, Expression, Extern, Field, Handler, Model,
+    ModelDirective, ModelField, Module, Narration, Operator, Prompt, PromptOption, Scene,
+    ScenePart, ScriptPart, Span, SpeakerChangeMarker, Symbol, Test, TestStatement, TextPart,
+    TypeExpression, Upgrade, Using, Visibility,
 };
-use asky::Text;
 use nom::{
     branch::alt,
     bytes::complete::{escaped, is_not, tag, take_until},
     character::complete::{
-        alpha1, alphanumeric1, char, multispace0, multispace1, newline, none_of, one_of, space0,
-        space1,
+        alpha1, alphanumeric1, char, digit1, multispace0, multispace1, newline, none_of, one_of,
+        space0, space1,
     },
-    combinator::{map, opt, recognize, value},
-    multi::{many0, many0_count, many1, separated_list0, separated_list1},
-    number::complete::float,
+    combinator::{map, map_res, not, opt, peek, recognize, success, value, verify},
+    multi::{fold_many0, many0, many0_count, many1, separated_list0, separated_list1},
     sequence::{delimited, pair, preceded, separated_pair, terminated},
     IResult, Parser,
 };
+use nom_locate::LocatedSpan;
+
+/// Parser input, which keeps track of where in the source it is.
+pub type Input<'a> = LocatedSpan<&'a str>;
+
+/// Words that start parts of scenes, or join or follow expressions,
+/// and so aren't names, or units.
+const KEYWORDS: &[&str] = &["and", "or", "if", "then", "else", "end", "prompt", "option"];
 
 pub fn p_script(input: &str) -> IResult<&str, Module> {
+    p_module(Input::new(input))
+        .map(|(rest, module)| (*rest.fragment(), module))
+        .map_err(|err| err.map_input(|input| *input.fragment()))
+}
+
+pub fn p_module(input: Input) -> IResult<Input, Module> {
     let (input, script_entries) = delimited(
         multispace0,
         separated_list0(
             multispace1,
             alt((
                 map(p_comment, ScriptPart::Comment),
+                map(p_model_directive, ScriptPart::ModelDirective),
+                map(p_using, ScriptPart::Using),
+                map(p_model, ScriptPart::Model),
                 map(p_let_scene, ScriptPart::Scene),
-                map(p_field, |(name, expr)| ScriptPart::Field(name, expr)),
+                map(p_action, ScriptPart::Action),
+                map(p_extern, ScriptPart::Extern),
+                map(p_test, ScriptPart::Test),
+                map(p_handler, ScriptPart::Handler),
+                map(p_field, |(name, field)| ScriptPart::Field(name, field)),
             )),
         ),
         multispace0,
@@ -38,17 +59,45 @@ pub fn p_script(input: &str) -> IResult<&str, Module> {
         input,
         script_entries.into_iter().fold(
             Module {
+                model: None,
+                usings: Vec::new(),
+                models: Vec::new(),
                 scenes: Vec::new(),
+                actions: Vec::new(),
+                externs: Vec::new(),
                 fields: HashMap::new(),
+                tests: Vec::new(),
+                handlers: Vec::new(),
             },
             |mut acc, part| {
                 match part {
+                    ScriptPart::ModelDirective(model) => {
+                        acc.model = Some(model);
+                    }
+                    ScriptPart::Using(usings) => {
+                        acc.usings.extend(usings);
+                    }
+                    ScriptPart::Model(model) => {
+                        acc.models.push(model);
+                    }
                     ScriptPart::Scene(scene) => {
                         acc.scenes.push(scene);
                     }
+                    ScriptPart::Action(action) => {
+                        acc.actions.push(action);
+                    }
+                    ScriptPart::Extern(item) => {
+                        acc.externs.push(item);
+                    }
                     ScriptPart::Comment(_) => {}
-                    ScriptPart::Field(name, expression) => {
-                        acc.fields.insert(name, expression);
+                    ScriptPart::Field(name, field) => {
+                        acc.fields.insert(name, field);
+                    }
+                    ScriptPart::Test(test) => {
+                        acc.tests.push(test);
+                    }
+                    ScriptPart::Handler(handler) => {
+                        acc.handlers.push(handler);
                     }
                 }
                 acc
@@ -57,105 +106,436 @@ pub fn p_script(input: &str) -> IResult<&str, Module> {
     ))
 }
 
-fn p_comment(input: &str) -> IResult<&str, String> {
+/// Runs `parser` and also returns the span of the input it consumed.
+fn spanned<'a, O, P>(mut parser: P) -> impl Parser<Input<'a>, Output = (O, Span), Error = P::Error>
+where
+    P: Parser<Input<'a>, Output = O>,
+{
+    move |input: Input<'a>| {
+        let start = input.location_offset();
+        let (rest, output) = parser.parse(input)?;
+        Ok((rest, (output, Span::new(start, rest.location_offset()))))
+    }
+}
+
+fn p_comment(input: Input) -> IResult<Input, String> {
     alt((
-        preceded(tag("--"), is_not("\n\r").map(&str::to_string)),
+        preceded(tag("--"), is_not("\n\r").map(to_owned_string)),
         delimited(
             tag("--["),
-            take_until("]--").map(&str::to_string),
+            take_until("]--").map(to_owned_string),
             tag("]--"),
         ),
     ))
     .parse(input)
 }
 
-pub fn p_let_scene(input: &str) -> IResult<&str, Scene> {
+fn p_model_directive(input: Input) -> IResult<Input, ModelDirective> {
     map(
-        delimited(
+        spanned(preceded(
+            (tag("@model"), space1),
+            (map(p_identifier, to_owned_string), opt(p_version)),
+        )),
+        |((name, version), span)| ModelDirective {
+            name,
+            version,
+            span,
+        },
+    )
+    .parse(input)
+}
+
+/// A model version, as in ` version 2`.
+fn p_version(input: Input) -> IResult<Input, u32> {
+    preceded(
+        (space1, tag("version"), space1),
+        map_res(digit1, |digits: Input| digits.fragment().parse()),
+    )
+    .parse(input)
+}
+
+fn p_using(input: Input) -> IResult<Input, Vec<Using>> {
+    map(
+        spanned(preceded(
+            (tag("using"), space1),
+            (
+                separated_list1(tag("."), map(p_identifier, to_owned_string)),
+                opt(preceded(
+                    tag("."),
+                    delimited(
+                        tag("("),
+                        separated_list1(
+                            tag(","),
+                            delimited(multispace0, map(p_identifier, to_owned_string), multispace0),
+                        ),
+                        tag(")"),
+                    ),
+                )),
+            ),
+        )),
+        |((path, group), span)| match group {
+            None => vec![Using { path, span }],
+            Some(group) => group
+                .into_iter()
+                .map(|last| Using {
+                    path: path.iter().cloned().chain([last]).collect(),
+                    span,
+                })
+                .collect(),
+        },
+    )
+    .parse(input)
+}
+
+pub fn p_model(input: Input) -> IResult<Input, Model> {
+    enum ModelPart {
+        Comment,
+        Upgrade(Upgrade),
+        Field(ModelField),
+    }
+
+    map(
+        (
+            p_visibility,
+            spanned(delimited(
+                tag("model"),
+                (
+                    preceded(space1, map(p_identifier, to_owned_string)),
+                    terminated(opt(p_version), multispace1),
+                    separated_list0(
+                        multispace1,
+                        alt((
+                            map(p_comment, |_| ModelPart::Comment),
+                            map(p_upgrade, ModelPart::Upgrade),
+                            map(p_model_field, ModelPart::Field),
+                        )),
+                    ),
+                ),
+                (multispace0, tag("end")),
+            )),
+        ),
+        |(visibility, ((name, version, parts), span))| {
+            let mut model = Model {
+                name,
+                visibility,
+                version: version.unwrap_or(1),
+                fields: Vec::new(),
+                upgrades: Vec::new(),
+                span,
+            };
+            for part in parts {
+                match part {
+                    ModelPart::Comment => {}
+                    ModelPart::Upgrade(upgrade) => model.upgrades.push(upgrade),
+                    ModelPart::Field(field) => model.fields.push(field),
+                }
+            }
+            model
+        },
+    )
+    .parse(input)
+}
+
+fn p_upgrade(input: Input) -> IResult<Input, Upgrade> {
+    map(
+        spanned(separated_pair(
+            preceded(
+                (tag("upgrade"), space1),
+                map_res(digit1, |digits: Input| digits.fragment().parse()),
+            ),
+            (space0, tag("="), space0),
+            p_list,
+        )),
+        |((from, steps), span)| Upgrade {
+            from,
+            steps: steps.into_iter().map(|(step, _)| step).collect(),
+            span,
+        },
+    )
+    .parse(input)
+}
+
+fn p_model_field(input: Input) -> IResult<Input, ModelField> {
+    map(
+        spanned(separated_pair(
+            spanned(map(p_identifier, to_owned_string)),
+            (space0, tag(":"), space0),
+            (opt(terminated(tag("dynamic"), space1)), p_type),
+        )),
+        |(((name, name_span), (dynamic, field_type)), span)| ModelField {
+            name,
+            field_type,
+            dynamic: dynamic.is_some(),
+            name_span,
+            span,
+        },
+    )
+    .parse(input)
+}
+
+pub fn p_extern(input: Input) -> IResult<Input, Extern> {
+    map(
+        (
+            p_visibility,
+            spanned(preceded(
+                (tag("extern"), space1),
+                separated_pair(
+                    spanned(map(p_identifier, to_owned_string)),
+                    (space0, tag(":"), space0),
+                    p_type,
+                ),
+            )),
+        ),
+        |(visibility, (((name, name_span), extern_type), span))| Extern {
+            name,
+            visibility,
+            extern_type,
+            name_span,
+            span,
+        },
+    )
+    .parse(input)
+}
+
+pub fn p_type(input: Input) -> IResult<Input, TypeExpression> {
+    map(
+        (
+            map(p_identifier, to_owned_string),
+            opt(delimited(
+                tag("("),
+                separated_list0(tag(","), delimited(multispace0, p_type, multispace0)),
+                tag(")"),
+            )),
+        ),
+        |(name, args)| TypeExpression {
+            name,
+            args: args.unwrap_or_default(),
+        },
+    )
+    .parse(input)
+}
+
+pub fn p_let_scene(input: Input) -> IResult<Input, Scene> {
+    map(
+        spanned(delimited(
             tag("scene"),
             (
-                delimited(space1, p_identifier, multispace1),
+                delimited(
+                    space1,
+                    spanned(map(p_identifier, to_owned_string)),
+                    multispace1,
+                ),
                 separated_list0(multispace0, p_scene_part),
             ),
             (multispace0, tag("end")),
-        ),
-        |(name, parts)| Scene {
-            name: name.to_string(),
+        )),
+        |(((name, name_span), parts), span)| Scene {
+            name,
             content: parts,
+            name_span,
+            span,
+        },
+    )
+    .parse(input)
+}
+
+pub fn p_action(input: Input) -> IResult<Input, Action> {
+    map(
+        (
+            p_visibility,
+            spanned(delimited(
+                (tag("action"), space1),
+                (
+                    spanned(map(p_identifier, to_owned_string)),
+                    delimited(
+                        tag("("),
+                        separated_list0(
+                            tag(","),
+                            delimited(multispace0, map(p_identifier, to_owned_string), multispace0),
+                        ),
+                        tag(")"),
+                    ),
+                    preceded(multispace1, separated_list0(multispace0, p_scene_part)),
+                ),
+                (multispace0, tag("end")),
+            )),
+        ),
+        |(visibility, (((name, name_span), parameters, body), span))| Action {
+            name,
+            visibility,
+            parameters,
+            body,
+            name_span,
+            span,
+        },
+    )
+    .parse(input)
+}
+
+pub fn p_test(input: Input) -> IResult<Input, Test> {
+    map(
+        spanned(delimited(
+            tag("test"),
+            (
+                delimited(
+                    space1,
+                    delimited(tag("\""), is_not("\"\r\n"), tag("\"")),
+                    multispace1,
+                ),
+                separated_list0(
+                    multispace1,
+                    alt((
+                        map(p_comment, |_| None),
+                        map(spanned(p_expression), |(expression, span)| {
+                            Some(TestStatement { expression, span })
+                        }),
+                    )),
+                ),
+            ),
+            (multispace0, tag("end")),
+        )),
+        |((name, statements), span)| Test {
+            name: to_owned_string(name),
+            statements: statements.into_iter().flatten().collect(),
+            span,
+        },
+    )
+    .parse(input)
+}
+
+pub fn p_handler(input: Input) -> IResult<Input, Handler> {
+    map(
+        spanned(delimited(
+            (tag("on"), space1),
+            (
+                separated_pair(
+                    map(p_identifier, to_owned_string),
+                    tag("."),
+                    map(p_identifier, to_owned_string),
+                ),
+                preceded(multispace1, separated_list0(multispace0, p_scene_part)),
+            ),
+            (multispace0, tag("end")),
+        )),
+        |(((model, signal), body), span)| Handler {
+            model,
+            signal,
+            body,
+            span,
         },
     )
     .parse(input)
 }
 
-fn p_scene_part(input: &str) -> IResult<&str, ScenePart> {
+fn p_scene_part(input: Input) -> IResult<Input, ScenePart> {
     alt((
         map(p_comment, ScenePart::Comment),
         map(p_speaker_change_marker, ScenePart::SpeakerChangeMarker),
         map(p_dialogue, ScenePart::Dialogue),
         map(p_narration, ScenePart::Narration),
-        map(p_expression, ScenePart::Expression),
+        map(p_conditional, ScenePart::Conditional),
         map(p_prompt, ScenePart::Prompt),
+        map(p_assignment, ScenePart::Assignment),
+        map(p_expression, ScenePart::Expression),
     ))
     .parse(input)
 }
 
-pub fn p_field(input: &str) -> IResult<&str, (String, Expression)> {
-    separated_pair(
-        map(p_identifier, &str::to_string),
-        (space0, tag("="), space0),
-        p_expression,
+pub fn p_field(input: Input) -> IResult<Input, (String, Field)> {
+    map(
+        (
+            p_visibility,
+            opt(terminated(tag("dynamic"), (space1, not(tag("="))))),
+            separated_pair(
+                spanned(map(p_identifier, to_owned_string)),
+                (space0, tag("="), space0),
+                spanned(p_expression),
+            ),
+        ),
+        |(visibility, dynamic, ((name, name_span), (value, value_span)))| {
+            (
+                name,
+                Field {
+                    value,
+                    visibility,
+                    dynamic: dynamic.is_some(),
+                    name_span,
+                    value_span,
+                },
+            )
+        },
     )
     .parse(input)
 }
 
-fn p_speaker_change_marker(input: &str) -> IResult<&str, SpeakerChangeMarker> {
+/// Parses a visibility modifier, followed by a space, or nothing for public items.
+fn p_visibility(input: Input) -> IResult<Input, Visibility> {
+    alt((
+        value(Visibility::Children, (tag("private(children)"), space1)),
+        value(Visibility::Private, (tag("private"), space1)),
+        success(Visibility::Public),
+    ))
+    .parse(input)
+}
+
+fn p_speaker_change_marker(input: Input) -> IResult<Input, SpeakerChangeMarker> {
     map(
         delimited(
             tag("["),
             (
-                map(alt((p_identifier, tag("&"))), &str::to_string),
-                many0(preceded(space1, map(p_identifier, &str::to_string))),
+                spanned(map(alt((p_identifier, tag("&"))), to_owned_string)),
+                many0(preceded(space1, map(p_identifier, to_owned_string))),
             ),
             tag("]"),
         ),
-        |(speaker_id, modifiers)| SpeakerChangeMarker {
+        |((speaker_id, speaker_span), modifiers)| SpeakerChangeMarker {
             speaker_id,
             modifiers,
+            speaker_span,
         },
     )
     .parse(input)
 }
 
-fn p_dialogue(input: &str) -> IResult<&str, Dialogue> {
+fn p_dialogue(input: Input) -> IResult<Input, Dialogue> {
     map(preceded(tag("- "), many1(p_text_part)), |parts| Dialogue {
         parts,
     })
     .parse(input)
 }
 
-fn p_narration(input: &str) -> IResult<&str, Narration> {
+fn p_narration(input: Input) -> IResult<Input, Narration> {
     map(preceded(tag("* "), many1(p_text_part)), |parts| Narration {
         parts,
     })
     .parse(input)
 }
 
-fn p_text_part(input: &str) -> IResult<&str, TextPart> {
+fn p_text_part(input: Input) -> IResult<Input, TextPart> {
     alt((
         // Normal text
         //map(is_not("\r\n{"), |s: &str| TextPart::Text(s.to_string())),
-        map(escaped(none_of("\r\n{\"\\"), '\\', tag("\"")), |s: &str| {
-            TextPart::Text(s.to_string())
-        }),
-        // Interpolation
-        map(delimited(tag("{"), p_expression, tag("}")), |e| {
-            TextPart::Expression(e)
-        }),
+        map(
+            verify(
+                escaped(none_of("\r\n{\"\\"), '\\', one_of("\"\\{")),
+                |s: &Input| !s.is_empty(),
+            ),
+            |s| TextPart::Text(to_owned_string(s)),
+        ),
+        p_interpolation,
     ))
     .parse(input)
 }
 
-fn p_prompt(input: &str) -> IResult<&str, Prompt> {
+/// An expression in text, as in `{player_name()}`.
+fn p_interpolation(input: Input) -> IResult<Input, TextPart> {
+    map(delimited(tag("{"), p_expression, tag("}")), |e| {
+        TextPart::Expression(e)
+    })
+    .parse(input)
+}
+
+fn p_prompt(input: Input) -> IResult<Input, Prompt> {
     map(
         delimited(
             tag("prompt"),
@@ -170,102 +550,326 @@ fn p_prompt(input: &str) -> IResult<&str, Prompt> {
     .parse(input)
 }
 
-fn p_prompt_option(input: &str) -> IResult<&str, PromptOption> {
+fn p_prompt_option(input: Input) -> IResult<Input, PromptOption> {
     map(
         delimited(
             tag("option"),
             (
-                delimited(space1, many1(p_text_part), multispace1),
+                preceded(space1, many1(p_option_text_part)),
+                terminated(opt(p_option_condition), multispace1),
                 separated_list0(multispace0, p_scene_part),
             ),
             (multispace0, tag("end")),
         ),
-        |(text, content)| PromptOption { text, content },
+        |(text, condition, content)| PromptOption {
+            text,
+            condition,
+            content,
+        },
     )
     .parse(input)
 }
 
-fn p_expression(input: &str) -> IResult<&str, Expression> {
+/// Text of an option, which ends where its condition starts.
+fn p_option_text_part(input: Input) -> IResult<Input, TextPart> {
     alt((
         map(
-            separated_pair(p_float, space1, p_identifier),
-            |(f, unit)| Expression::Unit(Box::new(Expression::Float(f)), unit.to_string()),
+            recognize(many1(preceded(
+                not(p_option_condition),
+                alt((
+                    recognize(pair(char('\\'), one_of("\"\\{"))),
+                    recognize(none_of("\r\n{\"\\")),
+                )),
+            ))),
+            |s| TextPart::Text(to_owned_string(s)),
         ),
-        map(p_float, Expression::Float),
-        map(
-            separated_pair(p_integer_decimal, space1, p_identifier),
-            |(i, unit)| Expression::Unit(Box::new(Expression::Int(i)), unit.to_string()),
+        p_interpolation,
+    ))
+    .parse(input)
+}
+
+/// The condition ending the line of an option, in brackets so it can't be mistaken for
+/// text, as in `option Ask again [if seen("- Hi!")]`.
+fn p_option_condition(input: Input) -> IResult<Input, Expression> {
+    delimited(
+        (space1, tag("[if"), space1),
+        p_expression,
+        (space0, char(']'), space0, peek(one_of("\r\n"))),
+    )
+    .parse(input)
+}
+
+fn p_conditional(input: Input) -> IResult<Input, Conditional> {
+    map(
+        delimited(
+            (tag("if"), space1),
+            (
+                terminated(p_expression, (space1, tag("then"), multispace1)),
+                separated_list0(multispace0, p_scene_part),
+                opt(preceded(
+                    (multispace0, tag("else"), multispace1),
+                    separated_list0(multispace0, p_scene_part),
+                )),
+            ),
+            (multispace0, tag("end")),
         ),
-        map(p_integer_decimal, Expression::Int),
-        map(p_string, Expression::Text),
-        map(
+        |(condition, content, otherwise)| Conditional {
+            condition,
+            content,
+            otherwise: otherwise.unwrap_or_default(),
+        },
+    )
+    .parse(input)
+}
+
+/// A variable, or a field, being set, as in `::gold = 0` or `self.durability -= 1`.
+fn p_assignment(input: Input) -> IResult<Input, Assignment> {
+    map(
+        spanned((
+            verify(p_member, |target| {
+                matches!(
+                    target,
+                    Expression::Symbol(_) | Expression::Member { args: None, .. }
+                )
+            }),
+            delimited(
+                space0,
+                terminated(opt(one_of("+-*/")), (char('='), not(char('=')))),
+                space0,
+            ),
+            p_expression,
+        )),
+        |((target, operator, value), span)| Assignment {
+            target,
+            operator: operator.map(|operator| {
+                Operator::from_symbol(&operator.to_string()).expect("Only operators are parsed.")
+            }),
+            value,
+            span,
+        },
+    )
+    .parse(input)
+}
+
+pub fn p_expression(input: Input) -> IResult<Input, Expression> {
+    p_operation(input, p_conjunction, &["or"])
+}
+
+fn p_conjunction(input: Input) -> IResult<Input, Expression> {
+    p_operation(input, p_comparison, &["and"])
+}
+
+fn p_comparison(input: Input) -> IResult<Input, Expression> {
+    p_operation(input, p_sum, &["==", "!=", "<=", ">=", "<", ">"])
+}
+
+fn p_sum(input: Input) -> IResult<Input, Expression> {
+    p_operation(input, p_term, &["+", "-"])
+}
+
+fn p_term(input: Input) -> IResult<Input, Expression> {
+    p_operation(input, p_member, &["*", "/"])
+}
+
+/// An operand, followed by any fields of it, as in `self.durability`, or calls on it,
+/// as in `target.damage(3)`.
+fn p_member(input: Input) -> IResult<Input, Expression> {
+    let (input, object) = p_operand(input)?;
+    fold_many0(
+        preceded(
+            tag("."),
             (
-                map(p_identifier, &str::to_string),
-                delimited(
-                    tag("("),
-                    separated_list0(tag(","), delimited(multispace0, p_expression, multispace0)),
-                    tag(")"),
-                ),
+                spanned(map(p_identifier, to_owned_string)),
+                opt(p_arguments),
             ),
-            |(name, parameters)| Expression::Call {
+        ),
+        move || object.clone(),
+        |object, ((name, name_span), args)| Expression::Member {
+            object: Box::new(object),
+            name,
+            args,
+            name_span,
+        },
+    )
+    .parse(input)
+}
+
+/// Parses `operand`s joined by any of `operators`, applying them from left to right.
+fn p_operation<'a>(
+    input: Input<'a>,
+    operand: fn(Input<'a>) -> IResult<Input<'a>, Expression>,
+    operators: &[&str],
+) -> IResult<Input<'a>, Expression> {
+    let (input, first) = operand(input)?;
+    fold_many0(
+        (|input| p_operator(input, operators), operand),
+        move || first.clone(),
+        |left, ((operator, operator_span), right)| Expression::Binary {
+            operator,
+            left: Box::new(left),
+            right: Box::new(right),
+            operator_span,
+        },
+    )
+    .parse(input)
+}
+
+/// Parses the first of `operators` found, along with the space around it.
+/// Operators that are words, as in `and`, need space on both sides.
+fn p_operator<'a>(input: Input<'a>, operators: &[&str]) -> IResult<Input<'a>, (Operator, Span)> {
+    for operator in operators {
+        let parsed: IResult<Input, (Input, Span)> = if operator.chars().all(char::is_alphabetic) {
+            delimited(space1, spanned(tag(*operator)), space1).parse(input)
+        } else {
+            delimited(space0, spanned(tag(*operator)), space0).parse(input)
+        };
+        if let Ok((rest, (_, span))) = parsed {
+            let operator = Operator::from_symbol(operator).expect("Only operators are parsed.");
+            return Ok((rest, (operator, span)));
+        }
+    }
+    Err(nom::Err::Error(nom::error::Error::new(
+        input,
+        nom::error::ErrorKind::Tag,
+    )))
+}
+
+fn p_operand(input: Input) -> IResult<Input, Expression> {
+    alt((
+        map(separated_pair(p_float, space1, p_name), |(f, unit)| {
+            Expression::Unit(Box::new(Expression::Float(f)), unit.to_string())
+        }),
+        map(p_float, Expression::Float),
+        map(separated_pair(p_integer, space1, p_name), |(i, unit)| {
+            Expression::Unit(Box::new(Expression::Int(i)), unit.to_string())
+        }),
+        map(p_integer, Expression::Int),
+        map(p_string, Expression::Text),
+        map(p_list, |items| {
+            let (items, item_spans) = items.into_iter().unzip();
+            Expression::List { items, item_spans }
+        }),
+        map(
+            (spanned(map(p_identifier, to_owned_string)), p_arguments),
+            |((name, name_span), parameters)| Expression::Call {
                 name,
                 args: parameters,
+                name_span,
             },
         ),
         map(p_symbol, Expression::Symbol),
+        map(spanned(p_name), |(name, span)| {
+            Expression::Symbol(Symbol {
+                path: vec![to_owned_string(name)],
+                span,
+            })
+        }),
+        delimited((tag("("), space0), p_expression, (space0, tag(")"))),
     ))
     .parse(input)
 }
 
-fn p_string(input: &str) -> IResult<&str, Vec<TextPart>> {
-    delimited(tag("\""), many1(p_text_part), tag("\"")).parse(input)
+/// The arguments of a call, as in `(::target, 3)`.
+fn p_arguments(input: Input) -> IResult<Input, Vec<Expression>> {
+    delimited(
+        tag("("),
+        separated_list0(tag(","), delimited(multispace0, p_expression, multispace0)),
+        tag(")"),
+    )
+    .parse(input)
 }
 
-fn p_integer_decimal(input: &str) -> IResult<&str, i32> {
-    recognize(many1(terminated(
-        one_of("0123456789"),
-        many0(nom::character::char('_')),
-    )))
+/// A name, as in `attack_damage`, or the unit of a number, as in `2 second`,
+/// which can't be a keyword, as in `2 then`.
+fn p_name(input: Input) -> IResult<Input, Input> {
+    verify(p_identifier, |name: &Input| {
+        !KEYWORDS.contains(name.fragment())
+    })
     .parse(input)
-    .map(|(input, i)| {
-        (
-            input,
-            i.replace("_", "").parse().expect("Well formed integer!"),
-        )
+}
+
+fn p_string(input: Input) -> IResult<Input, Vec<TextPart>> {
+    delimited(tag("\""), many0(p_text_part), tag("\"")).parse(input)
+}
+
+fn p_list(input: Input) -> IResult<Input, Vec<(Expression, Span)>> {
+    delimited(
+        (tag("["), multispace0),
+        terminated(
+            separated_list0((multispace0, tag(","), multispace0), p_list_item),
+            opt((multispace0, tag(","))),
+        ),
+        (multispace0, tag("]")),
+    )
+    .parse(input)
+}
+
+fn p_list_item(input: Input) -> IResult<Input, (Expression, Span)> {
+    preceded(many0((p_comment, multispace0)), spanned(p_expression)).parse(input)
+}
+
+fn p_integer_decimal(input: Input) -> IResult<Input, i32> {
+    recognize(many1(terminated(one_of("0123456789"), many0(char('_')))))
+        .parse(input)
+        .map(|(input, i)| {
+            (
+                input,
+                i.fragment()
+                    .replace("_", "")
+                    .parse()
+                    .expect("Well formed integer!"),
+            )
+        })
+}
+
+/// An integer, which might be negative.
+fn p_integer(input: Input) -> IResult<Input, i32> {
+    map((opt(char('-')), p_integer_decimal), |(sign, integer)| {
+        if sign.is_some() {
+            -integer
+        } else {
+            integer
+        }
     })
+    .parse(input)
 }
 
-fn p_float(input: &str) -> IResult<&str, f32> {
-    alt((
-        // Case one: .42
-        recognize((
-            char('.'),
-            p_integer_decimal,
-            opt((one_of("eE"), opt(one_of("+-")), p_integer_decimal)),
-        )), // Case two: 42e42 and 42.42e42
-        recognize((
-            p_integer_decimal,
-            opt(preceded(char('.'), p_integer_decimal)),
-            one_of("eE"),
-            opt(one_of("+-")),
-            p_integer_decimal,
-        )), // Case three: 42. and 42.42
-        recognize((p_integer_decimal, char('.'), opt(p_integer_decimal))),
+fn p_float(input: Input) -> IResult<Input, f32> {
+    recognize((
+        opt(char('-')),
+        alt((
+            // Case one: .42
+            recognize((
+                char('.'),
+                p_integer_decimal,
+                opt((one_of("eE"), opt(one_of("+-")), p_integer_decimal)),
+            )), // Case two: 42e42 and 42.42e42
+            recognize((
+                p_integer_decimal,
+                opt(preceded(char('.'), p_integer_decimal)),
+                one_of("eE"),
+                opt(one_of("+-")),
+                p_integer_decimal,
+            )), // Case three: 42. and 42.42
+            recognize((p_integer_decimal, char('.'), opt(p_integer_decimal))),
+        )),
     ))
     .parse(input)
-    .map(|(input, f)| (input, f.parse().expect("Well formed float")))
+    .map(|(input, f)| (input, f.fragment().parse().expect("Well formed float")))
 }
 
-fn p_symbol(input: &str) -> IResult<&str, Symbol> {
-    preceded(
-        tag("::"),
-        separated_list1(tag("::"), map(p_identifier, &str::to_string)),
+fn p_symbol(input: Input) -> IResult<Input, Symbol> {
+    map(
+        spanned(preceded(
+            tag("::"),
+            separated_list1(tag("::"), map(p_identifier, to_owned_string)),
+        )),
+        |(path, span)| Symbol { path, span },
     )
     .parse(input)
-    .map(|(input, path)| (input, Symbol { path }))
 }
 
-fn p_identifier(input: &str) -> IResult<&str, &str> {
+fn p_identifier(input: Input) -> IResult<Input, Input> {
     recognize(pair(
         alt((alpha1, tag("_"))),
         many0_count(alt((alphanumeric1, tag("_")))),
@@ -273,9 +877,17 @@ fn p_identifier(input: &str) -> IResult<&str, &str> {
     .parse(input)
 }
 
+fn to_owned_string(input: Input) -> String {
+    input.fragment().to_string()
+}
+
+#[cfg(test)]
 mod tests {
-    use super::p_script;
-    use crate::parser::ast::{Dialogue, Module, Scene, ScenePart, TextPart};
+    use super::{p_expression, p_script, Input};
+    use crate::parser::ast::{
+        Assignment, Dialogue, Expression, Module, Operator, Scene, ScenePart, Span, Symbol,
+        TextPart, Visibility,
+    };
     use std::collections::HashMap;
 
     #[test]
@@ -286,7 +898,8 @@ mod tests {
                 "",
                 Module {
                     scenes: Vec::new(),
-                    fields: HashMap::new()
+                    fields: HashMap::new(),
+                    ..Default::default()
                 }
             ))
         )
@@ -306,10 +919,258 @@ mod tests {
                         name: "main".to_owned(),
                         content: vec![ScenePart::Dialogue(Dialogue {
                             parts: vec![TextPart::Text("Hello, there!".to_owned())]
-                        })]
-                    }]
+                        })],
+                        name_span: Span::default(),
+                        span: Span::default(),
+                    }],
+                    ..Default::default()
                 }
             ))
         );
     }
+
+    #[test]
+    fn model() {
+        let (rest, script) = p_script(
+            "@model Thing version 3\n\nmodel Item version 2\n    name: Text\n    -- Might break.\n    durability: dynamic Nat\n    on_use: Action(Character, Character)\n\n    upgrade 1 = [rename_field(\"uses\", \"durability\")]\nend\n",
+        )
+        .unwrap();
+
+        assert_eq!(rest, "");
+        let directive = script.model.unwrap();
+        assert_eq!(
+            (directive.name.as_str(), directive.version),
+            ("Thing", Some(3))
+        );
+        assert_eq!(script.models[0].version, 2);
+        assert_eq!(script.models[0].upgrades[0].from, 1);
+        let fields = &script.models[0].fields;
+        assert_eq!(
+            fields
+                .iter()
+                .map(|field| (field.name.as_str(), field.dynamic))
+                .collect::<Vec<_>>(),
+            vec![("name", false), ("durability", true), ("on_use", false)]
+        );
+        assert_eq!(fields[2].field_type.args.len(), 2);
+    }
+
+    #[test]
+    fn usings() {
+        let (rest, script) = p_script("using Icons\nusing Chars.(Echo, Jude)\n").unwrap();
+
+        assert_eq!(rest, "");
+        assert_eq!(
+            script
+                .usings
+                .iter()
+                .map(|using| using.path.join("."))
+                .collect::<Vec<_>>(),
+            vec!["Icons", "Chars.Echo", "Chars.Jude"]
+        );
+    }
+
+    #[test]
+    fn tests() {
+        let (rest, script) = p_script(
+            "test \"leaves\"\n    play(::main)\n    -- Picks the first option.\n    choose(1)\n    expect_end()\nend\n",
+        )
+        .unwrap();
+
+        assert_eq!(rest, "");
+        let test = &script.tests[0];
+        assert_eq!(test.name, "leaves");
+        assert_eq!(test.statements.len(), 3);
+    }
+
+    #[test]
+    fn visibility() {
+        let (rest, script) = p_script(
+            "private sharpness = 2\nprivate(children) base_damage = 3\nprivate dynamic gold = 0\ndynamic = 1\n\nprivate action sharpen()\n    polish()\nend\n\nprivate(children) model Weapon\n    damage: Nat\nend\n",
+        )
+        .unwrap();
+
+        assert_eq!(rest, "");
+        assert_eq!(script.fields["sharpness"].visibility, Visibility::Private);
+        assert_eq!(
+            script.fields["base_damage"].visibility,
+            Visibility::Children
+        );
+        assert!(script.fields["gold"].dynamic);
+        assert_eq!(script.fields["gold"].visibility, Visibility::Private);
+        assert!(!script.fields["dynamic"].dynamic);
+        assert_eq!(script.actions[0].visibility, Visibility::Private);
+        assert_eq!(script.models[0].visibility, Visibility::Children);
+    }
+
+    #[test]
+    fn externs() {
+        let (rest, script) =
+            p_script("extern player_name: Text\nprivate extern give: Action(Item, Nat)\n").unwrap();
+
+        assert_eq!(rest, "");
+        assert_eq!(script.externs[0].name, "player_name");
+        assert_eq!(script.externs[1].visibility, Visibility::Private);
+        assert_eq!(
+            script.externs[1].extern_type.to_string(),
+            "Action(Item, Nat)"
+        );
+    }
+
+    #[test]
+    fn handlers() {
+        let (rest, script) =
+            p_script("on Item.destroyed\n    * Something breaks.\nend\non_use = 1\n").unwrap();
+
+        assert_eq!(rest, "");
+        assert_eq!(script.handlers[0].model, "Item");
+        assert_eq!(script.handlers[0].signal, "destroyed");
+        assert_eq!(script.handlers[0].body.len(), 1);
+        assert!(script.fields.contains_key("on_use"));
+    }
+
+    #[test]
+    fn operators() {
+        let (rest, expression) = p_expression(Input::new("1 + ::damage * (2 - 3)")).unwrap();
+
+        assert_eq!(*rest.fragment(), "");
+        let Expression::Binary {
+            operator: Operator::Add,
+            right,
+            ..
+        } = expression
+        else {
+            panic!("`+` is applied last.");
+        };
+        assert!(matches!(
+            *right,
+            Expression::Binary {
+                operator: Operator::Multiply,
+                ref right,
+                ..
+            } if matches!(**right, Expression::Binary { operator: Operator::Subtract, .. })
+        ));
+
+        let (rest, expression) = p_expression(Input::new(
+            "::gold + 1 >= 10 and ::open == ::True or seen(\"- Hi!\")",
+        ))
+        .unwrap();
+        assert_eq!(*rest.fragment(), "");
+        let Expression::Binary {
+            operator: Operator::Or,
+            left,
+            ..
+        } = expression
+        else {
+            panic!("`or` is applied last.");
+        };
+        assert!(matches!(
+            *left,
+            Expression::Binary {
+                operator: Operator::And,
+                ref left,
+                ..
+            } if matches!(**left, Expression::Binary { operator: Operator::GreaterOrEqual, .. })
+        ));
+    }
+
+    #[test]
+    fn conditionals() {
+        let (rest, script) = p_script(
+            "scene main\n    if ::gold >= 10 then\n        - Deal.\n        if ::gold > 20 then\n        end\n    else\n        - Come back later.\n    end\n    - Bye.\nend\n",
+        )
+        .unwrap();
+
+        assert_eq!(rest, "");
+        let content = &script.scenes[0].content;
+        assert_eq!(content.len(), 2);
+        let ScenePart::Conditional(conditional) = &content[0] else {
+            panic!("The scene starts with a conditional.");
+        };
+        assert!(matches!(
+            &conditional.condition,
+            Expression::Binary { operator: Operator::GreaterOrEqual, right, .. }
+                if **right == Expression::Int(10)
+        ));
+        assert_eq!(conditional.content.len(), 2);
+        assert_eq!(conditional.otherwise.len(), 1);
+    }
+
+    #[test]
+    fn option_conditions() {
+        let (rest, script) = p_script(
+            "scene main\n    prompt\n        option Run away if scared\n        end\n        option Ask again [if seen(\"- Hi!\")]\n        end\n    end\nend\n",
+        )
+        .unwrap();
+
+        assert_eq!(rest, "");
+        let ScenePart::Prompt(prompt) = &script.scenes[0].content[0] else {
+            panic!("The scene is a prompt.");
+        };
+        assert_eq!(
+            prompt.options[0].text,
+            [TextPart::Text("Run away if scared".to_string())]
+        );
+        assert_eq!(prompt.options[0].condition, None);
+        assert_eq!(
+            prompt.options[1].text,
+            [TextPart::Text("Ask again".to_string())]
+        );
+        assert!(matches!(
+            &prompt.options[1].condition,
+            Some(Expression::Call { name, .. }) if name == "seen"
+        ));
+    }
+
+    #[test]
+    fn statements() {
+        let (rest, script) =
+            p_script(include_str!("../../examples/simple/wooden_sword.sol")).unwrap();
+
+        assert_eq!(rest, "");
+        let body = &script.actions[0].body;
+        let ScenePart::Conditional(conditional) = &body[0] else {
+            panic!("The action starts with a conditional.");
+        };
+        assert!(matches!(
+            &conditional.condition,
+            Expression::Member { object, name, args: Some(args), .. }
+                if **object == symbol("target") && name == "is_enemy" && args.is_empty()
+        ));
+        assert!(matches!(
+            &conditional.content[0],
+            ScenePart::Expression(Expression::Member { name, args: Some(args), .. })
+                if name == "damage" && *args == [symbol("attack_damage")]
+        ));
+        assert!(matches!(
+            &conditional.content[1],
+            ScenePart::Assignment(Assignment {
+                target: Expression::Member { object, name, args: None, .. },
+                operator: Some(Operator::Subtract),
+                value: Expression::Int(1),
+                ..
+            }) if **object == symbol("self") && name == "durability"
+        ));
+        assert!(matches!(conditional.otherwise[0], ScenePart::Narration(_)));
+
+        let (_, script) =
+            p_script("scene main\n    ::gold = ::gold * 2\n    ::gold == 2\nend\n").unwrap();
+        assert!(matches!(
+            &script.scenes[0].content[..],
+            [
+                ScenePart::Assignment(Assignment { operator: None, .. }),
+                ScenePart::Expression(Expression::Binary {
+                    operator: Operator::Equal,
+                    ..
+                })
+            ]
+        ));
+    }
+
+    fn symbol(name: &str) -> Expression {
+        Expression::Symbol(Symbol {
+            path: vec![name.to_string()],
+            span: Span::default(),
+        })
+    }
 }
@@ -0,0 +1,330 @@
+//! The read history: which lines and options the player saw, and how often.
+//!
+//! Every line and option is known by an ID, made of the scene, or the handler, it's in,
+//! the prompts and options leading to it, and how it's written. Edits elsewhere in the
+//! scene leave it alone, and it's kept by saves:
+//!
+//! ```text
+//! intro::main - Hi!
+//! intro::main - Hi! #2
+//! intro::main prompt Where to? > Outside
+//! intro::main prompt Where to? > Outside * You step outside.
+//! achievements on Item.destroyed * Something breaks.
+//! ```
+//!
+//! The second line written the same way in the same place is `#2`, and so on, as is the
+//! second handler of the same signal in a module. The state counts a line as visited each
+//! time it's played, and an option each time it's chosen. Scenes read the counts with
+//! `visits("- Hi!")`, or `seen("- Hi!")`, which is `::True` or `::False`. Those are lines
+//! of the scene, or handler, being played, unless another scene is named first, as in
+//! `seen(::intro::main, "prompt Where to? > Outside")`. Lines depend on them through
+//! conditionals, and options through conditions of their own:
+//!
+//! ```lua
+//! if seen("- Hi!") then
+//!     - Welcome back.
+//! end
+//! prompt
+//!     option Who are you, again? [if seen("prompt > Who are you?")]
+//!     end
+//! end
+//! ```
+//!
+//! Games skip text the player read already, as long as [`SceneRunner::read_before`] says so,
+//! and grey out options chosen before, with [`SceneRunner::option_ids`] and [`State::seen`].
+//!
+//! [`State::seen`]: super::state::State::seen
+use super::{
+    host::CallError,
+    nesting, render_text,
+    save::{anchor, Branch},
+    Nesting, SceneRunner,
+};
+use crate::{
+    introspec::repr::Value,
+    parser::ast::{Expression, Prompt, ScenePart},
+};
+
+impl SceneRunner<'_> {
+    /// The ID of the line the scene is on, right after it's returned as dialogue or narration.
+    pub fn line_id(&self) -> Option<String> {
+        let frame = self.frames.last()?;
+        match frame.parts.get(frame.played.checked_sub(1)?)? {
+            ScenePart::Dialogue(_) | ScenePart::Narration(_) => Some(self.played_id()),
+            _ => None,
+        }
+    }
+
+    /// Whether the line the scene is on was played before, so the game can skip it.
+    pub fn read_before(&self) -> bool {
+        self.line_id()
+            .is_some_and(|line| self.state.visits(&line) > 1)
+    }
+
+    /// The IDs of the options shown of the prompt the scene is waiting on, in order.
+    pub fn option_ids(&self) -> Vec<String> {
+        match self.prompt {
+            // The prompt was shown, so its conditions hold, or don't.
+            Some(prompt) => self
+                .shown(prompt)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|index| self.option_id(prompt, index))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The ID of the `index`th option of `prompt`, the last part played.
+    pub(super) fn option_id(&self, prompt: &Prompt, index: usize) -> String {
+        format!("{} > {}", self.played_id(), option_segment(prompt, index))
+    }
+
+    /// The ID of the last part played.
+    pub(super) fn played_id(&self) -> String {
+        let frame = self.frames.last().expect("A part was played.");
+        format!(
+            "{} {}",
+            self.block(),
+            part_segment(frame.parts, frame.played - 1)
+        )
+    }
+
+    /// The ID of the parts being played, as in `intro::main prompt Where to? > Outside`.
+    fn block(&self) -> String {
+        let root = self
+            .frames
+            .iter()
+            .rposition(|frame| {
+                frame.handler.is_some_and(|handling| {
+                    std::ptr::eq(handling.handler.body.as_slice(), frame.parts)
+                })
+            })
+            .unwrap_or(0);
+        let mut block = self.context();
+        for (parent, frame) in self.frames[root..].iter().zip(&self.frames[root + 1..]) {
+            let part = &parent.parts[parent.played - 1];
+            let nested = match nesting(part, frame.parts) {
+                Some(Nesting::Option(prompt, option)) => option_segment(prompt, option),
+                Some(Nesting::Branch(Branch::Then)) => "then".to_string(),
+                Some(Nesting::Branch(Branch::Else)) => "else".to_string(),
+                None => unreachable!("Only options and branches are played in frames."),
+            };
+            block = format!(
+                "{} {} > {}",
+                block,
+                part_segment(parent.parts, parent.played - 1),
+                nested
+            );
+        }
+        block
+    }
+
+    /// The scene, as in `intro::main`, or the handler, as in `achievements on Item.destroyed`,
+    /// being played.
+    fn context(&self) -> String {
+        match self.handler() {
+            Some(handling) => handling.id(),
+            None => self
+                .module
+                .iter()
+                .map(String::as_str)
+                .chain([self.name])
+                .collect::<Vec<_>>()
+                .join("::"),
+        }
+    }
+
+    /// Runs `visits("- Hi!")` and `seen("- Hi!")`, optionally of another scene,
+    /// as in `seen(::intro::main, "- Hi!")`.
+    pub(super) fn history(&self, name: &str, args: &[Expression]) -> Result<Value, CallError> {
+        let (context, line) = match args {
+            [line] => (self.context(), line),
+            [Expression::Symbol(symbol), line] => (self.absolute(&symbol.path).join("::"), line),
+            _ => {
+                return Err(CallError::Evaluation {
+                    message: format!(
+                        "`{}` takes a line of the scene, as in `{}(\"- Hi!\")`, \
+                        or of another scene, as in `{}(::intro::main, \"- Hi!\")`.",
+                        name, name, name
+                    ),
+                })
+            }
+        };
+        let Value::Text(line) = self.evaluate(line)? else {
+            return Err(CallError::Evaluation {
+                message: format!(
+                    "`{}` takes a line as it's written, in quotes, as in `{}(\"- Hi!\")`.",
+                    name, name
+                ),
+            });
+        };
+        let visits = self.state.visits(&format!("{} {}", context, line));
+        Ok(match name {
+            "visits" => Value::Nat(visits),
+            _ => Value::Variant(if visits > 0 { "True" } else { "False" }.to_string(), None),
+        })
+    }
+}
+
+/// Whether calls to `name` read the history.
+pub(super) fn reads_history(name: &str) -> bool {
+    name == "seen" || name == "visits"
+}
+
+/// The `index`th part, as it's written, followed by `#2` and so on when it's written
+/// the same way as parts before it.
+fn part_segment(parts: &[ScenePart], index: usize) -> String {
+    let written = anchor(&parts[index]);
+    let before = parts[..index]
+        .iter()
+        .filter(|part| anchor(part) == written)
+        .count();
+    numbered(written, before)
+}
+
+fn option_segment(prompt: &Prompt, index: usize) -> String {
+    let written = render_text(&prompt.options[index].text);
+    let before = prompt.options[..index]
+        .iter()
+        .filter(|option| render_text(&option.text) == written)
+        .count();
+    numbered(written, before)
+}
+
+/// `written`, followed by `#2` and so on when `before` things are written the same way.
+pub(super) fn numbered(written: String, before: usize) -> String {
+    match before {
+        0 => written,
+        before => format!("{} #{}", written, before + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        introspec::project::ModulePath,
+        parser,
+        runtime::{state::State, Event, SceneRunner},
+    };
+
+    #[test]
+    fn tracks_what_was_read() {
+        let module = parser::parse_module(
+            "intro.sol",
+            "scene main\n    - Hi!\n    - Hi!\n    prompt Where to?\n        option Outside\n            * You step outside.\n        end\n        option Nowhere\n        end\n    end\n    * Outside: {seen(\"prompt Where to? > Outside\")}, {visits(\"- Hi!\")} hi.\nend\n",
+        )
+        .unwrap();
+        let path = ModulePath::parse("intro").unwrap();
+        let mut state = State::new();
+        for _ in 0..2 {
+            let mut runner = SceneRunner::new(&module.scenes[0]).with_state(&path, state);
+            let mut read = Vec::new();
+            loop {
+                match runner.next().unwrap() {
+                    Event::Prompt { .. } => {
+                        assert_eq!(
+                            runner.option_ids(),
+                            [
+                                "intro::main prompt Where to? > Outside",
+                                "intro::main prompt Where to? > Nowhere"
+                            ]
+                        );
+                        runner.choose(0).unwrap();
+                    }
+                    Event::End => break,
+                    Event::Dialogue { text, .. } | Event::Narration { text } => {
+                        read.push((runner.line_id().unwrap(), text, runner.read_before()))
+                    }
+                    _ => {}
+                }
+            }
+            state = runner.into_state();
+            let again = state.visits("intro::main - Hi!") > 1;
+            assert_eq!(
+                read.iter()
+                    .map(|(id, _, read_before)| (id.as_str(), *read_before))
+                    .collect::<Vec<_>>(),
+                [
+                    ("intro::main - Hi!", again),
+                    ("intro::main - Hi! #2", again),
+                    ("intro::main prompt Where to? > Outside * You step outside.", again),
+                    (
+                        "intro::main * Outside: {seen(\"prompt Where to? > Outside\")}, {visits(\"- Hi!\")} hi.",
+                        again
+                    ),
+                ]
+            );
+            assert_eq!(
+                read[3].1,
+                format!("Outside: ::True, {} hi.", state.visits("intro::main - Hi!"))
+            );
+        }
+        assert!(state.seen("intro::main prompt Where to? > Outside"));
+        assert!(!state.seen("intro::main prompt Where to? > Nowhere"));
+        assert_eq!(state.history().count(), 5);
+    }
+
+    #[test]
+    fn gates_on_what_was_read() {
+        let module = parser::parse_module(
+            "intro.sol",
+            "scene main\n    if seen(\"- Hi!\") then\n        - Welcome back.\n    end\n    - Hi!\n    prompt\n        option Who are you?\n        end\n        option Who are you, again? [if seen(\"prompt > Who are you?\")]\n        end\n    end\n    prompt\n        option Leave. [if visits(\"prompt > Who are you?\") > 2]\n        end\n    end\n    - Bye.\nend\n",
+        )
+        .unwrap();
+        let path = ModulePath::parse("intro").unwrap();
+        let mut state = State::new();
+        let mut played = Vec::new();
+        for _ in 0..3 {
+            let mut runner = SceneRunner::new(&module.scenes[0]).with_state(&path, state);
+            let mut read = Vec::new();
+            loop {
+                match runner.next().unwrap() {
+                    Event::Prompt { options, .. } => {
+                        assert_eq!(runner.option_ids().len(), options.len());
+                        read.push(options.join(" / "));
+                        runner.choose(0).unwrap();
+                    }
+                    Event::Dialogue { text, .. } => read.push(text),
+                    Event::End => break,
+                    _ => {}
+                }
+            }
+            played.push(read);
+            state = runner.into_state();
+        }
+        assert_eq!(
+            played,
+            [
+                vec!["Hi!", "Who are you?", "Bye."],
+                vec![
+                    "Welcome back.",
+                    "Hi!",
+                    "Who are you? / Who are you, again?",
+                    "Bye."
+                ],
+                vec![
+                    "Welcome back.",
+                    "Hi!",
+                    "Who are you? / Who are you, again?",
+                    "Leave.",
+                    "Bye."
+                ],
+            ]
+        );
+
+        let mut runner = SceneRunner::new(&module.scenes[0]).with_state(&path, state);
+        while !matches!(runner.next().unwrap(), Event::Prompt { .. }) {}
+        assert_eq!(
+            runner.option_ids(),
+            [
+                "intro::main prompt > Who are you?",
+                "intro::main prompt > Who are you, again?"
+            ]
+        );
+        runner.choose(1).unwrap();
+        assert!(runner
+            .state()
+            .seen("intro::main prompt > Who are you, again?"));
+    }
+}
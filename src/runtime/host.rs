@@ -0,0 +1,355 @@
+//! Values and functions the game supplies for the names modules declare `extern`.
+//!
+//! Functions are declared as actions, as in `Action(Float)`, and are only called with
+//! arguments of those types. Numbers are converted to the declared type on the way in,
+//! so a function declared with a `Float` is given a `Value::Float`, even for `pause(1)`.
+//!
+//! ```
+//! use sol_lang::{introspec::repr::Value, runtime::host::HostRegistry};
+//!
+//! let mut host = HostRegistry::new();
+//! host.register_value("player_name", "Text", Value::Text("Echo".to_string()))
+//!     .unwrap();
+//! host.register_function("shake_screen", "Action(Nat)", |_| Ok(Value::Void(())))
+//!     .unwrap();
+//!
+//! assert_eq!(
+//!     host.manifest(),
+//!     "extern player_name: Text\nextern shake_screen: Action(Nat)\n"
+//! );
+//! ```
+//!
+//! Functions the scene waits on, as in `wait(1 second)`, are registered with
+//! [`HostRegistry::register_suspending`]. They start whatever the scene waits on,
+//! and the [`SceneRunner`](super::SceneRunner) suspends until the game resumes it.
+use crate::{
+    introspec::{eval, repr::Value},
+    parser::{self, ast::TypeExpression, ParseError},
+};
+use miette::Diagnostic;
+use std::{collections::BTreeMap, rc::Rc};
+use thiserror::Error;
+
+/// A function the game supplies. It's given the arguments of the call,
+/// and returns its result, or says what went wrong.
+pub type HostFunction = Rc<dyn Fn(&[Value]) -> Result<Value, String>>;
+
+/// A function the scene waits on. It's given the arguments of the call, and starts
+/// what the scene waits on, or says what went wrong.
+pub type SuspendingFunction = Rc<dyn Fn(&[Value]) -> Result<(), String>>;
+
+#[derive(Clone)]
+pub enum HostBinding {
+    Value(Value),
+    Function(HostFunction),
+    Suspending(SuspendingFunction),
+}
+
+/// What a call to the game came to.
+#[derive(Debug, Clone)]
+pub enum Completion {
+    /// The function returned, with this value.
+    Done(Value),
+    /// The function started something, which the scene waits on.
+    Suspended,
+}
+
+impl std::fmt::Debug for HostBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HostBinding::Value(value) => f.debug_tuple("Value").field(value).finish(),
+            HostBinding::Function(_) => f.debug_tuple("Function").finish_non_exhaustive(),
+            HostBinding::Suspending(_) => f.debug_tuple("Suspending").finish_non_exhaustive(),
+        }
+    }
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error(transparent)]
+pub enum RegistryError {
+    #[diagnostic(transparent)]
+    Parse(#[from] ParseError),
+    #[diagnostic(transparent)]
+    AlreadyRegistered(#[from] AlreadyRegistered),
+    #[diagnostic(transparent)]
+    NotAnAction(#[from] NotAnAction),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{name}` is already registered.")]
+#[diagnostic(code(sol::host_registered_twice))]
+pub struct AlreadyRegistered {
+    pub name: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{name}` is a function, but is declared as `{extern_type}`.")]
+#[diagnostic(
+    code(sol::host_not_an_action),
+    help("Functions are declared as actions, as in `Action(Float)`.")
+)]
+pub struct NotAnAction {
+    pub name: String,
+    pub extern_type: String,
+}
+
+/// Why a call to the game failed.
+#[derive(Error, Debug, Diagnostic)]
+pub enum CallError {
+    #[error("`{name}` is called, but the game didn't register it.")]
+    #[diagnostic(
+        code(sol::runtime::unregistered_call),
+        help("Register it with the host registry. Registered are {registered}.")
+    )]
+    Unregistered { name: String, registered: String },
+    #[error("`{name}` is a value the game supplies, and can't be called.")]
+    #[diagnostic(code(sol::runtime::not_callable))]
+    NotCallable { name: String },
+    #[error("`{name}` is an `{extern_type}`, but was called with ({arguments}).")]
+    #[diagnostic(code(sol::runtime::mismatched_arguments))]
+    MismatchedArguments {
+        name: String,
+        extern_type: String,
+        arguments: String,
+    },
+    #[error("`{name}` failed: {message}")]
+    #[diagnostic(code(sol::runtime::host_failure))]
+    Failed { name: String, message: String },
+    #[error("The scene waits on `{name}`, so it can only be called on a line of its own.")]
+    #[diagnostic(code(sol::runtime::nested_suspension))]
+    NestedSuspension { name: String },
+    #[error("{message}")]
+    #[diagnostic(code(sol::runtime::evaluation))]
+    Evaluation { message: String },
+}
+
+impl From<eval::EvaluationError> for CallError {
+    fn from(error: eval::EvaluationError) -> Self {
+        CallError::Evaluation {
+            message: error.message,
+        }
+    }
+}
+
+/// What the game supplies, by name, along with the type each name is declared with.
+#[derive(Debug, Default, Clone)]
+pub struct HostRegistry {
+    bindings: BTreeMap<String, (TypeExpression, HostBinding)>,
+}
+
+impl HostRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Supplies `value` for `name`, which is declared as an `extern_type`, as in `"Text"`.
+    pub fn register_value(
+        &mut self,
+        name: &str,
+        extern_type: &str,
+        value: Value,
+    ) -> Result<(), RegistryError> {
+        self.register(name, extern_type, HostBinding::Value(value))
+    }
+
+    /// Supplies `function` for `name`, which is declared as an `extern_type`, as in `"Action(Item)"`.
+    pub fn register_function(
+        &mut self,
+        name: &str,
+        extern_type: &str,
+        function: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+    ) -> Result<(), RegistryError> {
+        self.register(name, extern_type, HostBinding::Function(Rc::new(function)))
+    }
+
+    /// Supplies `function` for `name`, which is declared as an `extern_type`, as in `"Action(Nat)"`.
+    /// Calls to it suspend the scene, until the game resumes it.
+    pub fn register_suspending(
+        &mut self,
+        name: &str,
+        extern_type: &str,
+        function: impl Fn(&[Value]) -> Result<(), String> + 'static,
+    ) -> Result<(), RegistryError> {
+        self.register(
+            name,
+            extern_type,
+            HostBinding::Suspending(Rc::new(function)),
+        )
+    }
+
+    fn register(
+        &mut self,
+        name: &str,
+        extern_type: &str,
+        binding: HostBinding,
+    ) -> Result<(), RegistryError> {
+        let extern_type = parser::parse_type(name, extern_type)?;
+        if !matches!(binding, HostBinding::Value(_)) && parameters(&extern_type).is_none() {
+            return Err(NotAnAction {
+                name: name.to_string(),
+                extern_type: extern_type.to_string(),
+            }
+            .into());
+        }
+        if self.bindings.contains_key(name) {
+            return Err(AlreadyRegistered {
+                name: name.to_string(),
+            }
+            .into());
+        }
+        self.bindings
+            .insert(name.to_string(), (extern_type, binding));
+        Ok(())
+    }
+
+    /// What the game supplies for `name`, if anything.
+    pub fn get(&self, name: &str) -> Option<&HostBinding> {
+        self.bindings.get(name).map(|(_, binding)| binding)
+    }
+
+    /// The type `name` was registered as.
+    pub fn extern_type(&self, name: &str) -> Option<&TypeExpression> {
+        self.bindings.get(name).map(|(extern_type, _)| extern_type)
+    }
+
+    /// Calls the function registered for `name` with `args`, converted to the types it's declared with.
+    pub fn call(&self, name: &str, args: &[Value]) -> Result<Completion, CallError> {
+        let Some((extern_type, binding)) = self.bindings.get(name) else {
+            let registered = self
+                .bindings
+                .keys()
+                .map(|name| format!("`{}`", name))
+                .collect::<Vec<_>>();
+            return Err(CallError::Unregistered {
+                name: name.to_string(),
+                registered: if registered.is_empty() {
+                    "none".to_string()
+                } else {
+                    registered.join(", ")
+                },
+            });
+        };
+        let mismatched = || CallError::MismatchedArguments {
+            name: name.to_string(),
+            extern_type: extern_type.to_string(),
+            arguments: args.iter().map(eval::show).collect::<Vec<_>>().join(", "),
+        };
+        let parameters = parameters(extern_type).unwrap_or_default();
+        if parameters.len() != args.len() {
+            return Err(mismatched());
+        }
+        let args = parameters
+            .iter()
+            .zip(args)
+            .map(|(parameter, arg)| convert(arg, parameter))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(mismatched)?;
+        let failed = |message| CallError::Failed {
+            name: name.to_string(),
+            message,
+        };
+
+        match binding {
+            HostBinding::Value(_) => Err(CallError::NotCallable {
+                name: name.to_string(),
+            }),
+            HostBinding::Function(function) => {
+                function(&args).map(Completion::Done).map_err(failed)
+            }
+            HostBinding::Suspending(function) => function(&args)
+                .map(|()| Completion::Suspended)
+                .map_err(failed),
+        }
+    }
+
+    /// Renders a host manifest declaring everything registered, sorted by name,
+    /// for `index.sol` to point at with `host = "host.sol"`.
+    pub fn manifest(&self) -> String {
+        self.bindings
+            .iter()
+            .map(|(name, (extern_type, _))| format!("extern {}: {}\n", name, extern_type))
+            .collect()
+    }
+}
+
+/// The parameters of an action type, as in `Action(Item, Nat)`.
+fn parameters(extern_type: &TypeExpression) -> Option<&[TypeExpression]> {
+    match extern_type.name.as_str() {
+        "Action" => Some(&extern_type.args),
+        "Signal" if extern_type.args.is_empty() => Some(&[]),
+        _ => None,
+    }
+}
+
+/// Converts `value` to a `parameter` type, if it fits. Numbers widen, from `Nat` to `Int`
+/// to `Float`. Values of types the runtime doesn't know, as models, are passed as they are.
+fn convert(value: &Value, parameter: &TypeExpression) -> Option<Value> {
+    Some(match (parameter.name.as_str(), value) {
+        ("Nat", Value::Nat(_)) | ("Int", Value::Int(_)) | ("Float", Value::Float(_)) => {
+            value.clone()
+        }
+        ("Text", Value::Text(_)) => value.clone(),
+        ("Int", Value::Nat(value)) => Value::Int(i32::try_from(*value).ok()?),
+        ("Float", Value::Nat(value)) => Value::Float(*value as f32),
+        ("Float", Value::Int(value)) => Value::Float(*value as f32),
+        ("Nat" | "Int" | "Float" | "Text", _) => return None,
+        _ => value.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HostBinding, HostRegistry, RegistryError};
+    use crate::{introspec::repr::Value, parser};
+
+    #[test]
+    fn registers_bindings() {
+        let mut host = HostRegistry::new();
+        host.register_function("give", "Action(Item, Nat)", |args| match args {
+            [_, Value::Int(amount)] => Ok(Value::Int(*amount)),
+            _ => Err("Expected an item and an amount.".to_string()),
+        })
+        .unwrap();
+        host.register_value("coins", "Nat", Value::Nat(3)).unwrap();
+
+        assert!(matches!(
+            host.register_value("coins", "Nat", Value::Nat(4)),
+            Err(RegistryError::AlreadyRegistered(_))
+        ));
+        assert!(matches!(
+            host.register_value("gems", "Nat(", Value::Nat(4)),
+            Err(RegistryError::Parse(_))
+        ));
+        assert!(matches!(
+            host.register_function("gems", "Nat", |_| Ok(Value::Nat(4))),
+            Err(RegistryError::NotAnAction(_))
+        ));
+        assert!(matches!(
+            host.get("coins"),
+            Some(HostBinding::Value(Value::Nat(3)))
+        ));
+        let Some(HostBinding::Function(give)) = host.get("give") else {
+            panic!("`give` is a function.");
+        };
+        assert!(matches!(
+            give(&[Value::Void(()), Value::Int(2)]),
+            Ok(Value::Int(2))
+        ));
+
+        let manifest = parser::parse_module("host.sol", &host.manifest()).unwrap();
+        assert_eq!(
+            manifest
+                .externs
+                .iter()
+                .map(|declaration| (
+                    declaration.name.as_str(),
+                    declaration.extern_type.to_string()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("coins", "Nat".to_string()),
+                ("give", "Action(Item, Nat)".to_string())
+            ]
+        );
+    }
+}
@@ -1,3 +1,1292 @@
 //! Runtime for executing `.sol` functions.
-//! 
-//! Great for testing / previewing scenes and item interactions.
\ No newline at end of file
+//!
+//! Great for testing / previewing scenes and item interactions.
+//!
+//! Scenes are played by a [`SceneRunner`], which the engine pulls [`Event`]s from,
+//! one at a time, and tells which option the player chose when the scene prompts:
+//!
+//! ```
+//! use sol_lang::{parser, runtime::{Event, SceneRunner}};
+//!
+//! let module = parser::parse_module(
+//!     "intro.sol",
+//!     "scene main\n    [Echo]\n    - Hi!\n    prompt\n        option Bye.\n            * Echo waves.\n        end\n    end\nend\n",
+//! )
+//! .unwrap();
+//! let mut runner = SceneRunner::new(&module.scenes[0]);
+//!
+//! loop {
+//!     match runner.next().unwrap() {
+//!         Event::Prompt { .. } => runner.choose(0).unwrap(),
+//!         Event::End => break,
+//!         _ => {}
+//!     }
+//! }
+//! ```
+//!
+//! Played with a [`HostRegistry`], the runner calls what the game registered itself,
+//! instead of leaving calls to the engine, and fills interpolations in with what they return.
+//! Calling anything the game didn't register is then an error.
+//!
+//! Played with a [`State`], scenes read and write the game's variables, and their own:
+//! `local(::tries, 0)` declares a variable that lasts as long as the scene, and
+//! `set(::tries, ::tries + 1)` sets either kind. Interpolations of variables are filled in,
+//! and conditionals play their parts depending on them:
+//!
+//! ```lua
+//! if ::gold >= 10 and ::shop::open == ::True then
+//!     - That'll be ten gold.
+//! else
+//!     - Come back later.
+//! end
+//! ```
+//!
+//! Comparisons, with `==`, `!=`, `<`, `<=`, `>` and `>=`, and `and` and `or`, give `::True`
+//! or `::False`.
+//!
+//! Actions of assets, as in `action on_use(user, target)`, are run by a runner too, from
+//! [`SceneRunner::action`], with the arguments the game calls them with. Those are often
+//! references, to assets, as in `Value::AssetRef`, or to the game's own objects, as in
+//! `Value::Host`. Actions are played like scenes, so their narration is part of the same
+//! stream of events, and they fail the same way. They read the fields of their asset, as in
+//! `attack_damage` or `self.durability`, set its dynamic ones, as in `self.durability -= 1`,
+//! and call the game on values, as in `target.damage(3)`, which is `damage(target, 3)`.
+//! Once they're over, [`SceneRunner::result`] is what their last part came to.
+//!
+//! Played with [`Signals`], scenes emit the signals of assets, and the handlers of
+//! those signals are played as part of the scene.
+//!
+//! The state counts the visits of every line and option, by an ID the [`history`]
+//! gives them, for scenes to read with `seen("- Hi!")`, and games to skip read text.
+use crate::{
+    introspec::{eval, project::ModulePath, repr::Value},
+    parser::ast::{
+        unescape, Assignment, Expression, Module, Operator, Prompt, Scene, ScenePart, Symbol,
+        TextPart,
+    },
+    unparser::print_dialogue,
+};
+use host::{CallError, Completion, HostBinding, HostRegistry};
+use miette::Diagnostic;
+use replay::{Playthrough, Step};
+use save::Branch;
+use serde::{Deserialize, Serialize};
+use signal::{Handling, Signals, UndeclaredSignal};
+use state::{State, StateError};
+use std::{cell::RefCell, collections::BTreeMap};
+use thiserror::Error;
+
+pub mod history;
+pub mod host;
+pub mod replay;
+pub mod save;
+pub mod signal;
+pub mod state;
+
+/// Something a playing scene does, for the engine to show.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event<'a> {
+    /// Someone else speaks from now on, as in `[Echo happy]`.
+    /// `[& sad]` keeps the speaker, changing only the modifiers.
+    SpeakerChanged {
+        speaker: String,
+        modifiers: Vec<String>,
+    },
+    /// A line said by the current speaker, if there is one, as in `- Hello, there!`.
+    Dialogue {
+        speaker: Option<String>,
+        text: String,
+    },
+    /// A line nobody says, as in `* You step outside.`.
+    Narration { text: String },
+    /// A choice for the player. The scene waits on it, returning it again,
+    /// until an option is picked with [`SceneRunner::choose`].
+    Prompt {
+        text: Option<String>,
+        options: Vec<String>,
+    },
+    /// A call for the game to handle, as in `shake_screen(3)`.
+    /// Scenes played with a host call it instead.
+    Call {
+        name: &'a str,
+        args: &'a [Expression],
+    },
+    /// The scene waits on a call to the host, as in `wait(1 second)`. It's returned
+    /// again, until the game is done with it and calls [`SceneRunner::resume`].
+    Suspended { name: &'a str },
+    /// The scene is over. It's returned again for as long as it's pulled.
+    End,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error(transparent)]
+pub enum RunError {
+    #[diagnostic(transparent)]
+    Call(#[from] CallError),
+    #[diagnostic(transparent)]
+    State(#[from] StateError),
+    #[diagnostic(transparent)]
+    Signal(#[from] UndeclaredSignal),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+pub enum ChooseError {
+    #[error("The scene isn't waiting on a prompt.")]
+    #[diagnostic(code(sol::runtime::not_prompted))]
+    NotPrompted,
+    #[error("There's no option {index}, the prompt only has {count}.")]
+    #[diagnostic(code(sol::runtime::no_such_option))]
+    NoSuchOption { index: usize, count: usize },
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Condition(#[from] RunError),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+pub enum ActionError {
+    #[error("There's no action `{name}` in the module.")]
+    #[diagnostic(
+        code(sol::runtime::unknown_action),
+        help("Its actions are {available}.")
+    )]
+    Unknown { name: String, available: String },
+    #[error("`{name}` takes ({parameters}), but was given ({arguments}).")]
+    #[diagnostic(code(sol::runtime::mismatched_action_arguments))]
+    MismatchedArguments {
+        name: String,
+        parameters: String,
+        arguments: String,
+    },
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("The scene isn't waiting on the game.")]
+#[diagnostic(code(sol::runtime::not_suspended))]
+pub struct NotSuspended;
+
+/// The speaker of the lines of a scene, as in `[Echo happy]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Speaker {
+    pub name: String,
+    pub modifiers: Vec<String>,
+}
+
+/// Parts of a scene being played, along with how many of them were.
+#[derive(Debug, Clone)]
+struct Frame<'a> {
+    parts: &'a [ScenePart],
+    played: usize,
+    /// The handler of a signal the parts are from, unless they're the scene's own.
+    handler: Option<&'a Handling<'a>>,
+}
+
+/// How the parts of a frame are reached from the part played before them.
+enum Nesting<'a> {
+    /// The `index`th option of a prompt.
+    Option(&'a Prompt, usize),
+    Branch(Branch),
+}
+
+/// How `parts` are reached from `part`, if they're an option of a prompt, or a branch of
+/// a conditional.
+fn nesting<'a>(part: &'a ScenePart, parts: &[ScenePart]) -> Option<Nesting<'a>> {
+    match part {
+        ScenePart::Prompt(prompt) => prompt
+            .options
+            .iter()
+            .position(|option| std::ptr::eq(option.content.as_slice(), parts))
+            .map(|index| Nesting::Option(prompt, index)),
+        ScenePart::Conditional(conditional)
+            if std::ptr::eq(conditional.content.as_slice(), parts) =>
+        {
+            Some(Nesting::Branch(Branch::Then))
+        }
+        ScenePart::Conditional(conditional)
+            if std::ptr::eq(conditional.otherwise.as_slice(), parts) =>
+        {
+            Some(Nesting::Branch(Branch::Else))
+        }
+        _ => None,
+    }
+}
+
+/// A scene being played, one event at a time.
+#[derive(Debug, Clone)]
+pub struct SceneRunner<'a> {
+    /// The name of the scene, or the action, being played.
+    name: &'a str,
+    content: &'a [ScenePart],
+    /// The parts being played, innermost last. Options of prompts, and branches of
+    /// conditionals, are played in a frame of their own, which returns to the scene
+    /// when it's over.
+    frames: Vec<Frame<'a>>,
+    speaker: Option<Speaker>,
+    /// The prompt the scene is waiting on an option for.
+    prompt: Option<&'a Prompt>,
+    /// What the game registered, for the scene to call.
+    host: Option<&'a HostRegistry>,
+    /// The call to the host the scene is waiting on.
+    suspended: Option<&'a str>,
+    signals: Option<&'a Signals<'a>>,
+    state: State,
+    /// The path of the scene's module.
+    module: Vec<String>,
+    /// The variables declared by the scene with `local`.
+    locals: BTreeMap<String, Value>,
+    /// The module of the action being run, whose fields it reads, as in `attack_damage`.
+    asset: Option<&'a Module>,
+    /// What the last part played came to, as in `target.damage(3)`. Only calls come to
+    /// anything but `Value::Void`.
+    result: Value,
+    recording: Option<RefCell<Playthrough>>,
+    /// Calls answered with what the game returned when they were recorded.
+    replayed: Option<RefCell<replay::Replayed>>,
+}
+
+impl<'a> SceneRunner<'a> {
+    pub fn new(scene: &'a Scene) -> Self {
+        Self::start(&scene.name, &scene.content)
+    }
+
+    /// Runs the action called `name` of `module`, as the game calls it with `args`.
+    /// They're given to the action's parameters, as in `::user` and `::target`.
+    pub fn action(module: &'a Module, name: &str, args: Vec<Value>) -> Result<Self, ActionError> {
+        let Some(action) = module.actions.iter().find(|action| action.name == name) else {
+            let available = module
+                .actions
+                .iter()
+                .map(|action| format!("`{}`", action.name))
+                .collect::<Vec<_>>();
+            return Err(ActionError::Unknown {
+                name: name.to_string(),
+                available: if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.join(", ")
+                },
+            });
+        };
+        if action.parameters.len() != args.len() {
+            return Err(ActionError::MismatchedArguments {
+                name: name.to_string(),
+                parameters: action.parameters.join(", "),
+                arguments: args.iter().map(eval::show).collect::<Vec<_>>().join(", "),
+            });
+        }
+        let mut runner = Self::start(&action.name, &action.body);
+        runner.locals = action.parameters.iter().cloned().zip(args).collect();
+        runner.asset = Some(module);
+        Ok(runner)
+    }
+
+    fn start(name: &'a str, content: &'a [ScenePart]) -> Self {
+        SceneRunner {
+            name,
+            content,
+            frames: vec![Frame {
+                parts: content,
+                played: 0,
+                handler: None,
+            }],
+            speaker: None,
+            prompt: None,
+            host: None,
+            suspended: None,
+            signals: None,
+            state: State::new(),
+            module: Vec::new(),
+            locals: BTreeMap::new(),
+            asset: None,
+            result: Value::Void(()),
+            recording: None,
+            replayed: None,
+        }
+    }
+
+    /// Calls what `host` supplies, instead of leaving calls to the engine.
+    pub fn with_host(mut self, host: &'a HostRegistry) -> Self {
+        self.host = Some(host);
+        self
+    }
+
+    /// Emits signals to `signals`, playing their handlers as part of the scene.
+    pub fn with_signals(mut self, signals: &'a Signals<'a>) -> Self {
+        self.signals = Some(signals);
+        self
+    }
+
+    /// Plays with `state`, for a scene of the module at `module`.
+    pub fn with_state(mut self, module: &ModulePath, state: State) -> Self {
+        self.module = module.0.clone();
+        self.state = state;
+        self
+    }
+
+    /// Who is speaking, if anyone.
+    pub fn speaker(&self) -> Option<&Speaker> {
+        self.speaker.as_ref()
+    }
+
+    /// The game's variables, as the scene left them so far.
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
+
+    /// What the action came to, once it's over: what its last part did, as in
+    /// `target.damage(3)`, which is `Value::Void` unless it's a call.
+    pub fn result(&self) -> Option<&Value> {
+        self.frames.is_empty().then_some(&self.result)
+    }
+
+    /// Ends the scene, keeping the game's variables, for the next one.
+    pub fn into_state(self) -> State {
+        self.state
+    }
+
+    /// Plays until the scene does something. Only fails on calls to the host,
+    /// on variables being set wrong, and on signals assets don't declare.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Event<'a>, RunError> {
+        // Events returned again, while the scene waits or after it ends, are only transcribed once.
+        let repeated = self.suspended.is_some() || self.prompt.is_some() || self.frames.is_empty();
+        let event = self.play()?;
+        if !repeated {
+            self.transcribe(&event);
+        }
+        Ok(event)
+    }
+
+    fn play(&mut self) -> Result<Event<'a>, RunError> {
+        if let Some(name) = self.suspended {
+            return Ok(Event::Suspended { name });
+        }
+        if let Some(prompt) = self.prompt {
+            return self.prompt_event(prompt);
+        }
+
+        while let Some(frame) = self.frames.last_mut() {
+            let Some(part) = frame.parts.get(frame.played) else {
+                self.frames.pop();
+                continue;
+            };
+            frame.played += 1;
+            self.result = Value::Void(());
+            match part {
+                ScenePart::SpeakerChangeMarker(marker) => {
+                    let name = match (marker.speaker_id.as_str(), &self.speaker) {
+                        ("&", Some(speaker)) => speaker.name.clone(),
+                        (name, _) => name.to_string(),
+                    };
+                    let speaker = Speaker {
+                        name,
+                        modifiers: marker.modifiers.clone(),
+                    };
+                    self.speaker = Some(speaker.clone());
+                    return Ok(Event::SpeakerChanged {
+                        speaker: speaker.name,
+                        modifiers: speaker.modifiers,
+                    });
+                }
+                ScenePart::Dialogue(dialogue) => {
+                    self.state.visit(self.played_id());
+                    return Ok(Event::Dialogue {
+                        speaker: self.speaker.as_ref().map(|speaker| speaker.name.clone()),
+                        text: self.render(&dialogue.parts)?,
+                    });
+                }
+                ScenePart::Narration(narration) => {
+                    self.state.visit(self.played_id());
+                    return Ok(Event::Narration {
+                        text: self.render(&narration.parts)?,
+                    });
+                }
+                // Prompts none of whose options are shown are passed over.
+                ScenePart::Prompt(prompt) if self.shown(prompt)?.is_empty() => {}
+                ScenePart::Prompt(prompt) => {
+                    self.prompt = Some(prompt);
+                    return self.prompt_event(prompt);
+                }
+                ScenePart::Conditional(conditional) => {
+                    let holds = eval::condition_holds(&self.evaluate(&conditional.condition)?)
+                        .map_err(CallError::from)?;
+                    let parts = if holds {
+                        &conditional.content
+                    } else {
+                        &conditional.otherwise
+                    };
+                    if !parts.is_empty() {
+                        self.frames.push(Frame {
+                            parts,
+                            played: 0,
+                            handler: self.handler(),
+                        });
+                    }
+                }
+                ScenePart::Assignment(assignment) => self.assign_to(assignment)?,
+                ScenePart::Expression(Expression::Call { name, args, .. })
+                    if name == "set" || name == "local" =>
+                {
+                    self.assign(name, args)?
+                }
+                ScenePart::Expression(Expression::Call { name, args, .. }) if name == "emit" => {
+                    let [Expression::Symbol(symbol)] = args.as_slice() else {
+                        return Err(CallError::Evaluation {
+                            message: "`emit` takes the signal of an asset, \
+                                as in `emit(::wooden_sword::destroyed)`."
+                                .to_string(),
+                        }
+                        .into());
+                    };
+                    let mut source = self.absolute(&symbol.path);
+                    let signal = source.pop().expect("Symbols have a name.");
+                    self.emit(&source, &signal)?
+                }
+                ScenePart::Expression(Expression::Call { name, args, .. })
+                    if args.is_empty() && self.emits(name) =>
+                {
+                    let source = self.module().to_vec();
+                    self.emit(&source, name)?
+                }
+                ScenePart::Expression(Expression::Call { name, args, .. }) => {
+                    if self.host.is_none() && !self.replays(name) {
+                        return Ok(Event::Call { name, args });
+                    }
+                    let args = args
+                        .iter()
+                        .map(|arg| self.evaluate(arg))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    match self.call(name, &args)? {
+                        Completion::Done(value) => self.result = value,
+                        Completion::Suspended => {
+                            self.suspended = Some(name);
+                            return Ok(Event::Suspended { name });
+                        }
+                    }
+                }
+                ScenePart::Expression(Expression::Member {
+                    object,
+                    name,
+                    args: Some(args),
+                    ..
+                }) => {
+                    let object = self.evaluate(object)?;
+                    match object {
+                        // An asset's signal, as in `sword.destroyed()`, is emitted.
+                        Value::AssetRef(source)
+                            if args.is_empty()
+                                && self.signals.is_some_and(|signals| {
+                                    signals.emission(&source, name).is_ok()
+                                }) =>
+                        {
+                            self.emit(&source, name)?
+                        }
+                        object => {
+                            let args = std::iter::once(Ok(object))
+                                .chain(args.iter().map(|arg| self.evaluate(arg)))
+                                .collect::<Result<Vec<_>, _>>()?;
+                            match self.call(name, &args)? {
+                                Completion::Done(value) => self.result = value,
+                                Completion::Suspended => {
+                                    self.suspended = Some(name);
+                                    return Ok(Event::Suspended { name });
+                                }
+                            }
+                        }
+                    }
+                }
+                ScenePart::Expression(_) | ScenePart::Comment(_) => {}
+            }
+        }
+        Ok(Event::End)
+    }
+
+    /// Continues the scene after the call to the host it's waiting on.
+    pub fn resume(&mut self) -> Result<(), NotSuspended> {
+        self.suspended.take().ok_or(NotSuspended)?;
+        self.record(Step::Resume);
+        Ok(())
+    }
+
+    /// Continues the scene from the `index`th option shown, counting from zero,
+    /// of the prompt it's waiting on.
+    pub fn choose(&mut self, index: usize) -> Result<(), ChooseError> {
+        let prompt = self.prompt.ok_or(ChooseError::NotPrompted)?;
+        let shown = self.shown(prompt)?;
+        let written = *shown.get(index).ok_or(ChooseError::NoSuchOption {
+            index,
+            count: shown.len(),
+        })?;
+        let option = &prompt.options[written];
+        self.prompt = None;
+        self.record(Step::Choose {
+            index,
+            option: self
+                .render(&option.text)
+                .unwrap_or_else(|_| render_text(&option.text)),
+        });
+        self.state.visit(self.option_id(prompt, written));
+        self.frames.push(Frame {
+            parts: &option.content,
+            played: 0,
+            handler: self.handler(),
+        });
+        Ok(())
+    }
+
+    /// The handler being played, if any.
+    fn handler(&self) -> Option<&'a Handling<'a>> {
+        self.frames.iter().rev().find_map(|frame| frame.handler)
+    }
+
+    /// The path of the module being played, which names of a single segment are in.
+    /// It's the scene's, unless a handler of a signal is being played.
+    fn module(&self) -> &[String] {
+        match self.handler() {
+            Some(handling) => &handling.module,
+            None => &self.module,
+        }
+    }
+
+    /// Whether calls to `name` emit a signal of the asset being played.
+    fn emits(&self, name: &str) -> bool {
+        self.signals
+            .is_some_and(|signals| signals.emission(self.module(), name).is_ok())
+    }
+
+    /// Emits `signal` from the asset at `source`, telling the game's subscribers,
+    /// then playing its handlers, in order.
+    fn emit(&mut self, source: &[String], signal: &str) -> Result<(), RunError> {
+        let Some(signals) = self.signals else {
+            return Err(CallError::Evaluation {
+                message: format!(
+                    "`{}` is a signal, but the scene is played without signals.",
+                    signal
+                ),
+            }
+            .into());
+        };
+        let emission = signals.emission(source, signal)?;
+        signals.notify(&emission);
+        let handlers = signals.handlers(&emission).collect::<Vec<_>>();
+        for handling in handlers.into_iter().rev() {
+            self.frames.push(Frame {
+                parts: &handling.handler.body,
+                played: 0,
+                handler: Some(handling),
+            });
+        }
+        Ok(())
+    }
+
+    /// Calls the host, or answers with what it returned when the playthrough was recorded.
+    fn call(&self, name: &str, args: &[Value]) -> Result<Completion, CallError> {
+        if let Some(result) = self.replayed(name) {
+            return Ok(result.map_or(Completion::Suspended, |result| {
+                Completion::Done(save::unsave_value(&result))
+            }));
+        }
+        let Some(host) = self.host else {
+            return Err(CallError::Evaluation {
+                message: format!(
+                    "`{}` is for the game to call, but the scene is played without a host.",
+                    name
+                ),
+            });
+        };
+        let completion = host.call(name, args)?;
+        if self.recording.is_some() {
+            let result = match &completion {
+                Completion::Done(value) => Some(save::save_value(name, value).map_err(
+                    |error| CallError::Failed {
+                        name: name.to_string(),
+                        message: error.to_string(),
+                    },
+                )?),
+                Completion::Suspended => None,
+            };
+            self.record(Step::Call {
+                name: name.to_string(),
+                result,
+            });
+        }
+        Ok(completion)
+    }
+
+    fn prompt_event(&self, prompt: &Prompt) -> Result<Event<'a>, RunError> {
+        Ok(Event::Prompt {
+            text: prompt
+                .text
+                .as_deref()
+                .map(|text| self.render(text))
+                .transpose()?,
+            options: self
+                .shown(prompt)?
+                .into_iter()
+                .map(|index| self.render(&prompt.options[index].text))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// The options of `prompt` shown to the player, by their index in it: the ones
+    /// without a condition, and the ones whose condition holds.
+    fn shown(&self, prompt: &Prompt) -> Result<Vec<usize>, RunError> {
+        let mut shown = Vec::new();
+        for (index, option) in prompt.options.iter().enumerate() {
+            let holds = match &option.condition {
+                Some(condition) => {
+                    eval::condition_holds(&self.evaluate(condition)?).map_err(CallError::from)?
+                }
+                None => true,
+            };
+            if holds {
+                shown.push(index);
+            }
+        }
+        Ok(shown)
+    }
+
+    /// Runs `set(::name, value)` and `local(::name, value)`.
+    fn assign(&mut self, name: &str, args: &[Expression]) -> Result<(), RunError> {
+        let [Expression::Symbol(symbol), value] = args else {
+            return Err(CallError::Evaluation {
+                message: format!(
+                    "`{}` takes a variable and a value, as in `{}(::tries, 0)`.",
+                    name, name
+                ),
+            }
+            .into());
+        };
+        let value = self.evaluate(value)?;
+        match (name, symbol.path.as_slice()) {
+            ("local", [variable]) => {
+                self.locals.insert(variable.clone(), value);
+            }
+            ("local", _) => {
+                return Err(CallError::Evaluation {
+                    message: "The scene's own variables are named by a single name, \
+                        as in `local(::tries, 0)`."
+                        .to_string(),
+                }
+                .into())
+            }
+            (_, path) => self.set(path, value)?,
+        }
+        Ok(())
+    }
+
+    /// Runs an assignment, as in `::gold = 0`, or `self.durability -= 1`.
+    fn assign_to(&mut self, assignment: &Assignment) -> Result<(), RunError> {
+        let path = match &assignment.target {
+            Expression::Symbol(symbol) => symbol.path.clone(),
+            Expression::Member {
+                object,
+                name,
+                args: None,
+                ..
+            } => match self.evaluate(object)? {
+                Value::AssetRef(mut path) => {
+                    path.push(name.clone());
+                    path
+                }
+                object => {
+                    return Err(CallError::Evaluation {
+                        message: format!(
+                            "Only the fields of assets can be set, but this is {}.",
+                            eval::show(&object)
+                        ),
+                    }
+                    .into())
+                }
+            },
+            _ => {
+                return Err(CallError::Evaluation {
+                    message: "Only variables, and fields of assets, can be set, \
+                        as in `self.durability -= 1`."
+                        .to_string(),
+                }
+                .into())
+            }
+        };
+        let mut value = self.evaluate(&assignment.value)?;
+        if let Some(operator) = assignment.operator {
+            let current = self.evaluate(&assignment.target)?;
+            value = eval::operate(operator.symbol(), &current, &value).map_err(CallError::from)?;
+        }
+        self.set(&path, value)
+    }
+
+    /// Sets the variable at `path`, either the scene's own, or the game's.
+    fn set(&mut self, path: &[String], value: Value) -> Result<(), RunError> {
+        match path {
+            [variable] if self.locals.contains_key(variable) => {
+                let current = &self.locals[variable];
+                let converted =
+                    state::convert(current, &value).ok_or_else(|| StateError::Mismatched {
+                        path: variable.clone(),
+                        expected: state::kind(current).to_string(),
+                        found: eval::show(&value),
+                    })?;
+                self.locals.insert(variable.clone(), converted);
+            }
+            path => {
+                let path = self.absolute(path);
+                self.state.set(&path, value)?
+            }
+        }
+        Ok(())
+    }
+
+    /// The path of a variable of the state, as in `::gold`, in the scene's module,
+    /// or `::game::gold` in another.
+    fn absolute(&self, path: &[String]) -> Vec<String> {
+        match path {
+            [name] => self.module().iter().chain([name]).cloned().collect(),
+            path => path.to_vec(),
+        }
+    }
+
+    /// The value of a symbol, if the scene can tell: the scene's variables come first,
+    /// then the game's, then the fields of the asset whose action is run, then what
+    /// the host supplies. `self` is the asset being played.
+    fn lookup(&self, symbol: &Symbol) -> Option<Value> {
+        if let [name] = symbol.path.as_slice() {
+            if let Some(value) = self.locals.get(name) {
+                return Some(value.clone());
+            }
+        }
+        if let Some(value) = self.state.get(&self.absolute(&symbol.path)) {
+            return Some(value.clone());
+        }
+        if let [name] = symbol.path.as_slice() {
+            match name.as_str() {
+                "True" | "False" => return Some(eval::truth(name == "True")),
+                "self" => return Some(Value::AssetRef(self.module().to_vec())),
+                _ => {}
+            }
+            if let Some(value) = self.field(self.module(), name) {
+                return Some(value);
+            }
+        }
+        match (symbol.path.as_slice(), self.host) {
+            ([name], Some(host)) => match host.get(name) {
+                Some(HostBinding::Value(value)) => Some(value.clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The field `name` of the asset at `path`: a dynamic one, from the state, or any of them
+    /// for the asset whose action is run.
+    fn field(&self, path: &[String], name: &str) -> Option<Value> {
+        let mut field = path.to_vec();
+        field.push(name.to_string());
+        if let Some(value) = self.state.get(&field) {
+            return Some(value.clone());
+        }
+        let asset = self.asset.filter(|_| path == self.module())?;
+        self.evaluate(&asset.fields.get(name)?.value).ok()
+    }
+
+    /// Whether the scene can evaluate `expression`. Calls need a host, unless they read the history.
+    fn evaluable(&self, expression: &Expression) -> bool {
+        match expression {
+            Expression::Int(_) | Expression::Float(_) => true,
+            Expression::Text(parts) => parts.iter().all(|part| match part {
+                TextPart::Text(_) => true,
+                TextPart::Expression(expression) => self.evaluable(expression),
+            }),
+            Expression::Unit(expression, _) => self.evaluable(expression),
+            Expression::Binary { left, right, .. } => self.evaluable(left) && self.evaluable(right),
+            Expression::Call { name, .. } if history::reads_history(name) => true,
+            Expression::Call { name, args, .. } => {
+                (self.host.is_some() || self.replays(name))
+                    && args.iter().all(|arg| self.evaluable(arg))
+            }
+            Expression::Symbol(symbol) => self.lookup(symbol).is_some(),
+            Expression::Member {
+                object, args: None, ..
+            } => self.evaluable(object),
+            Expression::Member {
+                object,
+                name,
+                args: Some(args),
+                ..
+            } => {
+                (self.host.is_some() || self.replays(name))
+                    && self.evaluable(object)
+                    && args.iter().all(|arg| self.evaluable(arg))
+            }
+            Expression::Boolean(_) => true,
+            Expression::List { .. } => false,
+        }
+    }
+
+    /// Renders text, filling in what the scene can evaluate.
+    /// Other interpolations are left as they're written.
+    fn render(&self, parts: &[TextPart]) -> Result<String, RunError> {
+        let mut text = String::new();
+        for part in parts {
+            match part {
+                TextPart::Text(part) => text += &unescape(part),
+                TextPart::Expression(expression) if self.evaluable(expression) => {
+                    text +=
+                        &eval::interpolate(&self.evaluate(expression)?).map_err(CallError::from)?;
+                }
+                TextPart::Expression(_) => {
+                    text += &print_dialogue(std::iter::once(part))
+                        .pretty(usize::MAX)
+                        .to_string()
+                }
+            }
+        }
+        Ok(text.trim().to_string())
+    }
+
+    /// Calls the host for what it returns, which functions the scene waits on don't.
+    fn call_for_value<'e>(
+        &self,
+        name: &str,
+        args: impl Iterator<Item = &'e Expression>,
+    ) -> Result<Value, CallError> {
+        if let Some(HostBinding::Suspending(_)) = self.host.and_then(|host| host.get(name)) {
+            return Err(CallError::NestedSuspension {
+                name: name.to_string(),
+            });
+        }
+        let args = args
+            .map(|arg| self.evaluate(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        match self.call(name, &args)? {
+            Completion::Done(value) => Ok(value),
+            Completion::Suspended => Err(CallError::NestedSuspension {
+                name: name.to_string(),
+            }),
+        }
+    }
+
+    /// Evaluates an argument or an interpolation, calling the host for what it supplies.
+    fn evaluate(&self, expression: &Expression) -> Result<Value, CallError> {
+        let unsupported = |what: &str| CallError::Evaluation {
+            message: format!("{} can't be evaluated while playing yet.", what),
+        };
+        Ok(match expression {
+            Expression::Int(value) => u32::try_from(*value)
+                .map(Value::Nat)
+                .unwrap_or(Value::Int(*value)),
+            Expression::Float(value) => Value::Float(*value),
+            Expression::Text(parts) => {
+                let mut text = String::new();
+                for part in parts {
+                    match part {
+                        TextPart::Text(part) => text += &unescape(part),
+                        TextPart::Expression(expression) => {
+                            text += &eval::interpolate(&self.evaluate(expression)?)?
+                        }
+                    }
+                }
+                Value::Text(text)
+            }
+            Expression::Unit(expression, _) => self.evaluate(expression)?,
+            Expression::Binary {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                let left = self.evaluate(left)?;
+                // `and` and `or` only evaluate what they need to.
+                match (operator, eval::condition_holds(&left)) {
+                    (Operator::And, Ok(false)) | (Operator::Or, Ok(true)) => left,
+                    _ => eval::operate(operator.symbol(), &left, &self.evaluate(right)?)?,
+                }
+            }
+            Expression::Call { name, args, .. } if history::reads_history(name) => {
+                self.history(name, args)?
+            }
+            Expression::Call { name, args, .. } => self.call_for_value(name, args.iter())?,
+            Expression::Symbol(symbol) => match self.lookup(symbol) {
+                Some(value) => value,
+                None => return Err(unsupported(&format!("`::{}`", symbol.path.join("::")))),
+            },
+            Expression::Member {
+                object,
+                name,
+                args: None,
+                ..
+            } => match self.evaluate(object)? {
+                Value::AssetRef(path) => match self.field(&path, name) {
+                    Some(value) => value,
+                    None => return Err(unsupported(&format!("`::{}::{}`", path.join("::"), name))),
+                },
+                object => {
+                    return Err(CallError::Evaluation {
+                        message: format!(
+                            "Only assets have fields, as in `self.{}`, but this is {}.",
+                            name,
+                            eval::show(&object)
+                        ),
+                    })
+                }
+            },
+            // A call on a value, as in `target.is_enemy()`, is a call with the value first.
+            Expression::Member {
+                object,
+                name,
+                args: Some(args),
+                ..
+            } => self.call_for_value(name, std::iter::once(object.as_ref()).chain(args))?,
+            Expression::Boolean(value) => eval::truth(*value),
+            Expression::List { .. } => return Err(unsupported("A list")),
+        })
+    }
+}
+
+/// Renders text as it is written, with interpolations in braces.
+pub(crate) fn render_text(parts: &[TextPart]) -> String {
+    print_dialogue(parts.iter())
+        .pretty(usize::MAX)
+        .to_string()
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        host::{CallError, HostRegistry},
+        signal::Signals,
+        state::{State, StateError},
+        ActionError, ChooseError, Event, RunError, SceneRunner,
+    };
+    use crate::{
+        introspec::{eval, lower, project::ModulePath, repr::Value, types},
+        parser,
+        workspace::ParsedModule,
+    };
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn plays_scenes() {
+        let module = parser::parse_module(
+            "intro.sol",
+            "scene main\n    [Echo happy]\n    - Hi!\n    [& sad]\n    shake_screen(3)\n    prompt Where to?\n        option Outside\n            * You step outside.\n        end\n        option Nowhere\n        end\n    end\n    - Bye.\nend\n",
+        )
+        .unwrap();
+        let mut runner = SceneRunner::new(&module.scenes[0]);
+
+        assert_eq!(
+            runner.next().unwrap(),
+            Event::SpeakerChanged {
+                speaker: "Echo".to_string(),
+                modifiers: vec!["happy".to_string()]
+            }
+        );
+        assert!(matches!(
+            runner.next().unwrap(),
+            Event::Dialogue { speaker: Some(speaker), text } if speaker == "Echo" && text == "Hi!"
+        ));
+        assert!(matches!(
+            runner.next().unwrap(),
+            Event::SpeakerChanged { speaker, modifiers } if speaker == "Echo" && modifiers == ["sad"]
+        ));
+        assert!(
+            matches!(runner.next().unwrap(), Event::Call { name: "shake_screen", args } if args.len() == 1)
+        );
+        assert!(matches!(runner.choose(0), Err(ChooseError::NotPrompted)));
+
+        let prompt = runner.next().unwrap();
+        assert_eq!(
+            prompt,
+            Event::Prompt {
+                text: Some("Where to?".to_string()),
+                options: vec!["Outside".to_string(), "Nowhere".to_string()]
+            }
+        );
+        assert_eq!(runner.next().unwrap(), prompt);
+        assert!(matches!(
+            runner.choose(2),
+            Err(ChooseError::NoSuchOption { index: 2, count: 2 })
+        ));
+
+        let mut nowhere = runner.clone();
+        runner.choose(0).unwrap();
+        assert!(
+            matches!(runner.next().unwrap(), Event::Narration { text } if text == "You step outside.")
+        );
+        assert!(matches!(runner.next().unwrap(), Event::Dialogue { text, .. } if text == "Bye."));
+        assert_eq!(runner.next().unwrap(), Event::End);
+        assert_eq!(runner.next().unwrap(), Event::End);
+
+        nowhere.choose(1).unwrap();
+        assert!(matches!(nowhere.next().unwrap(), Event::Dialogue { text, .. } if text == "Bye."));
+    }
+
+    #[test]
+    fn unescapes_text() {
+        let module = parser::parse_module(
+            "intro.sol",
+            r#"scene main
+    - Say \"hi\" to \{name} and \\ {"\"me\""}.
+end
+"#,
+        )
+        .unwrap();
+        let mut runner = SceneRunner::new(&module.scenes[0]);
+        assert!(matches!(
+            runner.next().unwrap(),
+            Event::Dialogue { text, .. } if text == r#"Say "hi" to {name} and \ "me"."#
+        ));
+    }
+
+    #[test]
+    fn calls_the_host() {
+        let module = parser::parse_module(
+            "intro.sol",
+            "scene main\n    - Hi, {player_name()}!\n    pause(1)\n    wait(2 second)\n    - Can you find some {water()}?\nend\n",
+        )
+        .unwrap();
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let mut host = HostRegistry::new();
+        host.register_function("player_name", "Action", |_| {
+            Ok(Value::Text("Echo".to_string()))
+        })
+        .unwrap();
+        let pauses = calls.clone();
+        host.register_function("pause", "Action(Float)", move |args| {
+            pauses.borrow_mut().push(format!("{:?}", args));
+            Ok(Value::Void(()))
+        })
+        .unwrap();
+        let waits = calls.clone();
+        host.register_suspending("wait", "Action(Nat)", move |args| {
+            waits.borrow_mut().push(format!("{:?}", args));
+            Ok(())
+        })
+        .unwrap();
+        let mut runner = SceneRunner::new(&module.scenes[0]).with_host(&host);
+
+        assert!(
+            matches!(runner.next().unwrap(), Event::Dialogue { text, .. } if text == "Hi, Echo!")
+        );
+        assert_eq!(runner.next().unwrap(), Event::Suspended { name: "wait" });
+        assert_eq!(runner.next().unwrap(), Event::Suspended { name: "wait" });
+        assert_eq!(*calls.borrow(), ["[Float(1.0)]", "[Nat(2)]"]);
+
+        runner.resume().unwrap();
+        assert!(runner.resume().is_err());
+        assert!(matches!(
+            runner.next(),
+            Err(RunError::Call(CallError::Unregistered { name, registered }))
+                if name == "water" && registered == "`pause`, `player_name`, `wait`"
+        ));
+
+        let module =
+            parser::parse_module("intro.sol", "scene main\n    pause(::Up)\nend\n").unwrap();
+        assert!(matches!(
+            SceneRunner::new(&module.scenes[0]).with_host(&host).next(),
+            Err(RunError::Call(CallError::Evaluation { .. }))
+        ));
+        let module =
+            parser::parse_module("intro.sol", "scene main\n    pause(\"soon\")\nend\n").unwrap();
+        assert!(matches!(
+            SceneRunner::new(&module.scenes[0]).with_host(&host).next(),
+            Err(RunError::Call(CallError::MismatchedArguments { extern_type, .. })) if extern_type == "Action(Float)"
+        ));
+    }
+
+    #[test]
+    fn runs_actions() {
+        let parse = |file_name: &str, source: &str| ParsedModule {
+            file_name: file_name.to_string(),
+            source: source.to_string(),
+            ast: parser::parse_module(file_name, source).unwrap(),
+        };
+        let item = parse("Item.sol", include_str!("../../examples/simple/Item.sol"));
+        let character = parse(
+            "Character.sol",
+            include_str!("../../examples/simple/Character.sol"),
+        );
+        let sword = parse(
+            "wooden_sword.sol",
+            include_str!("../../examples/simple/wooden_sword.sol"),
+        );
+        types::check(&sword, &[&item, &character]).unwrap();
+        let path = ModulePath::parse("wooden_sword").unwrap();
+        let mut state = State::new();
+        state.load(&path, &lower::lower(&sword, &[&item, &character]).unwrap());
+
+        let mut signals = Signals::new();
+        signals.add_module(&ModulePath::parse("Item").unwrap(), &item.ast);
+        signals.add_module(&path, &sword.ast);
+        let destroyed = Rc::new(RefCell::new(Vec::new()));
+        let told = destroyed.clone();
+        signals
+            .subscribe("Item", "destroyed", move |emission| {
+                told.borrow_mut().push(emission.source.join("::"))
+            })
+            .unwrap();
+        let hits = Rc::new(RefCell::new(Vec::new()));
+        let mut host = HostRegistry::new();
+        host.register_function("is_enemy", "Action(Character)", |args| {
+            Ok(eval::truth(matches!(args, [Value::Host(7)])))
+        })
+        .unwrap();
+        let hit = hits.clone();
+        host.register_function("damage", "Action(Character, Nat)", move |args| {
+            hit.borrow_mut().push(format!("{:?}", args));
+            Ok(Value::Void(()))
+        })
+        .unwrap();
+
+        let mut use_on = |target: u64, state: State| {
+            let args = vec![Value::Host(1), Value::Host(target)];
+            let mut runner = SceneRunner::action(&sword.ast, "on_use", args)
+                .unwrap()
+                .with_host(&host)
+                .with_signals(&signals)
+                .with_state(&path, state);
+            let mut events = Vec::new();
+            loop {
+                match runner.next().unwrap() {
+                    Event::End => break,
+                    event => events.push(format!("{:?}", event)),
+                }
+            }
+            assert!(matches!(runner.result(), Some(Value::Void(()))));
+            (events, runner.into_state())
+        };
+        let (events, mut state) = use_on(7, state);
+        assert!(events.is_empty());
+        assert_eq!(*hits.borrow(), ["[Host(7), Nat(20)]"]);
+        assert!(matches!(
+            state.get(&["wooden_sword", "durability"]),
+            Some(Value::Nat(19))
+        ));
+
+        state
+            .set(&["wooden_sword", "durability"], Value::Nat(1))
+            .unwrap();
+        let (_, state) = use_on(7, state);
+        assert_eq!(*destroyed.borrow(), ["wooden_sword"]);
+        assert!(matches!(
+            state.get(&["wooden_sword", "durability"]),
+            Some(Value::Nat(0))
+        ));
+
+        let (events, mut state) = use_on(2, state);
+        assert_eq!(events, [r#"Narration { text: "Don't hit your friends!" }"#]);
+        assert_eq!(hits.borrow().len(), 2);
+        assert!(matches!(
+            state.get(&["wooden_sword", "durability"]),
+            Some(Value::Nat(0))
+        ));
+        // Durability is a `Nat`, so it can't go below zero.
+        assert!(matches!(
+            state.set(&["wooden_sword", "durability"], Value::Int(-5)),
+            Err(StateError::Mismatched { expected, .. }) if expected == "Nat"
+        ));
+
+        assert!(matches!(
+            SceneRunner::action(&sword.ast, "on_drop", Vec::new()),
+            Err(ActionError::Unknown { available, .. }) if available == "`on_use`"
+        ));
+        assert!(matches!(
+            SceneRunner::action(&sword.ast, "on_use", vec![Value::Nat(1)]),
+            Err(ActionError::MismatchedArguments { parameters, arguments, .. })
+                if parameters == "user, target" && arguments == "1"
+        ));
+
+        // An action comes to what its last part did.
+        let potion = parser::parse_module(
+            "potion.sol",
+            "action on_use(user, target)\n    target.heal(5)\nend\n",
+        )
+        .unwrap();
+        host.register_function("heal", "Action(Character, Nat)", |_| Ok(Value::Nat(12)))
+            .unwrap();
+        let mut runner =
+            SceneRunner::action(&potion, "on_use", vec![Value::Host(1), Value::Host(7)])
+                .unwrap()
+                .with_host(&host);
+        assert!(runner.result().is_none());
+        assert_eq!(runner.next().unwrap(), Event::End);
+        assert!(matches!(runner.result(), Some(Value::Nat(12))));
+    }
+
+    #[test]
+    fn plays_conditionals() {
+        let module = parser::parse_module(
+            "shop.sol",
+            "scene main\n    if ::gold >= 10 and ::open == ::True then\n        set(::gold, ::gold - 10)\n        - Deal.\n    else\n        - Come back with {10 - ::gold} more gold.\n    end\n    - Bye.\nend\n",
+        )
+        .unwrap();
+        let path = ModulePath::parse("shop").unwrap();
+        let mut state = State::new();
+        state.declare(&["shop", "gold"], Value::Nat(15));
+        state.declare(&["shop", "open"], Value::Variant("True".to_string(), None));
+
+        let mut lines = Vec::new();
+        for _ in 0..2 {
+            let mut runner = SceneRunner::new(&module.scenes[0]).with_state(&path, state);
+            loop {
+                match runner.next().unwrap() {
+                    Event::Dialogue { text, .. } => lines.push(text),
+                    Event::End => break,
+                    _ => {}
+                }
+            }
+            state = runner.into_state();
+        }
+        assert_eq!(
+            lines,
+            ["Deal.", "Bye.", "Come back with 5 more gold.", "Bye."]
+        );
+
+        state.set(&["shop", "open"], Value::Nat(1)).unwrap_err();
+        let module = parser::parse_module(
+            "shop.sol",
+            "scene main\n    if ::gold then\n        - Deal.\n    end\nend\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            SceneRunner::new(&module.scenes[0]).with_state(&path, state).next(),
+            Err(RunError::Call(CallError::Evaluation { message }))
+                if message == "Conditions are `::True` or `::False`, but this is 5."
+        ));
+    }
+
+    #[test]
+    fn keeps_variables() {
+        let module = parser::parse_module(
+            "shop.sol",
+            "scene main\n    local(::tries, 0)\n    set(::tries, ::tries + 1)\n    set(::gold, ::gold - ::tries)\n    set(::game::flag, \"seen\")\n    - Tried {::tries} time, with {::gold} gold and {::rent} rent.\n    set(::gold, \"lots\")\nend\n",
+        )
+        .unwrap();
+        let mut state = State::new();
+        state.declare(&["shop", "gold"], Value::Nat(10));
+        state.declare(&["game", "flag"], Value::Text(String::new()));
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let observed = changes.clone();
+        state.observe(move |change| observed.borrow_mut().push(change.path.join("::")));
+        let mut runner = SceneRunner::new(&module.scenes[0])
+            .with_state(&ModulePath::parse("shop").unwrap(), state);
+
+        assert!(matches!(
+            runner.next().unwrap(),
+            Event::Dialogue { text, .. } if text == "Tried 1 time, with 9 gold and {rent} rent."
+        ));
+        assert!(matches!(
+            runner.next(),
+            Err(RunError::State(StateError::Mismatched { path, .. })) if path == "shop::gold"
+        ));
+        assert_eq!(*changes.borrow(), ["shop::gold", "game::flag"]);
+        let state = runner.into_state();
+        assert!(matches!(state.get(&["game", "flag"]), Some(Value::Text(flag)) if flag == "seen"));
+        assert!(state.get(&["shop", "tries"]).is_none());
+    }
+}
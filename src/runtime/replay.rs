@@ -0,0 +1,347 @@
+//! Playthroughs, recorded as they're played, and replayed later to reproduce them.
+//!
+//! A runner that's [`recording`](SceneRunner::recording) notes every option chosen,
+//! and everything the game returned to its calls, along with a transcript of what
+//! the scene did. [`replay`] plays the scene again, choosing the same options and
+//! answering calls with what the game returned then, without calling the game, and
+//! tells where the transcript first differs, as it does after the scene is edited:
+//!
+//! ```
+//! use sol_lang::{parser, runtime::{replay, Event, SceneRunner}};
+//!
+//! let module = parser::parse_module(
+//!     "intro.sol",
+//!     "scene main\n    prompt\n        option Stay.\n            - Hi!\n        end\n    end\nend\n",
+//! )
+//! .unwrap();
+//! let mut runner = SceneRunner::new(&module.scenes[0]).recording();
+//! while runner.next().unwrap() != Event::End {
+//!     runner.choose(0).ok();
+//! }
+//! let playthrough = runner.playthrough().unwrap();
+//!
+//! let replayed = replay::replay(SceneRunner::new(&module.scenes[0]), &playthrough).unwrap();
+//! assert!(replayed.divergence.is_none());
+//! ```
+use super::{save::SavedValue, Event, SceneRunner};
+use crate::{parser::ast::Expression, unparser::print_expression};
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, collections::VecDeque};
+use thiserror::Error;
+
+/// The version of playthroughs this runtime records, and the only one it replays.
+pub const PLAYTHROUGH_VERSION: u32 = 1;
+
+/// A scene, as it was played.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Playthrough {
+    pub version: u32,
+    pub module: Vec<String>,
+    pub scene: String,
+    /// What the player and the game did, in order.
+    pub steps: Vec<Step>,
+    /// What the scene did, a line at a time, as in `Echo: Hi!` or `choose "Stay."`.
+    pub transcript: Vec<String>,
+}
+
+/// Something the player or the game did.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum Step {
+    /// An option of a prompt was chosen.
+    Choose { index: usize, option: String },
+    /// What the game returned to a call, or nothing, when the scene waited on it.
+    Call {
+        name: String,
+        result: Option<SavedValue>,
+    },
+    /// The game was done with what the scene waited on.
+    Resume,
+}
+
+/// A scene, as it was replayed.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub transcript: Vec<String>,
+    /// Where the transcript first differs from the recorded one, if it does.
+    pub divergence: Option<Divergence>,
+}
+
+#[derive(Error, Debug, Diagnostic, Clone, PartialEq)]
+#[error("The replay differs from the recording at line {line}.")]
+#[diagnostic(
+    code(sol::runtime::diverged),
+    help("The recording has {expected} there, but the scene now has {found}.")
+)]
+pub struct Divergence {
+    /// The line of the transcript, counting from one.
+    pub line: usize,
+    pub expected: String,
+    pub found: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("The playthrough is of version {version}, but only version {supported} can be replayed.")]
+#[diagnostic(code(sol::runtime::unsupported_playthrough))]
+pub struct UnsupportedPlaythrough {
+    pub version: u32,
+    pub supported: u32,
+}
+
+impl<'a> SceneRunner<'a> {
+    /// Records the playthrough from now on, for [`SceneRunner::playthrough`].
+    pub fn recording(mut self) -> Self {
+        self.recording = Some(RefCell::new(Playthrough {
+            version: PLAYTHROUGH_VERSION,
+            module: self.module.clone(),
+            scene: self.name.to_string(),
+            steps: Vec::new(),
+            transcript: Vec::new(),
+        }));
+        self
+    }
+
+    /// Answers calls to the game with what it returned in `playthrough`, instead of
+    /// calling it, as long as they're made in the same order.
+    pub fn replaying(mut self, playthrough: &Playthrough) -> Self {
+        self.replayed = Some(RefCell::new(
+            playthrough
+                .steps
+                .iter()
+                .filter_map(|step| match step {
+                    Step::Call { name, result } => Some((name.clone(), result.clone())),
+                    _ => None,
+                })
+                .collect(),
+        ));
+        self
+    }
+
+    /// The playthrough recorded so far, if the runner is recording.
+    pub fn playthrough(&self) -> Option<Playthrough> {
+        self.recording
+            .as_ref()
+            .map(|recording| recording.borrow().clone())
+    }
+
+    /// Notes a step, if the runner is recording, along with its line of the transcript.
+    pub(super) fn record(&self, step: Step) {
+        if let Some(recording) = &self.recording {
+            let mut recording = recording.borrow_mut();
+            match &step {
+                Step::Choose { option, .. } => {
+                    recording.transcript.push(format!("choose {:?}", option))
+                }
+                Step::Resume => recording.transcript.push("resume".to_string()),
+                Step::Call { .. } => {}
+            }
+            recording.steps.push(step);
+        }
+    }
+
+    /// Notes the line of the transcript for `event`, if the runner is recording.
+    pub(super) fn transcribe(&self, event: &Event) {
+        if let (Some(recording), Some(line)) = (&self.recording, transcript_line(event)) {
+            recording.borrow_mut().transcript.push(line);
+        }
+    }
+
+    /// What the game returned to the next call, when it's to `name`, if the runner is replaying.
+    pub(super) fn replayed(&self, name: &str) -> Option<Option<SavedValue>> {
+        let mut replayed = self.replayed.as_ref()?.borrow_mut();
+        match replayed.front() {
+            Some((called, _)) if called == name => replayed.pop_front().map(|(_, result)| result),
+            _ => None,
+        }
+    }
+
+    /// Whether the next call is replayed, and is to `name`.
+    pub(super) fn replays(&self, name: &str) -> bool {
+        self.replayed.as_ref().is_some_and(|replayed| {
+            replayed
+                .borrow()
+                .front()
+                .is_some_and(|(called, _)| called == name)
+        })
+    }
+}
+
+/// Calls replayed, in order, along with what the game returned to them.
+pub(super) type Replayed = VecDeque<(String, Option<SavedValue>)>;
+
+/// Plays the scene of `runner` again, as `playthrough` was played, until it ends, or an
+/// option chosen then isn't there anymore.
+pub fn replay(
+    runner: SceneRunner,
+    playthrough: &Playthrough,
+) -> Result<Replay, UnsupportedPlaythrough> {
+    if playthrough.version != PLAYTHROUGH_VERSION {
+        return Err(UnsupportedPlaythrough {
+            version: playthrough.version,
+            supported: PLAYTHROUGH_VERSION,
+        });
+    }
+    let mut runner = runner.replaying(playthrough).recording();
+    let mut steps = playthrough
+        .steps
+        .iter()
+        .filter(|step| !matches!(step, Step::Call { .. }));
+    let mut errors = Vec::new();
+
+    loop {
+        match runner.next() {
+            Ok(Event::Prompt { options, .. }) => {
+                let Some(Step::Choose { option, .. }) = steps.next() else {
+                    break;
+                };
+                let Some(index) = options.iter().position(|shown| shown == option) else {
+                    break;
+                };
+                runner
+                    .choose(index)
+                    .expect("The option is one of the prompt's.");
+            }
+            Ok(Event::Suspended { .. }) => {
+                let Some(Step::Resume) = steps.next() else {
+                    break;
+                };
+                runner.resume().expect("The scene is waiting on the game.");
+            }
+            Ok(Event::End) => break,
+            Ok(_) => {}
+            Err(error) => {
+                errors.push(format!("error {}", error));
+                break;
+            }
+        }
+    }
+
+    let mut transcript = runner
+        .playthrough()
+        .expect("The replay is recorded.")
+        .transcript;
+    transcript.extend(errors);
+    let line = |transcript: &[String], index: usize| {
+        transcript
+            .get(index)
+            .map(|line| format!("`{}`", line))
+            .unwrap_or_else(|| "nothing".to_string())
+    };
+    let divergence = (0..transcript.len().max(playthrough.transcript.len()))
+        .find(|index| transcript.get(*index) != playthrough.transcript.get(*index))
+        .map(|index| Divergence {
+            line: index + 1,
+            expected: line(&playthrough.transcript, index),
+            found: line(&transcript, index),
+        });
+    Ok(Replay {
+        transcript,
+        divergence,
+    })
+}
+
+/// The line of the transcript for an event, unless it goes without saying.
+fn transcript_line(event: &Event) -> Option<String> {
+    Some(match event {
+        Event::SpeakerChanged { .. } => return None,
+        Event::Dialogue {
+            speaker: Some(speaker),
+            text,
+        } => format!("{}: {}", speaker, text),
+        Event::Dialogue {
+            speaker: None,
+            text,
+        }
+        | Event::Narration { text } => format!("* {}", text),
+        Event::Prompt { text, options } => format!(
+            "prompt{}: {}",
+            text.as_ref()
+                .map(|text| format!(" {:?}", text))
+                .unwrap_or_default(),
+            options
+                .iter()
+                .map(|option| format!("{:?}", option))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Event::Call { name, args } => format!(
+            "call {}",
+            print_expression(&Expression::Call {
+                name: name.to_string(),
+                args: args.to_vec(),
+                name_span: Default::default(),
+            })
+            .pretty(usize::MAX)
+        ),
+        Event::Suspended { name } => format!("wait {}", name),
+        Event::End => "end".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replay, Divergence};
+    use crate::{
+        introspec::repr::Value,
+        parser,
+        runtime::{host::HostRegistry, Event, SceneRunner},
+    };
+
+    const CAMP: &str = "scene main\n    [Echo]\n    - Hi, {player_name()}!\n    wait(2)\n    prompt Rest?\n        option Yes\n            * You rest.\n        end\n        option No\n        end\n    end\n    shake_screen(1)\nend\n";
+
+    #[test]
+    fn replays_playthroughs() {
+        let mut host = HostRegistry::new();
+        host.register_function("player_name", "Action", |_| {
+            Ok(Value::Text("Jude".to_string()))
+        })
+        .unwrap();
+        host.register_suspending("wait", "Action(Nat)", |_| Ok(()))
+            .unwrap();
+        host.register_function("shake_screen", "Action(Nat)", |_| Ok(Value::Void(())))
+            .unwrap();
+        let module = parser::parse_module("camp.sol", CAMP).unwrap();
+        let mut runner = SceneRunner::new(&module.scenes[0])
+            .with_host(&host)
+            .recording();
+        loop {
+            match runner.next().unwrap() {
+                Event::Prompt { .. } => runner.choose(0).unwrap(),
+                Event::Suspended { .. } => runner.resume().unwrap(),
+                Event::End => break,
+                _ => {}
+            }
+        }
+        let playthrough = runner.playthrough().unwrap();
+        assert_eq!(
+            playthrough.transcript,
+            [
+                "Echo: Hi, Jude!",
+                "wait wait",
+                "resume",
+                "prompt \"Rest?\": \"Yes\", \"No\"",
+                "choose \"Yes\"",
+                "* You rest.",
+                "end"
+            ]
+        );
+
+        // The game isn't needed, since what it returned is replayed.
+        let replayed = replay(SceneRunner::new(&module.scenes[0]), &playthrough).unwrap();
+        assert_eq!(replayed.transcript, playthrough.transcript);
+        assert_eq!(replayed.divergence, None);
+
+        let edited = CAMP.replace("You rest.", "You sleep.");
+        let module = parser::parse_module("camp.sol", &edited).unwrap();
+        let replayed = replay(SceneRunner::new(&module.scenes[0]), &playthrough).unwrap();
+        assert_eq!(
+            replayed.divergence,
+            Some(Divergence {
+                line: 6,
+                expected: "`* You rest.`".to_string(),
+                found: "`* You sleep.`".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,636 @@
+//! Saves of a game, taken mid-scene, which it can be restored from later.
+//!
+//! A [`Save`] holds the game's variables, how many times each line was seen, and,
+//! when taken by a [`SceneRunner`], where the scene was. It's serializable, as in
+//! `serde_json::to_string(&save)`, and versioned, so saves of older versions of
+//! the runtime are turned down instead of misread.
+//!
+//! Positions are saved by the lines played, not by their index, so a save still
+//! restores after its scene is edited, as long as the lines it was on are still there:
+//!
+//! ```
+//! use sol_lang::{parser, runtime::{state::State, Event, SceneRunner}};
+//!
+//! let module = parser::parse_module("intro.sol", "scene main\n    - Hi!\n    - Bye.\nend\n").unwrap();
+//! let mut runner = SceneRunner::new(&module.scenes[0]);
+//! runner.next().unwrap();
+//! let save = serde_json::to_string(&runner.save().unwrap()).unwrap();
+//!
+//! let module = parser::parse_module(
+//!     "intro.sol",
+//!     "scene main\n    - Hello.\n    - Hi!\n    - Bye.\nend\n",
+//! )
+//! .unwrap();
+//! let save = serde_json::from_str(&save).unwrap();
+//! let mut runner = SceneRunner::new(&module.scenes[0]).restore(&save, State::new()).unwrap();
+//! assert!(matches!(runner.next().unwrap(), Event::Dialogue { text, .. } if text == "Bye."));
+//! ```
+use super::{nesting, render_text, state::State, Frame, Nesting, SceneRunner, Speaker};
+use crate::{
+    introspec::{eval, repr::Value},
+    parser::ast::{Expression, Scene, ScenePart},
+    unparser::{print_expression, print_scene_part},
+};
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// The version of saves this runtime writes, and the only one it restores.
+pub const SAVE_VERSION: u32 = 1;
+
+/// A game, as it was when saved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Save {
+    pub version: u32,
+    pub state: SavedState,
+    /// The scene being played, if the save was taken mid-scene.
+    pub scene: Option<SavedScene>,
+}
+
+/// The game's variables, by their path, as in `game::gold`, and the visits of each line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedState {
+    pub values: BTreeMap<String, SavedValue>,
+    pub visits: BTreeMap<String, u32>,
+}
+
+/// Where a scene was, and what it held.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedScene {
+    pub module: Vec<String>,
+    pub scene: String,
+    /// The parts being played, the scene's own first, then those of each option chosen,
+    /// or branch taken.
+    pub frames: Vec<SavedFrame>,
+    pub speaker: Option<Speaker>,
+    pub waiting: Option<Waiting>,
+    pub locals: BTreeMap<String, SavedValue>,
+}
+
+/// Parts being played, and how far along.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedFrame {
+    /// The option of the prompt these parts are in, as it's written, unless they're the scene's own.
+    pub option: Option<String>,
+    /// The branch of the conditional these parts are, if they are one.
+    #[serde(default)]
+    pub branch: Option<Branch>,
+    /// The handler of a signal these parts are, as in `achievements on Item.destroyed`.
+    #[serde(default)]
+    pub handler: Option<String>,
+    /// The last part played, unless none were.
+    pub last: Option<Anchor>,
+}
+
+/// A part of a scene, as it's written, as in `- Hi!`. Parts written the same are
+/// told apart by how many of them come before it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Anchor {
+    pub part: String,
+    pub occurrence: usize,
+}
+
+/// A branch of a conditional, as in `if ::gold >= 10 then ... else ... end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Branch {
+    /// The parts played when the condition holds.
+    Then,
+    /// The parts played otherwise.
+    Else,
+}
+
+/// What the scene was waiting on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Waiting {
+    /// An option of the prompt it was on.
+    Prompt,
+    /// The game, to finish the call it was on.
+    Host,
+}
+
+/// A value of a variable, or one the game returned.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
+pub enum SavedValue {
+    Void,
+    Nat(u32),
+    Int(i32),
+    Float(f32),
+    Text(String),
+    Variant(String, Option<Box<SavedValue>>),
+    AssetRef(Vec<String>),
+    Host(u64),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{path}` holds {value}, which can't be saved.")]
+#[diagnostic(
+    code(sol::runtime::unsavable),
+    help("Only numbers, text, variants and references can be saved.")
+)]
+pub struct SaveError {
+    pub path: String,
+    pub value: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+pub enum RestoreError {
+    #[error("The save is of version {version}, but only version {supported} can be restored.")]
+    #[diagnostic(code(sol::runtime::unsupported_save))]
+    UnsupportedVersion { version: u32, supported: u32 },
+    #[error("The save wasn't taken mid-scene.")]
+    #[diagnostic(
+        code(sol::runtime::no_saved_scene),
+        help("Restore the state alone, with `State::restore`.")
+    )]
+    NoScene,
+    #[error("The save is of `{saved}`, not `{scene}`.")]
+    #[diagnostic(code(sol::runtime::wrong_scene))]
+    WrongScene { saved: String, scene: String },
+    #[error("The save was on `{line}` in `{scene}`, which isn't there anymore.")]
+    #[diagnostic(
+        code(sol::runtime::missing_position),
+        help("The scene changed since the save. Restore the state alone, with `State::restore`, and start the scene over.")
+    )]
+    MissingPosition { scene: String, line: String },
+    #[error("The save was in the option `{option}` in `{scene}`, which isn't there anymore.")]
+    #[diagnostic(
+        code(sol::runtime::missing_option),
+        help("The scene changed since the save. Restore the state alone, with `State::restore`, and start the scene over.")
+    )]
+    MissingOption { scene: String, option: String },
+    #[error("The save was in the handler `{handler}`, which isn't there anymore.")]
+    #[diagnostic(
+        code(sol::runtime::missing_handler),
+        help("Handlers are only found when the runner is played `with_signals`. Otherwise, restore the state alone, with `State::restore`, and start the scene over.")
+    )]
+    MissingHandler { handler: String },
+    #[error("`{path}` holds a `{expected}` now, but was saved with {found}.")]
+    #[diagnostic(code(sol::runtime::mismatched_save))]
+    Mismatched {
+        path: String,
+        expected: String,
+        found: String,
+    },
+}
+
+impl State {
+    /// Saves the game's variables, and the visits of each line.
+    pub fn save(&self) -> Result<Save, SaveError> {
+        Ok(Save {
+            version: SAVE_VERSION,
+            state: SavedState {
+                values: self
+                    .iter()
+                    .map(|(path, value)| {
+                        let path = path.join("::");
+                        Ok((path.clone(), save_value(&path, value)?))
+                    })
+                    .collect::<Result<_, _>>()?,
+                visits: self.visits.clone(),
+            },
+            scene: None,
+        })
+    }
+
+    /// Restores the variables of `save` onto this state, as the build left it. Variables
+    /// no longer declared are dropped, and those declared since keep their values.
+    pub fn restore(&mut self, save: &Save) -> Result<(), RestoreError> {
+        check_version(save)?;
+        for (path, saved) in save.state.values.iter() {
+            let path = path.split("::").collect::<Vec<_>>();
+            let Some(current) = self.get(&path) else {
+                continue;
+            };
+            let value = restore_value(&path.join("::"), current, saved)?;
+            self.values
+                .insert(path.into_iter().map(str::to_string).collect(), value);
+        }
+        self.visits = save.state.visits.clone();
+        Ok(())
+    }
+}
+
+impl<'a> SceneRunner<'a> {
+    /// Saves the game's variables, along with where the scene is.
+    pub fn save(&self) -> Result<Save, SaveError> {
+        let mut save = self.state.save()?;
+        let mut frames = Vec::new();
+        for (index, frame) in self.frames.iter().enumerate() {
+            let handler = frame
+                .handler
+                .filter(|handling| std::ptr::eq(handling.handler.body.as_slice(), frame.parts));
+            let (option, branch) = match index.checked_sub(1).map(|parent| &self.frames[parent]) {
+                Some(_) if handler.is_some() => (None, None),
+                Some(parent) => match nesting(&parent.parts[parent.played - 1], frame.parts) {
+                    Some(Nesting::Option(prompt, option)) => {
+                        (Some(render_text(&prompt.options[option].text)), None)
+                    }
+                    Some(Nesting::Branch(branch)) => (None, Some(branch)),
+                    None => unreachable!("Only options and branches are played in frames."),
+                },
+                None => (None, None),
+            };
+            frames.push(SavedFrame {
+                option,
+                branch,
+                handler: handler.map(|handling| handling.id()),
+                last: frame.played.checked_sub(1).map(|last| Anchor {
+                    part: anchor(&frame.parts[last]),
+                    occurrence: frame.parts[..last]
+                        .iter()
+                        .filter(|part| anchor(part) == anchor(&frame.parts[last]))
+                        .count(),
+                }),
+            });
+        }
+        save.scene = Some(SavedScene {
+            module: self.module.clone(),
+            scene: self.name.to_string(),
+            frames,
+            speaker: self.speaker.clone(),
+            waiting: match (self.prompt, self.suspended) {
+                (Some(_), _) => Some(Waiting::Prompt),
+                (_, Some(_)) => Some(Waiting::Host),
+                _ => None,
+            },
+            locals: self
+                .locals
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), save_value(name, value)?)))
+                .collect::<Result<_, _>>()?,
+        });
+        Ok(save)
+    }
+
+    /// Plays the scene from where `save` left it, with `state` as the build left it.
+    /// The lines the save was on are looked up by how they're written, so edits
+    /// elsewhere in the scene don't matter.
+    pub fn restore(mut self, save: &Save, mut state: State) -> Result<Self, RestoreError> {
+        check_version(save)?;
+        let saved = save.scene.as_ref().ok_or(RestoreError::NoScene)?;
+        if saved.scene != self.name {
+            return Err(RestoreError::WrongScene {
+                saved: saved.scene.clone(),
+                scene: self.name.to_string(),
+            });
+        }
+        state.restore(save)?;
+        let name = saved
+            .module
+            .iter()
+            .chain([&saved.scene])
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("::");
+
+        let mut frames: Vec<Frame<'a>> = Vec::new();
+        for frame in saved.frames.iter() {
+            let (parts, handler) = match (&frame.handler, &frame.option, frames.last()) {
+                (Some(handler), _, _) => {
+                    let handling = self
+                        .signals
+                        .and_then(|signals| signals.handler(handler))
+                        .ok_or_else(|| RestoreError::MissingHandler {
+                            handler: handler.clone(),
+                        })?;
+                    (handling.handler.body.as_slice(), Some(handling))
+                }
+                (None, Some(option), Some(parent)) => {
+                    let Some(ScenePart::Prompt(prompt)) = parent.parts.get(parent.played - 1)
+                    else {
+                        return Err(RestoreError::MissingOption {
+                            scene: name,
+                            option: option.clone(),
+                        });
+                    };
+                    let Some(chosen) = prompt
+                        .options
+                        .iter()
+                        .find(|candidate| render_text(&candidate.text) == *option)
+                    else {
+                        return Err(RestoreError::MissingOption {
+                            scene: name,
+                            option: option.clone(),
+                        });
+                    };
+                    (chosen.content.as_slice(), parent.handler)
+                }
+                (None, None, Some(parent)) if frame.branch.is_some() => {
+                    let part = parent.parts.get(parent.played - 1);
+                    let Some(ScenePart::Conditional(conditional)) = part else {
+                        return Err(RestoreError::MissingPosition {
+                            scene: name,
+                            line: part.map(anchor).unwrap_or_default(),
+                        });
+                    };
+                    let parts = match frame.branch {
+                        Some(Branch::Then) => &conditional.content,
+                        _ => &conditional.otherwise,
+                    };
+                    (parts.as_slice(), parent.handler)
+                }
+                _ => (self.content, None),
+            };
+            let played = match &frame.last {
+                Some(last) => {
+                    let found = parts
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, part)| anchor(part) == last.part)
+                        .nth(last.occurrence)
+                        .map(|(index, _)| index + 1);
+                    found.ok_or_else(|| RestoreError::MissingPosition {
+                        scene: name.clone(),
+                        line: last.part.clone(),
+                    })?
+                }
+                None => 0,
+            };
+            frames.push(Frame {
+                parts,
+                played,
+                handler,
+            });
+        }
+
+        let last = frames
+            .last()
+            .and_then(|frame| frame.parts.get(frame.played.checked_sub(1)?));
+        let mut runner = self;
+        runner.frames = frames;
+        runner.speaker = saved.speaker.clone();
+        runner.prompt = None;
+        runner.suspended = None;
+        runner.state = state;
+        runner.module = saved.module.clone();
+        runner.locals = BTreeMap::new();
+        match (saved.waiting, last) {
+            (Some(Waiting::Prompt), Some(ScenePart::Prompt(prompt))) => {
+                runner.prompt = Some(prompt)
+            }
+            // Calls on a value, as in `target.wait()`, wait the same way.
+            (
+                Some(Waiting::Host),
+                Some(ScenePart::Expression(
+                    Expression::Call { name, .. }
+                    | Expression::Member {
+                        name,
+                        args: Some(_),
+                        ..
+                    },
+                )),
+            ) => runner.suspended = Some(name),
+            (Some(_), _) => {
+                return Err(RestoreError::MissingPosition {
+                    scene: name,
+                    line: last.map(anchor).unwrap_or_default(),
+                })
+            }
+            (None, _) => {}
+        }
+        for (local, saved) in saved.locals.iter() {
+            runner.locals.insert(local.clone(), unsave_value(saved));
+        }
+        Ok(runner)
+    }
+}
+
+/// A part of a scene, as it's written, as in `- Hi!` or `[Echo happy]`.
+pub(crate) fn anchor(part: &ScenePart) -> String {
+    match part {
+        ScenePart::SpeakerChangeMarker(marker) => format!(
+            "[{}]",
+            [&marker.speaker_id]
+                .into_iter()
+                .chain(marker.modifiers.iter())
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+        ScenePart::Dialogue(dialogue) => format!("- {}", render_text(&dialogue.parts)),
+        ScenePart::Narration(narration) => format!("* {}", render_text(&narration.parts)),
+        ScenePart::Prompt(prompt) => match &prompt.text {
+            Some(text) => format!("prompt {}", render_text(text)),
+            None => "prompt".to_string(),
+        },
+        ScenePart::Conditional(conditional) => format!(
+            "if {}",
+            print_expression(&conditional.condition).pretty(usize::MAX)
+        ),
+        ScenePart::Assignment(_) => print_scene_part(part).pretty(usize::MAX).to_string(),
+        ScenePart::Expression(expression) => {
+            print_expression(expression).pretty(usize::MAX).to_string()
+        }
+        ScenePart::Comment(comment) => format!("--{}", comment),
+    }
+}
+
+fn check_version(save: &Save) -> Result<(), RestoreError> {
+    if save.version != SAVE_VERSION {
+        return Err(RestoreError::UnsupportedVersion {
+            version: save.version,
+            supported: SAVE_VERSION,
+        });
+    }
+    Ok(())
+}
+
+pub(super) fn save_value(path: &str, value: &Value) -> Result<SavedValue, SaveError> {
+    Ok(match value {
+        Value::Void(()) => SavedValue::Void,
+        Value::Nat(value) => SavedValue::Nat(*value),
+        Value::Int(value) => SavedValue::Int(*value),
+        Value::Float(value) => SavedValue::Float(*value),
+        Value::Text(value) => SavedValue::Text(value.clone()),
+        Value::AssetRef(path) => SavedValue::AssetRef(path.clone()),
+        Value::Host(handle) => SavedValue::Host(*handle),
+        Value::Variant(name, value) => SavedValue::Variant(
+            name.clone(),
+            value
+                .as_deref()
+                .map(|value| save_value(path, value).map(Box::new))
+                .transpose()?,
+        ),
+        _ => {
+            return Err(SaveError {
+                path: path.to_string(),
+                value: eval::show(value),
+            })
+        }
+    })
+}
+
+pub(super) fn unsave_value(saved: &SavedValue) -> Value {
+    match saved {
+        SavedValue::Void => Value::Void(()),
+        SavedValue::Nat(value) => Value::Nat(*value),
+        SavedValue::Int(value) => Value::Int(*value),
+        SavedValue::Float(value) => Value::Float(*value),
+        SavedValue::Text(value) => Value::Text(value.clone()),
+        SavedValue::AssetRef(path) => Value::AssetRef(path.clone()),
+        SavedValue::Host(handle) => Value::Host(*handle),
+        SavedValue::Variant(name, value) => Value::Variant(
+            name.clone(),
+            value.as_deref().map(|value| Box::new(unsave_value(value))),
+        ),
+    }
+}
+
+/// The saved value of a variable, converted to the type it holds now.
+fn restore_value(path: &str, current: &Value, saved: &SavedValue) -> Result<Value, RestoreError> {
+    let value = unsave_value(saved);
+    super::state::convert(current, &value).ok_or_else(|| RestoreError::Mismatched {
+        path: path.to_string(),
+        expected: super::state::kind(current).to_string(),
+        found: eval::show(&value),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Branch, RestoreError, Save, SAVE_VERSION};
+    use crate::{
+        introspec::{project::ModulePath, repr::Value},
+        parser,
+        runtime::{host::HostRegistry, state::State, Event, SceneRunner},
+    };
+
+    const SHOP: &str = "scene main\n    [Echo]\n    - Hi!\n    local(::tries, 1)\n    prompt Buy?\n        option Yes\n            set(::gold, ::gold - 5)\n            - Thanks!\n            - Bye.\n        end\n    end\n    - Bye.\nend\n";
+
+    fn state() -> State {
+        let mut state = State::new();
+        state.declare(&["shop", "gold"], Value::Nat(10));
+        state
+    }
+
+    #[test]
+    fn restores_mid_scene() {
+        let module = parser::parse_module("shop.sol", SHOP).unwrap();
+        let path = ModulePath::parse("shop").unwrap();
+        let mut runner = SceneRunner::new(&module.scenes[0]).with_state(&path, state());
+        while !matches!(runner.next().unwrap(), Event::Prompt { .. }) {}
+        let prompted = runner.save().unwrap();
+        runner.choose(0).unwrap();
+        runner.next().unwrap();
+        let save = serde_json::to_string(&runner.save().unwrap()).unwrap();
+        let save: Save = serde_json::from_str(&save).unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+
+        let edited = SHOP.replace("    - Hi!\n", "    - Hi!\n    - Welcome.\n");
+        let module = parser::parse_module("shop.sol", &edited).unwrap();
+        let mut restored = SceneRunner::new(&module.scenes[0])
+            .restore(&save, state())
+            .unwrap();
+        assert!(matches!(
+            restored.state().get(&["shop", "gold"]),
+            Some(Value::Nat(5))
+        ));
+        assert_eq!(restored.state().visits("shop::main - Hi!"), 1);
+        assert_eq!(restored.speaker().unwrap().name, "Echo");
+        assert!(matches!(restored.next().unwrap(), Event::Dialogue { text, .. } if text == "Bye."));
+        assert!(matches!(restored.next().unwrap(), Event::Dialogue { text, .. } if text == "Bye."));
+        assert_eq!(restored.next().unwrap(), Event::End);
+
+        let mut restored = SceneRunner::new(&module.scenes[0])
+            .restore(&prompted, state())
+            .unwrap();
+        assert!(matches!(restored.next().unwrap(), Event::Prompt { .. }));
+        restored.choose(0).unwrap();
+        assert!(
+            matches!(restored.next().unwrap(), Event::Dialogue { text, .. } if text == "Thanks!")
+        );
+    }
+
+    #[test]
+    fn restores_in_branches() {
+        let module = parser::parse_module(
+            "shop.sol",
+            "scene main\n    if ::gold >= 10 then\n        - Deal.\n        - Bye.\n    else\n        - Deal?\n    end\nend\n",
+        )
+        .unwrap();
+        let path = ModulePath::parse("shop").unwrap();
+        let mut runner = SceneRunner::new(&module.scenes[0]).with_state(&path, state());
+        runner.next().unwrap();
+        let save = runner.save().unwrap();
+        assert_eq!(
+            save.scene.as_ref().unwrap().frames[1].branch,
+            Some(Branch::Then)
+        );
+
+        // The branch is restored as it was taken, whatever the condition is now.
+        let mut state = state();
+        state.set(&["shop", "gold"], Value::Nat(0)).unwrap();
+        let mut restored = SceneRunner::new(&module.scenes[0])
+            .restore(&save, state)
+            .unwrap();
+        assert!(matches!(restored.next().unwrap(), Event::Dialogue { text, .. } if text == "Bye."));
+        assert_eq!(
+            restored
+                .state()
+                .visits("shop::main if gold >= 10 > then - Deal."),
+            1
+        );
+    }
+
+    #[test]
+    fn restores_waits_on_values() {
+        let mut host = HostRegistry::new();
+        host.register_suspending("wait", "Action(Asset)", |_| Ok(()))
+            .unwrap();
+        let module = parser::parse_module(
+            "shop.sol",
+            "scene main\n    self.wait()\n    - Done.\nend\n",
+        )
+        .unwrap();
+        let path = ModulePath::parse("shop").unwrap();
+        let mut runner = SceneRunner::new(&module.scenes[0])
+            .with_host(&host)
+            .with_state(&path, state());
+        assert_eq!(runner.next().unwrap(), Event::Suspended { name: "wait" });
+        let save = runner.save().unwrap();
+
+        let mut restored = SceneRunner::new(&module.scenes[0])
+            .with_host(&host)
+            .restore(&save, state())
+            .unwrap();
+        assert_eq!(restored.next().unwrap(), Event::Suspended { name: "wait" });
+        restored.resume().unwrap();
+        assert!(
+            matches!(restored.next().unwrap(), Event::Dialogue { text, .. } if text == "Done.")
+        );
+    }
+
+    #[test]
+    fn tells_of_missing_positions() {
+        let module = parser::parse_module("shop.sol", SHOP).unwrap();
+        let path = ModulePath::parse("shop").unwrap();
+        let mut runner = SceneRunner::new(&module.scenes[0]).with_state(&path, state());
+        runner.next().unwrap();
+        runner.next().unwrap();
+        let save = runner.save().unwrap();
+
+        let edited = SHOP.replace("- Hi!", "- Hello!");
+        let module = parser::parse_module("shop.sol", &edited).unwrap();
+        assert!(matches!(
+            SceneRunner::new(&module.scenes[0]).restore(&save, state()),
+            Err(RestoreError::MissingPosition { scene, line }) if scene == "shop::main" && line == "- Hi!"
+        ));
+
+        let mut state = State::new();
+        state.declare(&["shop", "gold"], Value::Text(String::new()));
+        assert!(matches!(
+            state.restore(&save),
+            Err(RestoreError::Mismatched { path, .. }) if path == "shop::gold"
+        ));
+        let mut old = save.clone();
+        old.version = 0;
+        assert!(matches!(
+            State::new().restore(&old),
+            Err(RestoreError::UnsupportedVersion { version: 0, .. })
+        ));
+    }
+}
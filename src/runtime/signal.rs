@@ -0,0 +1,351 @@
+//! Signals assets emit, as in `destroyed: Signal`, and what handles them.
+//!
+//! Models declare signals, and their assets emit them, from their own scenes and
+//! actions by calling them, as in `destroyed()`, and from anywhere else with `emit`,
+//! as in `emit(::weapons::wooden_sword::destroyed)`. Sol handles every emission of
+//! a model's signal with `on`:
+//!
+//! ```lua
+//! on Item.destroyed
+//!     * Something breaks.
+//! end
+//! ```
+//!
+//! The game subscribes with [`Signals::subscribe`]. Subscribers are told first, in the
+//! order they subscribed. Handlers are played next, in the same event stream as the
+//! scene that emitted the signal, ordered by the path of their module, then by the
+//! order they're written in.
+use super::history;
+use crate::{
+    introspec::project::ModulePath,
+    parser::ast::{Handler, Module},
+};
+use miette::Diagnostic;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+};
+use thiserror::Error;
+
+/// Told of every emission of the signal it subscribed to.
+pub type Subscriber = Rc<dyn Fn(&Emission)>;
+
+/// A signal being emitted by an asset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Emission {
+    /// The path of the asset, as in `weapons::wooden_sword`.
+    pub source: Vec<String>,
+    pub model: String,
+    pub signal: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{owner}` has no signal `{signal}`.")]
+#[diagnostic(
+    code(sol::runtime::undeclared_signal),
+    help(
+        "Signals are declared by models, as in `destroyed: Signal`, and emitted by their assets."
+    )
+)]
+pub struct UndeclaredSignal {
+    pub owner: String,
+    pub signal: String,
+}
+
+/// A handler of a signal, along with the path of the module it's in.
+#[derive(Debug, Clone)]
+pub struct Handling<'a> {
+    pub module: Vec<String>,
+    pub handler: &'a Handler,
+    /// How many handlers of the same signal are written before it in its module.
+    pub before: usize,
+}
+
+impl Handling<'_> {
+    /// How the handler is known to saves, and the read history, as in
+    /// `achievements on Item.destroyed`. The second handler of the same signal
+    /// in the same module is `#2`, and so on.
+    pub fn id(&self) -> String {
+        history::numbered(
+            format!(
+                "{} on {}.{}",
+                self.module.join("::"),
+                self.handler.model,
+                self.handler.signal
+            ),
+            self.before,
+        )
+    }
+}
+
+/// The signals of a game, and what handles them.
+#[derive(Default, Clone)]
+pub struct Signals<'a> {
+    /// The signals each model declares.
+    models: BTreeMap<String, BTreeSet<String>>,
+    /// The model of each asset, by its path.
+    assets: BTreeMap<Vec<String>, String>,
+    /// Sorted by the path of their module, then by the order they're written in.
+    handlers: Vec<Handling<'a>>,
+    subscribers: Vec<(String, String, Subscriber)>,
+}
+
+impl std::fmt::Debug for Signals<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Signals")
+            .field("models", &self.models)
+            .field("assets", &self.assets)
+            .field("handlers", &self.handlers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> Signals<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the signals `module`, found at `path`, declares, emits and handles.
+    pub fn add_module(&mut self, path: &ModulePath, module: &'a Module) {
+        for model in module.models.iter() {
+            let signals = model
+                .fields
+                .iter()
+                .filter(|field| field.field_type.name == "Signal")
+                .map(|field| field.name.clone());
+            self.models
+                .entry(model.name.clone())
+                .or_default()
+                .extend(signals);
+        }
+        if let Some(directive) = &module.model {
+            self.assets.insert(path.0.clone(), directive.name.clone());
+        }
+        for (index, handler) in module.handlers.iter().enumerate() {
+            let before = module.handlers[..index]
+                .iter()
+                .filter(|other| other.model == handler.model && other.signal == handler.signal)
+                .count();
+            self.handlers.push(Handling {
+                module: path.0.clone(),
+                handler,
+                before,
+            });
+        }
+        self.handlers
+            .sort_by(|first, second| first.module.cmp(&second.module));
+    }
+
+    /// Tells `subscriber` of every emission of `signal` by assets of `model`.
+    pub fn subscribe(
+        &mut self,
+        model: &str,
+        signal: &str,
+        subscriber: impl Fn(&Emission) + 'static,
+    ) -> Result<(), UndeclaredSignal> {
+        if !self.declares(model, signal) {
+            return Err(UndeclaredSignal {
+                owner: model.to_string(),
+                signal: signal.to_string(),
+            });
+        }
+        self.subscribers
+            .push((model.to_string(), signal.to_string(), Rc::new(subscriber)));
+        Ok(())
+    }
+
+    /// Whether `model` declares `signal`.
+    pub fn declares(&self, model: &str, signal: &str) -> bool {
+        self.models
+            .get(model)
+            .is_some_and(|signals| signals.contains(signal))
+    }
+
+    /// The emission of `signal` by the asset at `source`, as long as its model declares it.
+    pub fn emission(&self, source: &[String], signal: &str) -> Result<Emission, UndeclaredSignal> {
+        match self.assets.get(source) {
+            Some(model) if self.declares(model, signal) => Ok(Emission {
+                source: source.to_vec(),
+                model: model.clone(),
+                signal: signal.to_string(),
+            }),
+            _ => Err(UndeclaredSignal {
+                owner: format!("::{}", source.join("::")),
+                signal: signal.to_string(),
+            }),
+        }
+    }
+
+    /// Tells every subscriber of `emission`, in the order they subscribed.
+    pub fn notify(&self, emission: &Emission) {
+        for (model, signal, subscriber) in self.subscribers.iter() {
+            if *model == emission.model && *signal == emission.signal {
+                subscriber(emission);
+            }
+        }
+    }
+
+    /// The handlers of `emission`, in the order they're played.
+    pub fn handlers(&self, emission: &Emission) -> impl Iterator<Item = &Handling<'a>> {
+        let (model, signal) = (emission.model.clone(), emission.signal.clone());
+        self.handlers.iter().filter(move |handling| {
+            handling.handler.model == model && handling.handler.signal == signal
+        })
+    }
+
+    /// The handler known to saves as `id`.
+    pub(super) fn handler(&self, id: &str) -> Option<&Handling<'a>> {
+        self.handlers.iter().find(|handling| handling.id() == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Signals;
+    use crate::{
+        introspec::project::ModulePath,
+        parser,
+        runtime::{state::State, Event, RunError, SceneRunner},
+    };
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn handles_signals_in_order() {
+        let models = parser::parse_module(
+            "models.sol",
+            "model Item\n    name: Text\n    destroyed: Signal\nend\n",
+        )
+        .unwrap();
+        let sword = parser::parse_module(
+            "sword.sol",
+            "@model Item\nname = \"Sword\"\n\nscene swing\n    destroyed()\n    - Oh no.\nend\n",
+        )
+        .unwrap();
+        let journal = parser::parse_module(
+            "journal.sol",
+            "on Item.destroyed\n    prompt Mourn it?\n        option Yes\n            * You mourn.\n        end\n    end\nend\n",
+        )
+        .unwrap();
+        let achievements = parser::parse_module(
+            "achievements.sol",
+            "on Item.destroyed\n    * Achievement unlocked!\nend\n\nscene main\n    emit(::sword::destroyed)\n    emit(::sword::name)\nend\n",
+        )
+        .unwrap();
+        let mut signals = Signals::new();
+        for (path, module) in [
+            ("models", &models),
+            ("sword", &sword),
+            ("journal", &journal),
+            ("achievements", &achievements),
+        ] {
+            signals.add_module(&ModulePath::parse(path).unwrap(), module);
+        }
+        let emitted = Rc::new(RefCell::new(Vec::new()));
+        let told = emitted.clone();
+        signals
+            .subscribe("Item", "destroyed", move |emission| {
+                told.borrow_mut().push(emission.source.join("::"))
+            })
+            .unwrap();
+        assert!(signals.subscribe("Item", "name", |_| {}).is_err());
+
+        let path = ModulePath::parse("sword").unwrap();
+        let mut runner = SceneRunner::new(&sword.scenes[0])
+            .with_state(&path, State::new())
+            .with_signals(&signals);
+        assert!(
+            matches!(runner.next().unwrap(), Event::Narration { text } if text == "Achievement unlocked!")
+        );
+        assert_eq!(*emitted.borrow(), ["sword"]);
+        assert!(matches!(runner.next().unwrap(), Event::Prompt { .. }));
+
+        let save = runner.save().unwrap();
+        assert_eq!(
+            save.scene.as_ref().unwrap().frames[1].handler.as_deref(),
+            Some("journal on Item.destroyed")
+        );
+        let mut restored = SceneRunner::new(&sword.scenes[0])
+            .with_signals(&signals)
+            .restore(&save, State::new())
+            .unwrap();
+        restored.choose(0).unwrap();
+        assert!(
+            matches!(restored.next().unwrap(), Event::Narration { text } if text == "You mourn.")
+        );
+        assert!(
+            matches!(restored.next().unwrap(), Event::Dialogue { text, .. } if text == "Oh no.")
+        );
+        assert!(SceneRunner::new(&sword.scenes[0])
+            .restore(&save, State::new())
+            .is_err());
+
+        let path = ModulePath::parse("achievements").unwrap();
+        let mut runner = SceneRunner::new(&achievements.scenes[0])
+            .with_state(&path, State::new())
+            .with_signals(&signals);
+        assert!(matches!(runner.next().unwrap(), Event::Narration { .. }));
+        assert!(matches!(runner.next().unwrap(), Event::Prompt { .. }));
+        runner.choose(0).unwrap();
+        runner.next().unwrap();
+        assert!(matches!(
+            runner.next(),
+            Err(RunError::Signal(error)) if error.owner == "::sword" && error.signal == "name"
+        ));
+        assert_eq!(*emitted.borrow(), ["sword", "sword"]);
+    }
+
+    #[test]
+    fn numbers_handlers_of_the_same_signal() {
+        let models =
+            parser::parse_module("models.sol", "model Item\n    destroyed: Signal\nend\n").unwrap();
+        let sword = parser::parse_module(
+            "sword.sol",
+            "@model Item\n\nscene swing\n    destroyed()\nend\n",
+        )
+        .unwrap();
+        let journal = parser::parse_module(
+            "journal.sol",
+            "on Item.destroyed\n    prompt\n        option Mourn.\n            * You mourn.\n        end\n    end\nend\n\non Item.destroyed\n    prompt\n        option Mourn.\n            * You mourn again.\n        end\n    end\nend\n",
+        )
+        .unwrap();
+        let mut signals = Signals::new();
+        for (path, module) in [
+            ("models", &models),
+            ("sword", &sword),
+            ("journal", &journal),
+        ] {
+            signals.add_module(&ModulePath::parse(path).unwrap(), module);
+        }
+
+        let path = ModulePath::parse("sword").unwrap();
+        let mut runner = SceneRunner::new(&sword.scenes[0])
+            .with_state(&path, State::new())
+            .with_signals(&signals);
+        assert!(matches!(runner.next().unwrap(), Event::Prompt { .. }));
+        runner.choose(0).unwrap();
+        runner.next().unwrap();
+        assert!(matches!(runner.next().unwrap(), Event::Prompt { .. }));
+        assert_eq!(
+            runner.option_ids(),
+            ["journal on Item.destroyed #2 prompt > Mourn."]
+        );
+
+        let save = runner.save().unwrap();
+        assert_eq!(
+            save.scene.as_ref().unwrap().frames[1].handler.as_deref(),
+            Some("journal on Item.destroyed #2")
+        );
+        let mut restored = SceneRunner::new(&sword.scenes[0])
+            .with_signals(&signals)
+            .restore(&save, State::new())
+            .unwrap();
+        restored.choose(0).unwrap();
+        assert!(
+            matches!(restored.next().unwrap(), Event::Narration { text } if text == "You mourn again.")
+        );
+        for handler in ["journal on Item.destroyed", "journal on Item.destroyed #2"] {
+            let option = format!("{} prompt > Mourn.", handler);
+            assert_eq!(restored.state().visits(&option), 1);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! The state of a game as it runs: its dynamic fields, and its global variables.
+//!
+//! Both are dynamic fields of modules, declared by a model, as in `durability: dynamic Nat`,
+//! or by the field itself, as in `dynamic gold = 0`. Each is kept by its path, as in
+//! `::weapons::sword::durability` or `::game::gold`, and starts out with its value
+//! from the build. Scenes read them like any other item, and write them with `set`:
+//!
+//! ```lua
+//! set(::game::gold, ::game::gold + 10)
+//! ```
+//!
+//! The game is told of every change by observing the state:
+//!
+//! ```
+//! use sol_lang::{introspec::repr::Value, runtime::state::State};
+//!
+//! let mut state = State::new();
+//! state.declare(&["game", "gold"], Value::Nat(0));
+//! state.observe(|change| println!("{} is now {:?}", change.path.join("::"), change.new));
+//! state.set(&["game", "gold"], Value::Nat(10)).unwrap();
+//! ```
+use crate::introspec::{
+    eval,
+    project::ModulePath,
+    repr::{AssetModule, ModuleItemValue, Value},
+};
+use miette::Diagnostic;
+use std::{collections::BTreeMap, rc::Rc};
+use thiserror::Error;
+
+/// Told of every change to the state, after it happens.
+pub type Observer = Rc<dyn Fn(&Change)>;
+
+/// A value of the state being set.
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub path: Vec<String>,
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+pub enum StateError {
+    #[error("`::{path}` isn't a variable.")]
+    #[diagnostic(
+        code(sol::runtime::undeclared_variable),
+        help("Declare it as `dynamic`, in its module or its model, or with `local` in the scene.")
+    )]
+    Undeclared { path: String },
+    #[error("`::{path}` holds a `{expected}`, so it can't be set to {found}.")]
+    #[diagnostic(code(sol::runtime::mismatched_variable))]
+    Mismatched {
+        path: String,
+        expected: String,
+        found: String,
+    },
+}
+
+/// The dynamic fields of a game, by their path.
+#[derive(Default, Clone)]
+pub struct State {
+    pub(super) values: BTreeMap<Vec<String>, Value>,
+    /// How many times each line was played, as in `intro::main - Hi!`.
+    pub(super) visits: BTreeMap<String, u32>,
+    observers: Vec<Observer>,
+}
+
+impl std::fmt::Debug for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("State")
+            .field("values", &self.values)
+            .field("visits", &self.visits)
+            .finish_non_exhaustive()
+    }
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares the dynamic fields of `module`, found at `path`, with their values from the build.
+    /// Fields already declared keep their values.
+    pub fn load(&mut self, path: &ModulePath, module: &AssetModule) {
+        for (name, item) in module.items.iter() {
+            if let ModuleItemValue::Dynamic(value) = &item.value {
+                let mut path = path.0.clone();
+                path.push(name.clone());
+                self.values.entry(path).or_insert_with(|| value.clone());
+            }
+        }
+    }
+
+    /// Declares a variable at `path`, unless it already is.
+    pub fn declare(&mut self, path: &[impl AsRef<str>], value: Value) {
+        self.values.entry(key(path)).or_insert(value);
+    }
+
+    /// The value at `path`, if it's declared.
+    pub fn get(&self, path: &[impl AsRef<str>]) -> Option<&Value> {
+        self.values.get(&key(path))
+    }
+
+    /// Sets the variable at `path`, telling every observer. Numbers are converted to
+    /// the type the variable holds, as long as they fit.
+    pub fn set(&mut self, path: &[impl AsRef<str>], value: Value) -> Result<(), StateError> {
+        let path = key(path);
+        let Some(current) = self.values.get_mut(&path) else {
+            return Err(StateError::Undeclared {
+                path: path.join("::"),
+            });
+        };
+        let Some(value) = convert(current, &value) else {
+            return Err(StateError::Mismatched {
+                path: path.join("::"),
+                expected: kind(current).to_string(),
+                found: eval::show(&value),
+            });
+        };
+
+        let change = Change {
+            old: std::mem::replace(current, value.clone()),
+            path,
+            new: value,
+        };
+        for observer in self.observers.iter() {
+            observer(&change);
+        }
+        Ok(())
+    }
+
+    /// Tells `observer` of every change from now on.
+    pub fn observe(&mut self, observer: impl Fn(&Change) + 'static) {
+        self.observers.push(Rc::new(observer));
+    }
+
+    /// How many times `line` was played, as in `intro::main - Hi!`.
+    pub fn visits(&self, line: &str) -> u32 {
+        self.visits.get(line).copied().unwrap_or_default()
+    }
+
+    /// Whether the line, or option, was played at all.
+    pub fn seen(&self, line: &str) -> bool {
+        self.visits(line) > 0
+    }
+
+    /// The lines and options played, by their ID, along with how often they were.
+    pub fn history(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.visits
+            .iter()
+            .map(|(line, visits)| (line.as_str(), *visits))
+    }
+
+    pub(crate) fn visit(&mut self, line: String) {
+        *self.visits.entry(line).or_default() += 1;
+    }
+
+    /// Every variable, along with its value, sorted by path.
+    pub fn iter(&self) -> impl Iterator<Item = (&[String], &Value)> {
+        self.values
+            .iter()
+            .map(|(path, value)| (path.as_slice(), value))
+    }
+}
+
+fn key(path: &[impl AsRef<str>]) -> Vec<String> {
+    path.iter()
+        .map(|segment| segment.as_ref().to_string())
+        .collect()
+}
+
+/// Converts `value` to the type `current` holds, if it fits.
+pub(crate) fn convert(current: &Value, value: &Value) -> Option<Value> {
+    Some(match (current, value) {
+        (Value::Nat(_), Value::Nat(_))
+        | (Value::Int(_), Value::Int(_))
+        | (Value::Float(_), Value::Float(_))
+        | (Value::Text(_), Value::Text(_)) => value.clone(),
+        (Value::Nat(_), Value::Int(value)) => Value::Nat(u32::try_from(*value).ok()?),
+        (Value::Int(_), Value::Nat(value)) => Value::Int(i32::try_from(*value).ok()?),
+        (Value::Float(_), Value::Nat(value)) => Value::Float(*value as f32),
+        (Value::Float(_), Value::Int(value)) => Value::Float(*value as f32),
+        (Value::Variant(..), Value::Variant(..))
+        | (Value::AssetRef(_), Value::AssetRef(_))
+        | (Value::Host(_), Value::Host(_)) => value.clone(),
+        _ => return None,
+    })
+}
+
+/// The name of the type of a value, for errors.
+pub(crate) fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Nat(_) => "Nat",
+        Value::Int(_) => "Int",
+        Value::Float(_) => "Float",
+        Value::Text(_) => "Text",
+        Value::Variant(..) => "Either",
+        Value::AssetRef(_) => "Asset",
+        Value::Host(_) => "Host",
+        _ => "value",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{State, StateError};
+    use crate::{
+        introspec::{lower, project::ModulePath, repr::Value},
+        parser,
+        workspace::ParsedModule,
+    };
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn keeps_dynamic_fields() {
+        let source = "dynamic gold = 5 * 2\nname = \"Hub\"\n";
+        let module = ParsedModule {
+            file_name: "game.sol".to_string(),
+            source: source.to_string(),
+            ast: parser::parse_module("game.sol", source).unwrap(),
+        };
+        let mut state = State::new();
+        state.load(
+            &ModulePath::parse("game").unwrap(),
+            &lower::lower(&module, &[]).unwrap(),
+        );
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let observed = changes.clone();
+        state.observe(move |change| {
+            observed.borrow_mut().push(format!(
+                "{}: {:?} -> {:?}",
+                change.path.join("::"),
+                change.old,
+                change.new
+            ))
+        });
+
+        assert!(matches!(state.get(&["game", "gold"]), Some(Value::Int(10))));
+        assert!(state.get(&["game", "name"]).is_none());
+        state.set(&["game", "gold"], Value::Nat(3)).unwrap();
+        assert!(matches!(
+            state.set(&["game", "gold"], Value::Text("lots".to_string())),
+            Err(StateError::Mismatched { expected, .. }) if expected == "Int"
+        ));
+        assert!(matches!(
+            state.set(&["game", "name"], Value::Nat(3)),
+            Err(StateError::Undeclared { path }) if path == "game::name"
+        ));
+        assert_eq!(*changes.borrow(), ["game::gold: Int(10) -> Int(3)"]);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::parser::ast::{Expression, Scene, ScenePart, Module, TextPart};
+use crate::parser::ast::{Expression, Module, Scene, ScenePart, TextPart};
 use pretty::termcolor::{Color, ColorChoice, ColorSpec, StandardStream};
 use pretty::{Doc, RcDoc, RenderAnnotated};
 use std::{io::stdout, rc::Rc};
@@ -10,11 +10,11 @@ pub fn render_script(script: &Module) -> String {
     String::from_utf8(bytes).expect("Failed to render script as printer didn't output valid UTF-8.")
 }
 
-pub fn print_script(script: &Module) -> RcDoc {
+pub fn print_script(script: &Module) -> RcDoc<'_> {
     RcDoc::intersperse(script.scenes.iter().map(print_scene), "\n\n")
 }
 
-pub fn print_scene(scene: &Scene) -> RcDoc {
+pub fn print_scene(scene: &Scene) -> RcDoc<'_> {
     RcDoc::text("scene ")
         .append(scene.name.clone())
         .append(RcDoc::hardline())
@@ -26,7 +26,7 @@ pub fn print_scene(scene: &Scene) -> RcDoc {
         .append("end")
 }
 
-pub fn print_scene_part(scene_part: &ScenePart) -> RcDoc {
+pub fn print_scene_part(scene_part: &ScenePart) -> RcDoc<'_> {
     match scene_part {
         ScenePart::SpeakerChangeMarker(speaker_change_marker) => RcDoc::text("[")
             .append(
@@ -49,11 +49,46 @@ pub fn print_scene_part(scene_part: &ScenePart) -> RcDoc {
             RcDoc::text("* ").append(print_dialogue(narration.parts.iter()))
         }
         ScenePart::Prompt(prompt) => todo!(),
+        ScenePart::Conditional(conditional) => {
+            let otherwise = if conditional.otherwise.is_empty() {
+                RcDoc::nil()
+            } else {
+                RcDoc::hardline()
+                    .append("else")
+                    .append(print_branch(&conditional.otherwise))
+            };
+            RcDoc::text("if ")
+                .append(print_expression(&conditional.condition))
+                .append(" then")
+                .append(print_branch(&conditional.content))
+                .append(otherwise)
+                .append(RcDoc::hardline())
+                .append("end")
+        }
+        ScenePart::Assignment(assignment) => print_expression(&assignment.target)
+            .append(match assignment.operator {
+                Some(operator) => format!(" {}= ", operator.symbol()),
+                None => " = ".to_string(),
+            })
+            .append(print_expression(&assignment.value)),
         ScenePart::Expression(expression) => print_expression(expression),
         ScenePart::Comment(content) => RcDoc::text("--").append(RcDoc::text(content)),
     }
 }
 
+/// The parts of a branch of a conditional, each on a line of its own, indented.
+fn print_branch(parts: &[ScenePart]) -> RcDoc<'_> {
+    if parts.is_empty() {
+        return RcDoc::nil();
+    }
+    RcDoc::hardline()
+        .append(RcDoc::intersperse(
+            parts.iter().map(print_scene_part),
+            RcDoc::hardline(),
+        ))
+        .nest(2)
+}
+
 pub fn print_dialogue<'print, I>(text_parts: I) -> RcDoc<'print>
 where
     I: Iterator<Item = &'print TextPart>,
@@ -69,19 +104,66 @@ where
     )
 }
 
-pub fn print_expression(expression: &Expression) -> RcDoc {
+fn print_operand(operand: &Expression, grouped: bool) -> RcDoc<'_> {
+    if grouped {
+        RcDoc::text("(")
+            .append(print_expression(operand))
+            .append(RcDoc::text(")"))
+    } else {
+        print_expression(operand)
+    }
+}
+
+fn print_arguments(args: &[Expression]) -> RcDoc<'_> {
+    RcDoc::text("(")
+        .append(RcDoc::intersperse(args.iter().map(print_expression), RcDoc::text(",")).group())
+        .append(RcDoc::text(")"))
+}
+
+pub fn print_expression(expression: &Expression) -> RcDoc<'_> {
     match expression {
-        Expression::Call { name, args } => RcDoc::text(name)
-            .append(RcDoc::text("("))
-            .append(RcDoc::intersperse(args.iter().map(print_expression), RcDoc::text(",")).group())
-            .append(RcDoc::text(")")),
+        Expression::Call { name, args, .. } => RcDoc::text(name).append(print_arguments(args)),
+        Expression::Member {
+            object, name, args, ..
+        } => print_operand(object, matches!(**object, Expression::Binary { .. }))
+            .append(RcDoc::text("."))
+            .append(RcDoc::text(name))
+            .append(match args {
+                Some(args) => print_arguments(args),
+                None => RcDoc::nil(),
+            }),
         Expression::Unit(expression, unit) => print_expression(expression)
             .append(RcDoc::space())
             .append(RcDoc::text(unit)),
+        Expression::Binary {
+            operator,
+            left,
+            right,
+            ..
+        } => {
+            // Operands are grouped only when their operators wouldn't be applied first anyway.
+            let precedence = |operand: &Expression| match operand {
+                Expression::Binary { operator, .. } => operator.precedence(),
+                _ => u8::MAX,
+            };
+            print_operand(left, precedence(left) < operator.precedence())
+                .append(RcDoc::text(format!(" {} ", operator.symbol())))
+                .append(print_operand(
+                    right,
+                    precedence(right) <= operator.precedence(),
+                ))
+        }
         Expression::Int(val) => RcDoc::text(val.to_string()),
         Expression::Float(val) => RcDoc::text(val.to_string()),
         Expression::Boolean(val) => RcDoc::text(val.to_string()),
-        Expression::Text(text_parts) => print_dialogue(text_parts.iter()),
+        Expression::Text(text_parts) => RcDoc::text("\"")
+            .append(print_dialogue(text_parts.iter()))
+            .append(RcDoc::text("\"")),
+        Expression::List { items, .. } => RcDoc::text("[")
+            .append(
+                RcDoc::intersperse(items.iter().map(print_expression), RcDoc::text(", ")).group(),
+            )
+            .append(RcDoc::text("]")),
         Expression::Symbol(symbol) => RcDoc::intersperse(
             symbol.path.iter().map(|node| RcDoc::text(node.as_str())),
             RcDoc::text("."),
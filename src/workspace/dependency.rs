@@ -0,0 +1,885 @@
+//! Dependencies between workspaces, as declared in `index.sol`.
+use super::{
+    git::{self, GitError, MissingCommit},
+    lockfile::{self, LockedPackage, LockedSource, Lockfile},
+    manifest::{self, ManifestError, WorkspaceManifest},
+    registry::{
+        self, InvalidRegistryDependency, MislabeledPackage, NoRegistry, Registry, Requirement,
+        VersionConflict,
+    },
+    vendor::{self, VendorMismatch},
+    SOL_MANIFEST_FILENAME,
+};
+use crate::introspec::project::{Project, ProjectLoadError};
+use clap::Args;
+use miette::Diagnostic;
+use semver::VersionReq;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// Where a dependency comes from, as written in the manifest.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DependencySpec {
+    /// Another workspace on disk, relative to the depending workspace.
+    Path(PathBuf),
+    /// A git repository, as in `git+file:///path/to/repo.git#v1.0`.
+    ///
+    /// The reference after `#` can be a branch, tag or commit,
+    /// and defaults to the repository's default branch.
+    Git {
+        url: String,
+        reference: Option<String>,
+    },
+    /// A version of a package from the workspace's registry, as in `items@^1.2`.
+    Registry {
+        name: String,
+        requirement: VersionReq,
+    },
+}
+
+impl DependencySpec {
+    pub fn parse(spec: &str) -> Result<Self, InvalidDependencySpec> {
+        let invalid = || InvalidDependencySpec {
+            spec: spec.to_string(),
+        };
+
+        if let Some(git) = spec.strip_prefix("git+") {
+            let (url, reference) = match git.split_once('#') {
+                Some((url, reference)) => (url, Some(reference.to_string())),
+                None => (git, None),
+            };
+            // Neither may be taken for an option by `git`.
+            if url.is_empty()
+                || url.starts_with('-')
+                || reference
+                    .as_ref()
+                    .is_some_and(|reference| reference.is_empty() || reference.starts_with('-'))
+            {
+                return Err(invalid());
+            }
+            return Ok(DependencySpec::Git {
+                url: url.to_string(),
+                reference,
+            });
+        }
+
+        if let Some((name, requirement)) = spec.split_once('@') {
+            if manifest::is_package_name(name) {
+                let requirement = VersionReq::parse(requirement).map_err(|_| invalid())?;
+                return Ok(DependencySpec::Registry {
+                    name: name.to_string(),
+                    requirement,
+                });
+            }
+        }
+
+        if spec.trim().is_empty() {
+            return Err(invalid());
+        }
+        Ok(DependencySpec::Path(PathBuf::from(spec)))
+    }
+}
+
+/// A dependency after resolution, ready to be imported from.
+pub struct ResolvedDependency {
+    pub project: Project,
+    pub locked: LockedPackage,
+}
+
+/// Which locked packages resolution is allowed to move to newer versions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Update {
+    /// Keep everything as locked. Only new dependencies are resolved.
+    Nothing,
+    /// Resolve every dependency again.
+    Everything,
+    /// Resolve only the packages with these names again.
+    Packages(Vec<String>),
+}
+
+impl Update {
+    fn allows(&self, name: &str) -> bool {
+        match self {
+            Update::Nothing => false,
+            Update::Everything => true,
+            Update::Packages(names) => names.iter().any(|candidate| candidate == name),
+        }
+    }
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error(transparent)]
+pub enum DependencyError {
+    IO(#[from] std::io::Error),
+    #[diagnostic(transparent)]
+    Manifest(#[from] ManifestError),
+    #[diagnostic(transparent)]
+    Project(#[from] ProjectLoadError),
+    #[diagnostic(transparent)]
+    InvalidSpec(#[from] InvalidDependencySpec),
+    #[diagnostic(transparent)]
+    NotAWorkspace(#[from] NotAWorkspace),
+    #[diagnostic(transparent)]
+    Conflict(#[from] PackageConflict),
+    #[diagnostic(transparent)]
+    AlreadyAdded(#[from] DependencyAlreadyAdded),
+    #[diagnostic(transparent)]
+    NotFound(#[from] DependencyNotFound),
+    #[diagnostic(transparent)]
+    Git(#[from] GitError),
+    #[diagnostic(transparent)]
+    MissingCommit(#[from] MissingCommit),
+    #[diagnostic(transparent)]
+    HashMismatch(#[from] HashMismatch),
+    #[diagnostic(transparent)]
+    NoRegistry(#[from] NoRegistry),
+    #[diagnostic(transparent)]
+    VersionConflict(#[from] VersionConflict),
+    #[diagnostic(transparent)]
+    InvalidRegistryDependency(#[from] InvalidRegistryDependency),
+    #[diagnostic(transparent)]
+    MislabeledPackage(#[from] MislabeledPackage),
+    #[diagnostic(transparent)]
+    VendorMismatch(#[from] VendorMismatch),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{spec}` is not a valid dependency.")]
+#[diagnostic(help(
+    "Dependencies are paths to other workspaces, as in `../shared_assets`, \
+    git repositories, as in `git+https://example.com/assets.git#v1.0`, \
+    or versions from the registry, as in `items@^1.2`."
+))]
+pub struct InvalidDependencySpec {
+    pub spec: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("Dependency {path:?} is not a Sol workspace.")]
+#[diagnostic(help("A workspace is a directory with an `index.sol`. Run `sol init` there."))]
+pub struct NotAWorkspace {
+    pub path: PathBuf,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("Two different packages are named `{name}`: {first:?} and {second:?}.")]
+#[diagnostic(help("Package names must be unique within a dependency graph."))]
+pub struct PackageConflict {
+    pub name: String,
+    pub first: PathBuf,
+    pub second: PathBuf,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{name}` is already a dependency of this workspace.")]
+pub struct DependencyAlreadyAdded {
+    pub name: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{name}` is not a dependency of this workspace.")]
+pub struct DependencyNotFound {
+    pub name: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("The contents of `{name}` don't match the lockfile.")]
+#[diagnostic(help(
+    "Expected {expected}, found {found}. The cached copy in {directory:?} might \
+    have been modified; delete it and build again."
+))]
+pub struct HashMismatch {
+    pub name: String,
+    pub directory: PathBuf,
+    pub expected: String,
+    pub found: String,
+}
+
+#[derive(Debug, Args)]
+pub struct AddArgs {
+    /// The dependency to add, as in `../shared_assets`, `git+https://example.com/assets.git#v1.0` or `items@^1.2`.
+    pub spec: String,
+}
+
+#[derive(Debug, Args)]
+pub struct RemoveArgs {
+    /// Package name of the dependency to remove.
+    pub name: String,
+}
+
+#[derive(Debug, Args)]
+pub struct UpdateArgs {
+    /// Package names of the dependencies to update. If none, everything is updated.
+    pub names: Vec<String>,
+}
+
+/// Resolves the whole dependency graph of the workspace at `root`.
+///
+/// Packages pinned by the workspace's current lockfile stay pinned,
+/// unless `update` allows them to move.
+///
+/// Returns the direct dependencies, and the lockfile describing the graph.
+pub fn resolve(
+    root: &Path,
+    manifest: &WorkspaceManifest,
+    update: &Update,
+) -> Result<(Vec<ResolvedDependency>, Lockfile), DependencyError> {
+    let mut resolver = Resolver::new(root, manifest, update.clone())?;
+    let root = resolver.root.0.clone();
+
+    let mut direct = Vec::new();
+    for spec in manifest.dependencies.iter() {
+        direct.push(resolver.resolve(&root, spec)?);
+    }
+    resolver.resolve_registry()?;
+
+    let mut lockfile = Lockfile {
+        packages: resolver.resolved.values().cloned().collect(),
+    };
+    lockfile.packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let direct = direct
+        .into_iter()
+        .filter(|name| name != &resolver.root.1)
+        .map(|name| {
+            let (directory, locked) = resolver
+                .resolved
+                .iter()
+                .find(|(_, locked)| locked.name == name)
+                .expect("Every dependency is resolved.");
+            Ok(ResolvedDependency {
+                project: Project::load_from_directory(directory.clone())?,
+                locked: locked.clone(),
+            })
+        })
+        .collect::<Result<Vec<_>, DependencyError>>()?;
+
+    Ok((direct, lockfile))
+}
+
+/// Resolves dependencies and updates the lockfile if it changed.
+///
+/// Vendored workspaces resolve from their `vendor` directory instead,
+/// and their lockfile is never changed.
+pub fn resolve_and_lock(
+    root: &Path,
+    manifest: &WorkspaceManifest,
+    update: &Update,
+) -> Result<Vec<ResolvedDependency>, DependencyError> {
+    if vendor::is_vendored(root) {
+        return vendor::resolve_vendored(root, manifest);
+    }
+
+    let (direct, lockfile) = resolve(root, manifest, update)?;
+
+    if Lockfile::load(root)?.as_ref() != Some(&lockfile) {
+        lockfile.save(root)?;
+    }
+
+    Ok(direct)
+}
+
+struct Resolver {
+    /// Directory and name of the workspace being resolved.
+    root: (PathBuf, String),
+    /// The workspace's lockfile, as it was before resolution.
+    previous: Lockfile,
+    update: Update,
+    /// Resolved packages, by their canonical directory.
+    resolved: HashMap<PathBuf, LockedPackage>,
+    /// Package index of the workspace being resolved, if it has one.
+    registry: Option<PathBuf>,
+    /// Versions asked of the registry so far. They are solved all together, at the end.
+    requirements: Vec<Requirement>,
+}
+
+impl Resolver {
+    fn new(
+        root: &Path,
+        manifest: &WorkspaceManifest,
+        update: Update,
+    ) -> Result<Self, DependencyError> {
+        Ok(Resolver {
+            root: (root.canonicalize()?, manifest.name.clone()),
+            previous: Lockfile::load(root)?.unwrap_or_default(),
+            update,
+            resolved: HashMap::new(),
+            registry: manifest
+                .registry
+                .as_ref()
+                .map(|registry| root.join(registry)),
+            requirements: Vec::new(),
+        })
+    }
+
+    /// Resolves `spec`, as declared by the workspace at `dependent`, and
+    /// everything it depends on. Returns the dependency's name.
+    ///
+    /// Registry dependencies are only recorded as requirements,
+    /// until [`Resolver::resolve_registry`] picks their versions.
+    fn resolve(&mut self, dependent: &Path, spec: &str) -> Result<String, DependencyError> {
+        let (directory, source) = match DependencySpec::parse(spec)? {
+            DependencySpec::Registry { name, requirement } => {
+                if self.registry.is_none() {
+                    Err(NoRegistry {
+                        spec: spec.to_string(),
+                    })?;
+                }
+                let requirer = if dependent == self.root.0 {
+                    self.root.1.clone()
+                } else {
+                    self.resolved[dependent].name.clone()
+                };
+                self.requirements.push(Requirement {
+                    requirer,
+                    name: name.clone(),
+                    requirement,
+                });
+                return Ok(name);
+            }
+            DependencySpec::Path(path) => {
+                let directory = workspace_directory(&dependent.join(path))?;
+                let path = lockfile::relative_path(&self.root.0, &directory);
+                (directory, LockedSource::Path { path })
+            }
+            DependencySpec::Git { url, reference } => {
+                let commit = self.git_commit(&url, reference.as_deref())?;
+                let directory = workspace_directory(&git::checkout(&self.root.0, &url, &commit)?)?;
+                (
+                    directory,
+                    LockedSource::Git {
+                        url,
+                        reference,
+                        commit,
+                    },
+                )
+            }
+        };
+
+        if directory == self.root.0 {
+            return Ok(self.root.1.clone());
+        }
+        if let Some(locked) = self.resolved.get(&directory) {
+            return Ok(locked.name.clone());
+        }
+
+        let manifest = WorkspaceManifest::load(&directory.join(SOL_MANIFEST_FILENAME))?;
+        self.check_name(&manifest.name, &directory)?;
+        let locked = self.lock(&manifest, &directory, source)?;
+        // Registered before recursing, so that cycles terminate.
+        self.resolved.insert(directory.clone(), locked);
+
+        let mut dependencies = Vec::new();
+        for spec in manifest.dependencies.iter() {
+            dependencies.push(self.resolve(&directory, spec)?);
+        }
+        dependencies.sort();
+        self.resolved
+            .get_mut(&directory)
+            .expect("Registered above.")
+            .dependencies = dependencies;
+
+        Ok(manifest.name)
+    }
+
+    /// Picks versions for every registry requirement found while resolving.
+    fn resolve_registry(&mut self) -> Result<(), DependencyError> {
+        let Some(directory) = self.registry.clone() else {
+            return Ok(());
+        };
+        let registry = Registry::new(directory);
+
+        let preferred = self
+            .previous
+            .packages
+            .iter()
+            .filter(|package| matches!(package.source, LockedSource::Registry { .. }))
+            .filter(|package| !self.update.allows(&package.name))
+            .map(|package| (package.name.clone(), package.version.clone()))
+            .collect();
+        let requirements = std::mem::take(&mut self.requirements);
+        let solution = registry::solve(&registry, requirements, &preferred)?;
+
+        for (name, version) in solution {
+            let manifest = registry.manifest(&name, &version)?;
+            let directory = registry.package_directory(&name, &version).canonicalize()?;
+            self.check_name(&name, &directory)?;
+
+            let index = lockfile::relative_path(&self.root.0, &registry.directory.canonicalize()?);
+            let mut locked = self.lock(&manifest, &directory, LockedSource::Registry { index })?;
+            for spec in manifest.dependencies.iter() {
+                if let DependencySpec::Registry { name, .. } = DependencySpec::parse(spec)? {
+                    locked.dependencies.push(name);
+                }
+            }
+            locked.dependencies.sort();
+            self.resolved.insert(directory, locked);
+        }
+        Ok(())
+    }
+
+    /// Fails if a package other than the one at `directory` is already called `name`.
+    fn check_name(&self, name: &str, directory: &Path) -> Result<(), DependencyError> {
+        let conflict = self
+            .resolved
+            .iter()
+            .map(|(directory, locked)| (directory, &locked.name))
+            .chain([(&self.root.0, &self.root.1)])
+            .find(|(other, other_name)| *other_name == name && *other != directory);
+        if let Some((other, _)) = conflict {
+            Err(PackageConflict {
+                name: name.to_string(),
+                first: other.clone(),
+                second: directory.to_path_buf(),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Describes the package at `directory` for the lockfile, without its dependencies.
+    fn lock(
+        &self,
+        manifest: &WorkspaceManifest,
+        directory: &Path,
+        source: LockedSource,
+    ) -> Result<LockedPackage, DependencyError> {
+        let hash = lockfile::content_hash(directory, std::slice::from_ref(&manifest.output))?;
+        let locked = LockedPackage {
+            name: manifest.name.clone(),
+            version: manifest.version.clone(),
+            source,
+            hash,
+            dependencies: Vec::new(),
+        };
+
+        // Git commits and published versions are immutable,
+        // so they must hash as they did when locked.
+        if !matches!(locked.source, LockedSource::Path { .. }) {
+            if let Some(previous) = self.previous_version_of(&locked) {
+                if previous.hash != locked.hash {
+                    Err(HashMismatch {
+                        name: locked.name.clone(),
+                        directory: directory.to_path_buf(),
+                        expected: previous.hash.clone(),
+                        found: locked.hash.clone(),
+                    })?;
+                }
+            }
+        }
+        Ok(locked)
+    }
+
+    /// Picks the commit a git dependency resolves to: the locked one, if
+    /// there is one and it may not be updated, or the reference's latest.
+    fn git_commit(&self, url: &str, reference: Option<&str>) -> Result<String, DependencyError> {
+        let locked = self.previous.packages.iter().find(|package| {
+            matches!(&package.source, LockedSource::Git { url: locked_url, reference: locked_reference, .. }
+                if locked_url == url && locked_reference.as_deref() == reference)
+        });
+
+        match locked {
+            Some(package) if !self.update.allows(&package.name) => {
+                let LockedSource::Git { commit, .. } = &package.source else {
+                    unreachable!("Matched as a git source above.");
+                };
+                if !git::has_commit(&self.root.0, url, commit) {
+                    git::fetch(&self.root.0, url)?;
+                    if !git::has_commit(&self.root.0, url, commit) {
+                        Err(MissingCommit {
+                            url: url.to_string(),
+                            commit: commit.clone(),
+                        })?;
+                    }
+                }
+                Ok(commit.clone())
+            }
+            _ => {
+                git::fetch(&self.root.0, url)?;
+                Ok(git::resolve_reference(&self.root.0, url, reference)?)
+            }
+        }
+    }
+
+    fn previous_version_of(&self, locked: &LockedPackage) -> Option<&LockedPackage> {
+        self.previous.packages.iter().find(|package| {
+            package.source == locked.source
+                && package.name == locked.name
+                && package.version == locked.version
+        })
+    }
+}
+
+fn workspace_directory(path: &Path) -> Result<PathBuf, DependencyError> {
+    if !path.join(SOL_MANIFEST_FILENAME).is_file() {
+        Err(NotAWorkspace {
+            path: path.to_path_buf(),
+        })?;
+    }
+    Ok(path.canonicalize()?)
+}
+
+/// Adds a dependency to the workspace at `root`. Returns its package name.
+///
+/// Relative paths in `spec` are relative to `cwd`.
+pub fn add(root: &Path, cwd: &Path, spec: &str) -> Result<String, DependencyError> {
+    let manifest_path = root.join(SOL_MANIFEST_FILENAME);
+    let source = std::fs::read_to_string(&manifest_path)?;
+    let mut manifest = WorkspaceManifest::parse(&manifest_path.display().to_string(), &source)?;
+
+    let spec = match DependencySpec::parse(spec)? {
+        DependencySpec::Path(path) => {
+            let directory = workspace_directory(&cwd.join(path))?;
+            lockfile::relative_path(&root.canonicalize()?, &directory)
+                .display()
+                .to_string()
+        }
+        DependencySpec::Git { .. } | DependencySpec::Registry { .. } => spec.to_string(),
+    };
+
+    let mut resolver = Resolver::new(root, &manifest, Update::Nothing)?;
+    let root_directory = resolver.root.0.clone();
+    let name = resolver.resolve(&root_directory, &spec)?;
+    let lockfile = Lockfile::load(root)?.unwrap_or_default();
+    if manifest
+        .dependencies
+        .iter()
+        .any(|existing| locked_name(root, existing, &lockfile).as_ref() == Some(&name))
+    {
+        Err(DependencyAlreadyAdded { name: name.clone() })?;
+    }
+
+    manifest.dependencies.push(spec);
+    save_dependencies(root, &manifest, &source)?;
+    Ok(name)
+}
+
+/// Removes the dependency called `name` from the workspace at `root`.
+pub fn remove(root: &Path, name: &str) -> Result<(), DependencyError> {
+    let manifest_path = root.join(SOL_MANIFEST_FILENAME);
+    let source = std::fs::read_to_string(&manifest_path)?;
+    let mut manifest = WorkspaceManifest::parse(&manifest_path.display().to_string(), &source)?;
+
+    let lockfile = Lockfile::load(root)?;
+    let locked = lockfile.clone().unwrap_or_default();
+    let index = manifest
+        .dependencies
+        .iter()
+        .position(|spec| locked_name(root, spec, &locked).as_deref() == Some(name))
+        .ok_or_else(|| DependencyNotFound {
+            name: name.to_string(),
+        })?;
+    manifest.dependencies.remove(index);
+    let edited = manifest::with_dependencies(
+        &manifest_path.display().to_string(),
+        &source,
+        &manifest.dependencies,
+    )?;
+    std::fs::write(manifest_path, edited)?;
+
+    // What's left isn't resolved again, so removing works offline, and with other
+    // dependencies missing. Unless every one is locked, the next build relocks them.
+    if let Some(mut lockfile) = lockfile {
+        let remaining = manifest
+            .dependencies
+            .iter()
+            .map(|spec| locked_name(root, spec, &lockfile))
+            .collect::<Option<Vec<_>>>();
+        if let Some(remaining) = remaining {
+            lockfile.retain_needed(&remaining);
+            lockfile.save(root)?;
+        }
+    }
+    Ok(())
+}
+
+/// The name of the package `spec` stands for, as the spec itself or the lockfile of the
+/// workspace at `root` says, without resolving it. Path dependencies that aren't locked
+/// yet are named by their manifest.
+fn locked_name(root: &Path, spec: &str, lockfile: &Lockfile) -> Option<String> {
+    let locked = |matches: &dyn Fn(&LockedSource) -> bool| {
+        lockfile
+            .packages
+            .iter()
+            .find(|package| matches(&package.source))
+            .map(|package| package.name.clone())
+    };
+    match DependencySpec::parse(spec).ok()? {
+        DependencySpec::Registry { name, .. } => Some(name),
+        DependencySpec::Git { url, reference } => locked(&|source| {
+            matches!(source, LockedSource::Git { url: locked_url, reference: locked_reference, .. }
+                if *locked_url == url && *locked_reference == reference)
+        }),
+        DependencySpec::Path(path) => {
+            fn components(path: &Path) -> Vec<std::path::Component<'_>> {
+                path.components()
+                    .filter(|component| *component != std::path::Component::CurDir)
+                    .collect()
+            }
+            locked(&|source| {
+                matches!(source, LockedSource::Path { path: locked_path }
+                    if components(locked_path) == components(&path))
+            })
+            .or_else(|| {
+                WorkspaceManifest::load(&root.join(&path).join(SOL_MANIFEST_FILENAME))
+                    .ok()
+                    .map(|manifest| manifest.name)
+            })
+        }
+    }
+}
+
+/// Moves the locked versions of dependencies forward. Returns the packages that changed.
+pub fn update(root: &Path, names: &[String]) -> Result<Vec<LockedPackage>, DependencyError> {
+    let manifest = WorkspaceManifest::load(&root.join(SOL_MANIFEST_FILENAME))?;
+    let previous = Lockfile::load(root)?.unwrap_or_default();
+
+    for name in names {
+        if previous.package(name).is_none() {
+            Err(DependencyNotFound { name: name.clone() })?;
+        }
+    }
+    let update = if names.is_empty() {
+        Update::Everything
+    } else {
+        Update::Packages(names.to_vec())
+    };
+
+    let (_, lockfile) = resolve(root, &manifest, &update)?;
+    lockfile.save(root)?;
+
+    Ok(lockfile
+        .packages
+        .into_iter()
+        .filter(|package| previous.package(&package.name) != Some(package))
+        .collect())
+}
+
+/// Writes `manifest`'s dependencies into `index.sol` and updates the lockfile.
+fn save_dependencies(
+    root: &Path,
+    manifest: &WorkspaceManifest,
+    source: &str,
+) -> Result<(), DependencyError> {
+    let manifest_path = root.join(SOL_MANIFEST_FILENAME);
+    let edited = manifest::with_dependencies(
+        &manifest_path.display().to_string(),
+        source,
+        &manifest.dependencies,
+    )?;
+
+    // Resolve before writing anything, so that a broken graph leaves the workspace untouched.
+    let (_, lockfile) = resolve(root, manifest, &Update::Nothing)?;
+    std::fs::write(manifest_path, edited)?;
+    lockfile.save(root)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add, remove, resolve, update, DependencySpec, Update};
+    use crate::workspace::{
+        lockfile::{LockedSource, Lockfile},
+        manifest::WorkspaceManifest,
+        SOL_MANIFEST_FILENAME,
+    };
+    use std::{fs, path::Path};
+    use tempfile::tempdir;
+
+    fn workspace(path: &Path, name: &str, dependencies: &[&str]) {
+        let mut manifest = WorkspaceManifest::new(name);
+        manifest.dependencies = dependencies.iter().map(|spec| spec.to_string()).collect();
+        fs::create_dir_all(path.join("src")).unwrap();
+        fs::write(path.join(SOL_MANIFEST_FILENAME), manifest.to_source()).unwrap();
+    }
+
+    #[test]
+    fn add_and_remove() {
+        let temp = tempdir().unwrap();
+        let game = temp.path().join("game");
+        workspace(&game, "game", &[]);
+        workspace(&temp.path().join("shared"), "shared", &["../icons"]);
+        workspace(&temp.path().join("icons"), "icons", &[]);
+
+        let name = add(&game, temp.path(), "shared").unwrap();
+        assert_eq!(name, "shared");
+
+        let manifest = WorkspaceManifest::load(&game.join(SOL_MANIFEST_FILENAME)).unwrap();
+        assert_eq!(manifest.dependencies, vec!["../shared"]);
+
+        let lockfile = Lockfile::load(&game).unwrap().unwrap();
+        let names = lockfile
+            .packages
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["icons", "shared"]);
+        assert_eq!(
+            lockfile.package("icons").unwrap().source,
+            LockedSource::Path {
+                path: "../icons".into()
+            }
+        );
+        assert!(add(&game, temp.path(), "shared").is_err());
+
+        remove(&game, "shared").unwrap();
+        let manifest = WorkspaceManifest::load(&game.join(SOL_MANIFEST_FILENAME)).unwrap();
+        assert!(manifest.dependencies.is_empty());
+        assert!(Lockfile::load(&game).unwrap().unwrap().packages.is_empty());
+
+        // Removing doesn't need the other dependencies to be there anymore.
+        workspace(&temp.path().join("other"), "other", &[]);
+        add(&game, temp.path(), "shared").unwrap();
+        add(&game, temp.path(), "other").unwrap();
+        fs::remove_dir_all(temp.path().join("other")).unwrap();
+        remove(&game, "shared").unwrap();
+        let manifest = WorkspaceManifest::load(&game.join(SOL_MANIFEST_FILENAME)).unwrap();
+        assert_eq!(manifest.dependencies, vec!["../other"]);
+        let lockfile = Lockfile::load(&game).unwrap().unwrap();
+        assert_eq!(lockfile.packages.len(), 1);
+        assert_eq!(lockfile.packages[0].name, "other");
+    }
+
+    #[test]
+    fn refuses_options_for_git() {
+        assert!(DependencySpec::parse("git+file:///assets.git#v1.0").is_ok());
+        assert!(DependencySpec::parse("git+--upload-pack=touch /tmp/owned").is_err());
+        assert!(DependencySpec::parse("git+file:///assets.git#--output=x").is_err());
+    }
+
+    #[test]
+    fn cycles_terminate() {
+        let temp = tempdir().unwrap();
+        workspace(&temp.path().join("a"), "a", &["../b"]);
+        workspace(&temp.path().join("b"), "b", &["../a"]);
+
+        let root = temp.path().join("a");
+        let manifest = WorkspaceManifest::load(&root.join(SOL_MANIFEST_FILENAME)).unwrap();
+        let (direct, lockfile) = resolve(&root, &manifest, &Update::Nothing).unwrap();
+        assert_eq!(direct.len(), 1);
+        assert_eq!(lockfile.packages.len(), 1);
+        assert_eq!(lockfile.packages[0].dependencies, vec!["a"]);
+    }
+
+    fn git(repository: &Path, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=Sol", "-c", "user.email=sol@example.com"])
+            .arg("-C")
+            .arg(repository)
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn git_dependencies_are_pinned() {
+        let temp = tempdir().unwrap();
+        let repository = temp.path().join("assets");
+        workspace(&repository, "assets", &[]);
+        fs::write(repository.join("src/sword.sol"), "name = \"Sword\"\n").unwrap();
+        git(
+            &repository,
+            &["init", "--quiet", "--initial-branch", "main"],
+        );
+        git(&repository, &["add", "."]);
+        git(&repository, &["commit", "--quiet", "-m", "First"]);
+
+        let game = temp.path().join("game");
+        workspace(&game, "game", &[]);
+        let spec = format!("git+file://{}#main", repository.display());
+        assert_eq!(add(&game, temp.path(), &spec).unwrap(), "assets");
+
+        let locked = |game: &Path| {
+            let lockfile = Lockfile::load(game).unwrap().unwrap();
+            match &lockfile.package("assets").unwrap().source {
+                LockedSource::Git { commit, .. } => commit.clone(),
+                other => panic!("Expected a git source, found {:?}.", other),
+            }
+        };
+        let first = locked(&game);
+
+        // New commits don't move the pin...
+        fs::write(repository.join("src/shield.sol"), "name = \"Shield\"\n").unwrap();
+        git(&repository, &["add", "."]);
+        git(&repository, &["commit", "--quiet", "-m", "Second"]);
+        let manifest = WorkspaceManifest::load(&game.join(SOL_MANIFEST_FILENAME)).unwrap();
+        let (direct, _) = resolve(&game, &manifest, &Update::Nothing).unwrap();
+        assert_eq!(direct[0].project.modules.len(), 1);
+        assert_eq!(locked(&game), first);
+
+        // ...until the dependency is updated.
+        let updated = update(&game, &[]).unwrap();
+        assert_eq!(updated.len(), 1);
+        assert_ne!(locked(&game), first);
+        let (direct, _) = resolve(&game, &manifest, &Update::Nothing).unwrap();
+        assert_eq!(direct[0].project.modules.len(), 2);
+
+        // Tampering with the cached checkout is caught.
+        let checkout = direct[0].project.index_directory.clone();
+        fs::write(checkout.join("src/sword.sol"), "name = \"Stick\"\n").unwrap();
+        assert!(matches!(
+            resolve(&game, &manifest, &Update::Nothing),
+            Err(super::DependencyError::HashMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn registry_versions_are_locked() {
+        let temp = tempdir().unwrap();
+        let publish = |name: &str, version: &str, dependencies: &[&str]| {
+            let directory = temp.path().join("packages").join(name).join(version);
+            workspace(&directory, name, dependencies);
+            let path = directory.join(SOL_MANIFEST_FILENAME);
+            let mut manifest = WorkspaceManifest::load(&path).unwrap();
+            manifest.version = version.parse().unwrap();
+            fs::write(path, manifest.to_source()).unwrap();
+        };
+        publish("items", "1.2.0", &[]);
+        publish("items", "2.0.0", &[]);
+        publish("weapons", "1.0.0", &["items@^1"]);
+
+        let game = temp.path().join("game");
+        let mut manifest = WorkspaceManifest::new("game");
+        manifest.registry = Some("../packages".into());
+        fs::create_dir_all(game.join("src")).unwrap();
+        fs::write(game.join(SOL_MANIFEST_FILENAME), manifest.to_source()).unwrap();
+
+        assert_eq!(add(&game, temp.path(), "weapons@^1").unwrap(), "weapons");
+        let version = |game: &Path, name: &str| {
+            let lockfile = Lockfile::load(game).unwrap().unwrap();
+            lockfile.package(name).unwrap().version.to_string()
+        };
+        assert_eq!(version(&game, "items"), "1.2.0");
+        assert_eq!(
+            Lockfile::load(&game)
+                .unwrap()
+                .unwrap()
+                .package("weapons")
+                .unwrap()
+                .source,
+            LockedSource::Registry {
+                index: "../packages".into()
+            }
+        );
+
+        // Newer compatible versions are only picked up by `sol update`.
+        publish("items", "1.3.0", &[]);
+        add(&game, temp.path(), "items@^1.2").unwrap();
+        assert_eq!(version(&game, "items"), "1.2.0");
+        update(&game, &["items".to_string()]).unwrap();
+        assert_eq!(version(&game, "items"), "1.3.0");
+
+        // A requirement nothing can satisfy is refused, leaving the manifest as it was.
+        remove(&game, "items").unwrap();
+        assert!(matches!(
+            add(&game, temp.path(), "items@^2"),
+            Err(super::DependencyError::VersionConflict(_))
+        ));
+        let manifest = WorkspaceManifest::load(&game.join(SOL_MANIFEST_FILENAME)).unwrap();
+        assert_eq!(manifest.dependencies, vec!["weapons@^1"]);
+    }
+}
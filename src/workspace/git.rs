@@ -0,0 +1,171 @@
+//! Fetching dependencies from git repositories, through the `git` command.
+//!
+//! Repositories are mirrored into the workspace-local cache, at `.sol/git/db`,
+//! and each commit that is depended on is checked out into `.sol/git/checkouts`.
+use miette::Diagnostic;
+use sha2::{Digest, Sha256};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+use thiserror::Error;
+
+/// Directory, relative to a workspace, where fetched dependencies are kept.
+pub const SOL_CACHE_DIRECTORY: &str = ".sol";
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`git {command}` failed: {stderr}")]
+#[diagnostic(help("Make sure `git` is installed and the repository URL is correct."))]
+pub struct GitError {
+    pub command: String,
+    pub stderr: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("The locked commit {commit} is not in {url}.")]
+#[diagnostic(help(
+    "It might have been removed by a force-push. Run `sol update` to pick a new commit."
+))]
+pub struct MissingCommit {
+    pub url: String,
+    pub commit: String,
+}
+
+/// The mirror of the repository at `url`, inside the cache of the workspace at `root`.
+fn database(root: &Path, url: &str) -> PathBuf {
+    root.join(SOL_CACHE_DIRECTORY)
+        .join("git/db")
+        .join(short_hash(url))
+}
+
+/// Mirrors (or updates the mirror of) the repository at `url`.
+///
+/// URLs come from manifests of dependencies, so they're always passed after `--`,
+/// where `git` can't take them for options, as in `--upload-pack=...`.
+pub fn fetch(root: &Path, url: &str) -> Result<(), GitError> {
+    let database = database(root, url);
+    if database.exists() {
+        git(
+            Some(&database),
+            &[
+                "fetch",
+                "--force",
+                "--tags",
+                "--",
+                url,
+                "+refs/heads/*:refs/heads/*",
+            ],
+        )?;
+    } else {
+        std::fs::create_dir_all(database.parent().expect("The database is in the cache."))
+            .map_err(|err| io_error("clone", err))?;
+        git(
+            None,
+            &[
+                "clone",
+                "--bare",
+                "--quiet",
+                "--",
+                url,
+                &database.to_string_lossy(),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// Finds which commit `reference` (a branch, tag or commit) points to in the
+/// mirror of `url`. No reference means the default branch.
+pub fn resolve_reference(
+    root: &Path,
+    url: &str,
+    reference: Option<&str>,
+) -> Result<String, GitError> {
+    let revision = format!("{}^{{commit}}", reference.unwrap_or("HEAD"));
+    git(
+        Some(&database(root, url)),
+        &["rev-parse", "--verify", "--quiet", &revision],
+    )
+}
+
+/// Whether the mirror of `url` exists and has `commit`.
+pub fn has_commit(root: &Path, url: &str, commit: &str) -> bool {
+    let database = database(root, url);
+    database.exists()
+        && git(
+            Some(&database),
+            &["cat-file", "-e", &format!("{}^{{commit}}", commit)],
+        )
+        .is_ok()
+}
+
+/// Checks `commit` of `url` out into the cache, if it isn't already. Returns its directory.
+pub fn checkout(root: &Path, url: &str, commit: &str) -> Result<PathBuf, GitError> {
+    let checkout = root
+        .join(SOL_CACHE_DIRECTORY)
+        .join("git/checkouts")
+        .join(format!(
+            "{}-{}",
+            short_hash(url),
+            &commit[..12.min(commit.len())]
+        ));
+    if checkout.exists() {
+        return Ok(checkout);
+    }
+
+    // Checked out next to the destination and then moved,
+    // so that an interrupted checkout is never mistaken for a complete one.
+    let partial = checkout.with_extension("partial");
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial).map_err(|err| io_error("checkout", err))?;
+    }
+    std::fs::create_dir_all(&partial).map_err(|err| io_error("checkout", err))?;
+    git(
+        Some(&database(root, url)),
+        &[
+            "--work-tree",
+            &partial.to_string_lossy(),
+            "checkout",
+            "--force",
+            commit,
+            "--",
+            ".",
+        ],
+    )?;
+    std::fs::rename(&partial, &checkout).map_err(|err| io_error("checkout", err))?;
+
+    Ok(checkout)
+}
+
+fn git(database: Option<&Path>, args: &[&str]) -> Result<String, GitError> {
+    let mut command = Command::new("git");
+    if let Some(database) = database {
+        command.arg("--git-dir").arg(database);
+    }
+    let output = command
+        .args(args)
+        .output()
+        .map_err(|err| io_error(args[0], err))?;
+
+    if !output.status.success() {
+        return Err(GitError {
+            command: args.join(" "),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn io_error(command: &str, err: std::io::Error) -> GitError {
+    GitError {
+        command: command.to_string(),
+        stderr: err.to_string(),
+    }
+}
+
+fn short_hash(text: &str) -> String {
+    Sha256::digest(text.as_bytes())[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,217 @@
+//! Checking that the game supplies every extern a package uses.
+//!
+//! The game says what it supplies in a host manifest, a module made of externs,
+//! which `index.sol` points at with `host = "host.sol"`:
+//!
+//! ```lua
+//! extern player_name: Text
+//! extern give: Action(Item)
+//! ```
+//!
+//! [`HostRegistry::manifest`](crate::runtime::host::HostRegistry::manifest) writes one
+//! from what the game registered.
+use super::{migrate, ParsedModule};
+use crate::{
+    introspec::project::ModulePath,
+    parser::ast::{Expression, Extern, Span},
+};
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic)]
+#[error(transparent)]
+pub enum HostError {
+    #[diagnostic(transparent)]
+    MissingHostManifest(#[from] Box<MissingHostManifest>),
+    #[diagnostic(transparent)]
+    UnsuppliedExtern(#[from] Box<UnsuppliedExtern>),
+    #[diagnostic(transparent)]
+    MismatchedExtern(#[from] Box<MismatchedExtern>),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{name}` is supplied by the game, but there's no host manifest saying what it supplies.")]
+#[diagnostic(
+    code(sol::missing_host_manifest),
+    help("Add `host = \"host.sol\"` to `index.sol`, and declare `extern {name}: {extern_type}` there.")
+)]
+pub struct MissingHostManifest {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("used here")]
+    pub span: SourceSpan,
+    pub name: String,
+    pub extern_type: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{name}` is used, but the game doesn't supply it.")]
+#[diagnostic(
+    code(sol::unsupplied_extern),
+    help("Register `{name}` with the game's host registry, and declare `extern {name}: {extern_type}` in `{manifest}`.")
+)]
+pub struct UnsuppliedExtern {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("not in the host manifest")]
+    pub span: SourceSpan,
+    pub name: String,
+    pub extern_type: String,
+    pub manifest: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{name}` is declared as `{declared}`, but the game supplies a `{supplied}`.")]
+#[diagnostic(code(sol::mismatched_extern))]
+pub struct MismatchedExtern {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("used here")]
+    pub span: SourceSpan,
+    pub name: String,
+    pub declared: String,
+    pub supplied: String,
+}
+
+/// Checks that every extern `module` uses, its own or another module's, is declared
+/// the same way in `host`, the host manifest, if the package has one.
+///
+/// `modules` are every module references can resolve to, as they're referred to.
+pub fn check(
+    module: &ParsedModule,
+    modules: &[(ModulePath, &ParsedModule)],
+    host: Option<&ParsedModule>,
+) -> Result<(), HostError> {
+    let own = |name: &str| {
+        module
+            .ast
+            .externs
+            .iter()
+            .find(|declaration| declaration.name == name)
+    };
+    let mut uses = Vec::<(&Extern, Span)>::new();
+    let mut collect = |expression: &Expression| match expression {
+        Expression::Call {
+            name, name_span, ..
+        } => uses.extend(own(name).map(|declaration| (declaration, *name_span))),
+        Expression::Symbol(symbol) => match symbol.path.split_last() {
+            Some((name, [])) => {
+                uses.extend(own(name).map(|declaration| (declaration, symbol.span)))
+            }
+            Some((name, target)) => uses.extend(
+                modules
+                    .iter()
+                    .find(|(path, _)| path.0 == target)
+                    .and_then(|(_, module)| {
+                        module
+                            .ast
+                            .externs
+                            .iter()
+                            .find(|declaration| &declaration.name == name)
+                    })
+                    .map(|declaration| (declaration, symbol.span)),
+            ),
+            None => {}
+        },
+        _ => {}
+    };
+    migrate::visit_module(&module.ast, &mut collect);
+    for test in module.ast.tests.iter() {
+        for statement in test.statements.iter() {
+            migrate::visit_expressions(&statement.expression, &mut collect);
+        }
+    }
+    uses.sort_by_key(|(_, span)| span.start);
+
+    for (declaration, span) in uses {
+        let Some(host) = host else {
+            return Err(Box::new(MissingHostManifest {
+                src: module.named_source(),
+                span: span.into(),
+                name: declaration.name.clone(),
+                extern_type: declaration.extern_type.to_string(),
+            })
+            .into());
+        };
+        let Some(supplied) = host
+            .ast
+            .externs
+            .iter()
+            .find(|supplied| supplied.name == declaration.name)
+        else {
+            return Err(Box::new(UnsuppliedExtern {
+                src: module.named_source(),
+                span: span.into(),
+                name: declaration.name.clone(),
+                extern_type: declaration.extern_type.to_string(),
+                manifest: host.file_name.clone(),
+            })
+            .into());
+        };
+        if supplied.extern_type != declaration.extern_type {
+            return Err(Box::new(MismatchedExtern {
+                src: module.named_source(),
+                span: span.into(),
+                name: declaration.name.clone(),
+                declared: declaration.extern_type.to_string(),
+                supplied: supplied.extern_type.to_string(),
+            })
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, HostError};
+    use crate::{introspec::project::ModulePath, parser, workspace::ParsedModule};
+
+    fn module(file_name: &str, source: &str) -> ParsedModule {
+        ParsedModule {
+            file_name: file_name.to_string(),
+            source: source.to_string(),
+            ast: parser::parse_module(file_name, source).unwrap(),
+        }
+    }
+
+    #[test]
+    fn checks_externs_against_the_host() {
+        let game = module(
+            "game.sol",
+            "extern player_name: Text\nextern coins: Nat\nextern unused: Nat\n",
+        );
+        let intro = module(
+            "intro.sol",
+            "scene main\n    - Welcome, {::game::player_name}!\nend\n",
+        );
+        let shop = module(
+            "shop.sol",
+            "scene main\n    - You have {::game::coins} coins.\nend\n",
+        );
+        let modules = [
+            (ModulePath::parse("game").unwrap(), &game),
+            (ModulePath::parse("intro").unwrap(), &intro),
+            (ModulePath::parse("shop").unwrap(), &shop),
+        ];
+        let host = module("host.sol", "extern player_name: Text\nextern coins: Int\n");
+
+        check(&game, &modules, None).unwrap();
+        check(&intro, &modules, Some(&host)).unwrap();
+        assert!(matches!(
+            check(&intro, &modules, None),
+            Err(HostError::MissingHostManifest(error)) if error.name == "player_name"
+        ));
+        assert!(matches!(
+            check(&shop, &modules, Some(&host)),
+            Err(HostError::MismatchedExtern(error)) if error.supplied == "Int"
+        ));
+
+        let empty = module("host.sol", "");
+        assert!(matches!(
+            check(&shop, &modules, Some(&empty)),
+            Err(HostError::UnsuppliedExtern(error)) if error.extern_type == "Nat"
+        ));
+    }
+}
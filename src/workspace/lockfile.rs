@@ -0,0 +1,203 @@
+//! The lockfile, `sol.lock`, which records exactly which
+//! dependencies a workspace was built with.
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+pub const SOL_LOCKFILE_FILENAME: &str = "sol.lock";
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// Every package in the dependency graph, sorted by name.
+    pub packages: Vec<LockedPackage>,
+}
+
+/// A dependency, resolved to an exact location and contents.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: semver::Version,
+    pub source: LockedSource,
+    /// Hash of the package's files, as in `sha256:...`.
+    pub hash: String,
+    /// Names of the packages this one depends on.
+    pub dependencies: Vec<String>,
+}
+
+/// Where a locked package comes from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LockedSource {
+    /// A workspace on disk, relative to the workspace that owns the lockfile.
+    Path { path: PathBuf },
+    /// A commit of a git repository.
+    Git {
+        url: String,
+        /// The branch, tag or commit the manifest asks for.
+        reference: Option<String>,
+        commit: String,
+    },
+    /// A published version from a package index, relative to the workspace that owns the lockfile.
+    Registry { index: PathBuf },
+}
+
+impl Lockfile {
+    /// Reads the lockfile of the workspace at `root`, if it has one.
+    pub fn load(root: &Path) -> Result<Option<Lockfile>, std::io::Error> {
+        let path = root.join(SOL_LOCKFILE_FILENAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = std::fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(std::io::Error::other)
+    }
+
+    pub fn save(&self, root: &Path) -> Result<(), std::io::Error> {
+        let mut contents = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        contents.push('\n');
+        std::fs::write(root.join(SOL_LOCKFILE_FILENAME), contents)
+    }
+
+    pub fn package(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages.iter().find(|package| package.name == name)
+    }
+
+    /// Keeps only the packages `direct`, the names of a workspace's dependencies, need.
+    pub fn retain_needed(&mut self, direct: &[String]) {
+        let mut needed = BTreeSet::new();
+        let mut pending = direct.to_vec();
+        while let Some(name) = pending.pop() {
+            if !needed.insert(name.clone()) {
+                continue;
+            }
+            if let Some(package) = self.package(&name) {
+                pending.extend(package.dependencies.iter().cloned());
+            }
+        }
+        self.packages
+            .retain(|package| needed.contains(&package.name));
+    }
+}
+
+/// Hashes every file of the package at `root`, except for `skip`ped
+/// directories (like the build output), hidden files and the lockfile.
+pub fn content_hash(root: &Path, skip: &[PathBuf]) -> Result<String, std::io::Error> {
+    let mut files = Vec::new();
+    collect_files(root, root, skip, &mut files)?;
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for file in files {
+        hasher.update(file.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(std::fs::read(root.join(&file))?);
+        hasher.update([0]);
+    }
+
+    let hash = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    Ok(format!("sha256:{}", hash))
+}
+
+fn collect_files(
+    root: &Path,
+    directory: &Path,
+    skip: &[PathBuf],
+    files: &mut Vec<PathBuf>,
+) -> Result<(), std::io::Error> {
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        let relative = path
+            .strip_prefix(root)
+            .expect("Entries are read from inside the root.")
+            .to_path_buf();
+
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if hidden || skip.contains(&relative) || relative == Path::new(SOL_LOCKFILE_FILENAME) {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_files(root, &path, skip, files)?;
+        } else {
+            files.push(relative);
+        }
+    }
+    Ok(())
+}
+
+/// Expresses `to` relative to the directory `from`. Both must be absolute.
+pub fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from = from.components().collect::<Vec<_>>();
+    let to = to.components().collect::<Vec<_>>();
+    let common = from
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component);
+    }
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_hash, relative_path};
+    use std::{
+        collections::BTreeSet,
+        path::{Path, PathBuf},
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(
+            relative_path(Path::new("/games/maple"), Path::new("/games/shared/items")),
+            PathBuf::from("../shared/items")
+        );
+        assert_eq!(
+            relative_path(Path::new("/games/maple"), Path::new("/games/maple")),
+            PathBuf::from(".")
+        );
+    }
+
+    #[test]
+    fn hash_ignores_build_output() {
+        let temp = tempdir().unwrap();
+        std::fs::write(temp.path().join("index.sol"), "name = \"a\"").unwrap();
+        let before = content_hash(temp.path(), &[PathBuf::from("build")]).unwrap();
+
+        std::fs::create_dir(temp.path().join("build")).unwrap();
+        std::fs::write(temp.path().join("build/out.json"), "{}").unwrap();
+        assert_eq!(
+            content_hash(temp.path(), &[PathBuf::from("build")]).unwrap(),
+            before
+        );
+
+        std::fs::write(temp.path().join("index.sol"), "name = \"b\"").unwrap();
+        assert_ne!(
+            content_hash(temp.path(), &[PathBuf::from("build")]).unwrap(),
+            before
+        );
+    }
+}
@@ -0,0 +1,521 @@
+//! The workspace manifest, `index.sol`.
+//!
+//! A manifest is an ordinary Sol module made only of fields:
+//!
+//! ```lua
+//! name = "maple_room"
+//! version = "0.1.0"
+//! sources = ["src"]
+//! output = "build"
+//! default_locale = "en"
+//! targets = ["json"]
+//! dependencies = []
+//! registry = "../packages"
+//! host = "host.sol"
+//! ```
+//!
+//! Only `name` and `version` are required, everything else has a default.
+use crate::parser::{
+    self,
+    ast::{Expression, Module, Span, TextPart},
+    ParseError,
+};
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The typed contents of a workspace's `index.sol`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceManifest {
+    /// Name of the package, used to import it from other workspaces.
+    pub name: String,
+    pub version: semver::Version,
+    /// Directories (relative to the manifest) where `.sol` modules live.
+    pub sources: Vec<PathBuf>,
+    /// Directory (relative to the manifest) where build artifacts are written.
+    pub output: PathBuf,
+    /// Locale the sources are authored in, as in `"en"` or `"pt_BR"`.
+    pub default_locale: Option<String>,
+    pub targets: Vec<ExportTarget>,
+    /// Dependency specifications, exactly as written in the manifest.
+    pub dependencies: Vec<String>,
+    /// Package index (relative to the manifest) that versioned dependencies are picked from.
+    pub registry: Option<PathBuf>,
+    /// Module (relative to the manifest) declaring the externs the game supplies,
+    /// as in `extern player_name: Text`.
+    pub host: Option<PathBuf>,
+}
+
+/// A format the build can export assets to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportTarget {
+    Json,
+    Rust,
+}
+
+impl ExportTarget {
+    pub const ALL: [ExportTarget; 2] = [ExportTarget::Json, ExportTarget::Rust];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportTarget::Json => "json",
+            ExportTarget::Rust => "rust",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|target| target.name() == name)
+    }
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error(transparent)]
+pub enum ManifestError {
+    IO(#[from] std::io::Error),
+    #[diagnostic(transparent)]
+    Parse(#[from] ParseError),
+    #[diagnostic(transparent)]
+    Invalid(#[from] InvalidManifest),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("Invalid workspace manifest.")]
+#[diagnostic(code(sol::manifest))]
+pub struct InvalidManifest {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[related]
+    pub problems: Vec<ManifestProblem>,
+}
+
+/// A single thing wrong with a manifest, pointing at where it happens.
+#[derive(Error, Debug, Diagnostic)]
+#[error("{message}")]
+pub struct ManifestProblem {
+    pub message: String,
+    #[label("{label}")]
+    pub span: SourceSpan,
+    pub label: String,
+}
+
+impl WorkspaceManifest {
+    /// Creates the manifest `sol init` writes for a new workspace.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: semver::Version::new(0, 1, 0),
+            sources: vec![PathBuf::from("src")],
+            output: PathBuf::from("build"),
+            default_locale: None,
+            targets: vec![ExportTarget::Json],
+            dependencies: Vec::new(),
+            registry: None,
+            host: None,
+        }
+    }
+
+    /// Reads and validates the manifest at `path`.
+    pub fn load(path: &Path) -> Result<Self, ManifestError> {
+        let source = std::fs::read_to_string(path)?;
+        Self::parse(&path.display().to_string(), &source)
+    }
+
+    /// Parses and validates manifest `source`. `file_name` labels the diagnostics.
+    pub fn parse(file_name: &str, source: &str) -> Result<Self, ManifestError> {
+        let module = parser::parse_module(file_name, source)?;
+        let mut reader = ManifestReader {
+            module: &module,
+            end: Span::new(source.len(), source.len()),
+            problems: Vec::new(),
+        };
+
+        for (field_name, field) in module.fields.iter() {
+            if !FIELDS.contains(&field_name.as_str()) {
+                reader.problem(
+                    field.name_span,
+                    format!("Unknown manifest field `{}`.", field_name),
+                    format!("expected one of {}", FIELDS.join(", ")),
+                );
+            }
+        }
+        for scene in module.scenes.iter() {
+            reader.problem(
+                scene.name_span,
+                format!("Manifests can't declare scenes, found `{}`.", scene.name),
+                "move scenes to a module in a source directory",
+            );
+        }
+
+        let name = reader.required("name", |text, span, reader| {
+            if is_package_name(&text) {
+                Some(text)
+            } else {
+                reader.problem(
+                    span,
+                    format!("`{}` is not a valid package name.", text),
+                    "package names are made of letters, digits and `_`",
+                );
+                None
+            }
+        });
+        let version = reader.required("version", |text, span, reader| {
+            semver::Version::parse(&text)
+                .map_err(|err| {
+                    reader.problem(
+                        span,
+                        format!("`{}` is not a valid version.", text),
+                        err.to_string(),
+                    )
+                })
+                .ok()
+        });
+        let sources = reader
+            .optional_list("sources", |text, span, reader| {
+                let path = PathBuf::from(text);
+                if path.is_absolute() {
+                    reader.problem(
+                        span,
+                        "Source directories must be relative to the manifest.",
+                        "absolute path",
+                    );
+                    return None;
+                }
+                Some(path)
+            })
+            .unwrap_or_else(|| vec![PathBuf::from("src")]);
+        let output = reader
+            .optional("output", |text, _, _| Some(PathBuf::from(text)))
+            .unwrap_or_else(|| PathBuf::from("build"));
+        let default_locale = reader.optional("default_locale", |text, _, _| Some(text));
+        let targets = reader
+            .optional_list("targets", |text, span, reader| {
+                let target = ExportTarget::from_name(&text);
+                if target.is_none() {
+                    let known = ExportTarget::ALL.map(|target| target.name()).join(", ");
+                    reader.problem(
+                        span,
+                        format!("Unknown export target `{}`.", text),
+                        format!("expected one of {}", known),
+                    );
+                }
+                target
+            })
+            .unwrap_or_else(|| vec![ExportTarget::Json]);
+        let dependencies = reader
+            .optional_list("dependencies", |text, _, _| Some(text))
+            .unwrap_or_default();
+        let registry = reader.optional("registry", |text, _, _| Some(PathBuf::from(text)));
+        let host = reader.optional("host", |text, _, _| Some(PathBuf::from(text)));
+
+        if !reader.problems.is_empty() {
+            return Err(InvalidManifest {
+                src: NamedSource::new(file_name, source.to_string()),
+                problems: reader.problems,
+            }
+            .into());
+        }
+
+        Ok(Self {
+            name: name.expect("Missing fields are reported as problems."),
+            version: version.expect("Missing fields are reported as problems."),
+            sources,
+            output,
+            default_locale,
+            targets,
+            dependencies,
+            registry,
+            host,
+        })
+    }
+
+    /// Renders the manifest as Sol source.
+    pub fn to_source(&self) -> String {
+        let mut source = String::new();
+        source.push_str(&format!("name = {}\n", quote(&self.name)));
+        source.push_str(&format!("version = {}\n", quote(&self.version.to_string())));
+        source.push_str(&format!(
+            "sources = {}\n",
+            list(self.sources.iter().map(|path| path.display().to_string()))
+        ));
+        source.push_str(&format!(
+            "output = {}\n",
+            quote(&self.output.display().to_string())
+        ));
+        if let Some(locale) = &self.default_locale {
+            source.push_str(&format!("default_locale = {}\n", quote(locale)));
+        }
+        source.push_str(&format!(
+            "targets = {}\n",
+            list(self.targets.iter().map(|target| target.name().to_string()))
+        ));
+        source.push_str(&format!(
+            "dependencies = {}\n",
+            list(self.dependencies.iter().cloned())
+        ));
+        if let Some(registry) = &self.registry {
+            source.push_str(&format!(
+                "registry = {}\n",
+                quote(&registry.display().to_string())
+            ));
+        }
+        if let Some(host) = &self.host {
+            source.push_str(&format!("host = {}\n", quote(&host.display().to_string())));
+        }
+        source
+    }
+}
+
+/// Rewrites the `dependencies` of manifest `source`, leaving everything else
+/// (comments included) as it was written.
+pub fn with_dependencies(
+    file_name: &str,
+    source: &str,
+    dependencies: &[String],
+) -> Result<String, ManifestError> {
+    let module = parser::parse_module(file_name, source)?;
+    let rendered = list(dependencies.iter().cloned());
+
+    Ok(match module.fields.get("dependencies") {
+        Some(field) => format!(
+            "{}{}{}",
+            &source[..field.value_span.start],
+            rendered,
+            &source[field.value_span.end..]
+        ),
+        None => {
+            let separator = if source.is_empty() || source.ends_with('\n') {
+                ""
+            } else {
+                "\n"
+            };
+            format!("{}{}dependencies = {}\n", source, separator, rendered)
+        }
+    })
+}
+
+const FIELDS: [&str; 9] = [
+    "name",
+    "version",
+    "sources",
+    "output",
+    "default_locale",
+    "targets",
+    "dependencies",
+    "registry",
+    "host",
+];
+
+/// Whether `name` can be used as a package name (and so, in a `using`).
+pub fn is_package_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+/// Writes `text` as Sol text, escaping what would otherwise end it or be interpolated.
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for char in text.chars() {
+        if matches!(char, '"' | '\\' | '{') {
+            quoted.push('\\');
+        }
+        quoted.push(char);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Reads text as it's written between quotes, as in `C:\\games`, back into what it stands for.
+fn unescape(text: &str) -> String {
+    let mut unquoted = String::new();
+    let mut chars = text.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => unquoted.extend(chars.next()),
+            char => unquoted.push(char),
+        }
+    }
+    unquoted
+}
+
+fn list(items: impl Iterator<Item = String>) -> String {
+    format!(
+        "[{}]",
+        items
+            .map(|item| quote(&item))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Pulls typed values out of a parsed manifest, collecting problems on the way.
+struct ManifestReader<'m> {
+    module: &'m Module,
+    /// Where missing fields would be added.
+    end: Span,
+    problems: Vec<ManifestProblem>,
+}
+
+impl ManifestReader<'_> {
+    fn problem(&mut self, span: Span, message: impl Into<String>, label: impl Into<String>) {
+        self.problems.push(ManifestProblem {
+            message: message.into(),
+            span: span.into(),
+            label: label.into(),
+        });
+    }
+
+    fn required<T>(
+        &mut self,
+        name: &str,
+        convert: impl FnOnce(String, Span, &mut Self) -> Option<T>,
+    ) -> Option<T> {
+        if !self.module.fields.contains_key(name) {
+            self.problem(
+                self.end,
+                format!("Missing required manifest field `{}`.", name),
+                format!("add `{} = ...` to the manifest", name),
+            );
+        }
+        self.optional(name, convert)
+    }
+
+    fn optional<T>(
+        &mut self,
+        name: &str,
+        convert: impl FnOnce(String, Span, &mut Self) -> Option<T>,
+    ) -> Option<T> {
+        let field = self.module.fields.get(name)?;
+        let text = self.text(&field.value, field.value_span)?;
+        convert(text, field.value_span, self)
+    }
+
+    fn optional_list<T>(
+        &mut self,
+        name: &str,
+        mut convert: impl FnMut(String, Span, &mut Self) -> Option<T>,
+    ) -> Option<Vec<T>> {
+        let field = self.module.fields.get(name)?;
+        let Expression::List { items, item_spans } = &field.value else {
+            self.problem(
+                field.value_span,
+                format!("Manifest field `{}` must be a list.", name),
+                "expected a list, as in `[\"a\", \"b\"]`",
+            );
+            return None;
+        };
+
+        let mut values = Vec::new();
+        for (item, span) in items.iter().zip(item_spans) {
+            if let Some(value) = self
+                .text(item, *span)
+                .and_then(|text| convert(text, *span, self))
+            {
+                values.push(value);
+            }
+        }
+        Some(values)
+    }
+
+    fn text(&mut self, expression: &Expression, span: Span) -> Option<String> {
+        let plain = match expression {
+            Expression::Text(parts) => parts
+                .iter()
+                .map(|part| match part {
+                    TextPart::Text(text) => Some(text.as_str()),
+                    TextPart::Expression(_) => None,
+                })
+                .collect::<Option<String>>()
+                .map(|text| unescape(&text)),
+            _ => None,
+        };
+
+        if plain.is_none() {
+            self.problem(
+                span,
+                "Manifest values must be plain text.",
+                "expected text without interpolation, as in `\"value\"`",
+            );
+        }
+        plain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{with_dependencies, ExportTarget, ManifestError, WorkspaceManifest};
+    use std::path::PathBuf;
+
+    #[test]
+    fn round_trip() {
+        let mut manifest = WorkspaceManifest::new("maple_room");
+        manifest.default_locale = Some("en".to_string());
+        manifest.dependencies.push("../shared".to_string());
+        manifest
+            .dependencies
+            .push("C:\\packages\\{odd} \"name\"".to_string());
+        manifest.registry = Some(PathBuf::from("../packages"));
+        manifest.host = Some(PathBuf::from("host.sol"));
+
+        let parsed = WorkspaceManifest::parse("index.sol", &manifest.to_source());
+        assert_eq!(parsed.unwrap(), manifest);
+    }
+
+    #[test]
+    fn edit_dependencies() {
+        let source =
+            "-- My game.\nname = \"maple\"\nversion = \"0.1.0\"\ndependencies = [] -- None yet.\n";
+        let edited = with_dependencies("index.sol", source, &["../shared".to_string()]).unwrap();
+        assert_eq!(
+            edited,
+            "-- My game.\nname = \"maple\"\nversion = \"0.1.0\"\ndependencies = [\"../shared\"] -- None yet.\n"
+        );
+
+        let appended = with_dependencies("index.sol", "name = \"maple\"", &[]).unwrap();
+        assert_eq!(appended, "name = \"maple\"\ndependencies = []\n");
+    }
+
+    #[test]
+    fn defaults() {
+        let manifest = WorkspaceManifest::parse(
+            "index.sol",
+            "-- Just the basics.\nname = \"maple_room\"\nversion = \"1.2.3\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(manifest.sources, vec![PathBuf::from("src")]);
+        assert_eq!(manifest.output, PathBuf::from("build"));
+        assert_eq!(manifest.targets, vec![ExportTarget::Json]);
+        assert!(manifest.dependencies.is_empty());
+    }
+
+    #[test]
+    fn problems_point_into_the_file() {
+        let source = "name = \"maple room\"\ncolour = \"red\"\ntargets = [\"json\", \"pdf\"]\n\nscene main\nend\n";
+        let Err(ManifestError::Invalid(invalid)) = WorkspaceManifest::parse("index.sol", source)
+        else {
+            panic!("Manifest should be invalid.");
+        };
+
+        let mut labelled = invalid
+            .problems
+            .iter()
+            .map(|problem| {
+                let start = problem.span.offset();
+                &source[start..start + problem.span.len()]
+            })
+            .collect::<Vec<_>>();
+        labelled.sort();
+        assert_eq!(
+            labelled,
+            vec!["", "\"maple room\"", "\"pdf\"", "colour", "main"]
+        );
+        assert!(invalid
+            .problems
+            .iter()
+            .any(|problem| problem.span.offset() == source.len()
+                && problem.message.contains("`version`")));
+    }
+}
@@ -0,0 +1,1063 @@
+//! Declarative migrations, which rewrite a workspace's sources as its models change.
+//!
+//! A migration is a Sol module with a list of `steps`:
+//!
+//! ```lua
+//! -- Items are now used up, instead of worn down.
+//! steps = [
+//!     rename_field("Item", "durability", "uses"),
+//!     set_default("Item", "rarity", "common"),
+//!     rename_speaker("Narator", "Narrator"),
+//!     move_module("items.swords", "weapons.swords"),
+//!     rename_call("give", "give_item"),
+//!     set_version("Item", 2),
+//! ]
+//! ```
+//!
+//! Steps only replace the text they change, so comments and formatting are kept.
+use crate::{
+    introspec::project::{ModulePath, Project, ProjectLoadError},
+    parser::{
+        self,
+        ast::{Expression, Module, ScenePart, Span, TextPart},
+        ParseError,
+    },
+    unparser,
+};
+use clap::Args;
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// One transformation of a migration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationStep {
+    /// Renames a field of a model, in its declaration and in all of its assets,
+    /// along with where their actions and handlers use it.
+    RenameField {
+        model: String,
+        from: String,
+        to: String,
+    },
+    /// Renames a speaker in every scene.
+    RenameSpeaker { from: String, to: String },
+    /// Moves a module (or every module under a path) and updates the `using`s importing it.
+    MoveModule { from: ModulePath, to: ModulePath },
+    /// Adds a field to every asset of a model that doesn't have it yet.
+    SetDefault {
+        model: String,
+        field: String,
+        /// The value, as Sol source.
+        value: String,
+    },
+    /// Renames every call to a function, including the ones on a value, as in `target.damage(3)`.
+    RenameCall { from: String, to: String },
+    /// Records that every asset of a model is authored against `version`.
+    SetVersion { model: String, version: u32 },
+}
+
+/// The parsed contents of a migration file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration {
+    pub steps: Vec<MigrationStep>,
+}
+
+#[derive(Debug, Args)]
+pub struct MigrateArgs {
+    /// The migration file to apply.
+    pub migration: PathBuf,
+
+    /// Print what would change, as a diff, without changing anything.
+    #[arg(short = 'n', long)]
+    pub dry_run: bool,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error(transparent)]
+pub enum MigrateError {
+    IO(#[from] std::io::Error),
+    #[diagnostic(transparent)]
+    Project(#[from] ProjectLoadError),
+    #[diagnostic(transparent)]
+    Parse(#[from] ParseError),
+    #[diagnostic(transparent)]
+    Invalid(#[from] InvalidMigration),
+    #[diagnostic(transparent)]
+    Conflict(#[from] MigrationConflict),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("Invalid migration.")]
+#[diagnostic(code(sol::migration))]
+pub struct InvalidMigration {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[related]
+    pub problems: Vec<MigrationProblem>,
+}
+
+/// A single thing wrong with a migration, pointing at where it happens.
+#[derive(Error, Debug, Diagnostic)]
+#[error("{message}")]
+pub struct MigrationProblem {
+    pub message: String,
+    #[label("{label}")]
+    pub span: SourceSpan,
+    pub label: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("Can't apply `{step}` to {file:?}: {reason}")]
+#[diagnostic(help("Nothing was changed. Fix the workspace or the migration, and try again."))]
+pub struct MigrationConflict {
+    pub step: String,
+    pub file: PathBuf,
+    pub reason: String,
+}
+
+/// Every step, with the Sol name and the number of arguments it's called with.
+const STEPS: [(&str, usize); 6] = [
+    ("rename_field", 3),
+    ("rename_speaker", 2),
+    ("move_module", 2),
+    ("set_default", 3),
+    ("rename_call", 2),
+    ("set_version", 2),
+];
+
+/// Steps a model's `upgrade` can use. They don't take the model's name, which is implied.
+const UPGRADE_STEPS: [&str; 2] = ["rename_field", "set_default"];
+
+impl Migration {
+    pub fn load(path: &Path) -> Result<Self, MigrateError> {
+        let source = std::fs::read_to_string(path)?;
+        Self::parse(&path.display().to_string(), &source)
+    }
+
+    /// Parses and validates migration `source`. `file_name` labels the diagnostics.
+    pub fn parse(file_name: &str, source: &str) -> Result<Self, MigrateError> {
+        let module = parser::parse_module(file_name, source)?;
+        let mut problems = Vec::new();
+
+        for (name, field) in module.fields.iter() {
+            if name != "steps" {
+                problems.push(problem(
+                    field.name_span,
+                    format!("Unknown migration field `{}`.", name),
+                    "expected `steps`",
+                ));
+            }
+        }
+
+        let mut steps = Vec::new();
+        match module.fields.get("steps") {
+            None => problems.push(problem(
+                Span::default(),
+                "Migrations must have a list of `steps`.",
+                "as in `steps = [rename_speaker(\"Narator\", \"Narrator\")]`",
+            )),
+            Some(field) => match &field.value {
+                Expression::List { items, item_spans } => {
+                    for (item, span) in items.iter().zip(item_spans) {
+                        match parse_step(item, *span, None) {
+                            Ok(step) => steps.push(step),
+                            Err(err) => problems.push(err),
+                        }
+                    }
+                }
+                _ => problems.push(problem(
+                    field.value_span,
+                    "`steps` must be a list.",
+                    "expected `[...]`",
+                )),
+            },
+        }
+
+        if !problems.is_empty() {
+            return Err(invalid(file_name, source, problems));
+        }
+        Ok(Migration { steps })
+    }
+}
+
+/// Parses the steps of an `upgrade` of `model`, declared at `span` of `source`.
+pub fn parse_upgrade_steps(
+    model: &str,
+    steps: &[Expression],
+    span: Span,
+    file_name: &str,
+    source: &str,
+) -> Result<Vec<MigrationStep>, MigrateError> {
+    let mut parsed = Vec::new();
+    let mut problems = Vec::new();
+    for step in steps {
+        match parse_step(step, span, Some(model)) {
+            Ok(step) => parsed.push(step),
+            Err(err) => problems.push(err),
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(invalid(file_name, source, problems));
+    }
+    Ok(parsed)
+}
+
+/// Parses a single step. `list_span` is where the list of steps is, and `model`
+/// is the model the steps are scoped to, when parsing an upgrade.
+fn parse_step(
+    step: &Expression,
+    list_span: Span,
+    model: Option<&str>,
+) -> Result<MigrationStep, MigrationProblem> {
+    let Expression::Call {
+        name,
+        args,
+        name_span,
+    } = step
+    else {
+        return Err(problem(
+            list_span,
+            "Every step must be a call, as in `rename_call(\"a\", \"b\")`.",
+            "in this list",
+        ));
+    };
+    let span = *name_span;
+
+    let Some((_, arity)) = STEPS.iter().find(|(step, _)| step == name) else {
+        let known = STEPS.map(|(step, _)| step).join(", ");
+        return Err(problem(
+            span,
+            format!("Unknown step `{}`.", name),
+            format!("expected one of {}", known),
+        ));
+    };
+    if model.is_some() && !UPGRADE_STEPS.contains(&name.as_str()) {
+        return Err(problem(
+            span,
+            format!("`{}` can't be used to upgrade a model.", name),
+            format!("expected one of {}", UPGRADE_STEPS.join(", ")),
+        ));
+    }
+
+    // Scoped steps take every argument but the model's name.
+    let arity = arity - model.map_or(0, |_| 1);
+    if args.len() != arity {
+        return Err(problem(
+            span,
+            format!("`{}` takes {} arguments, not {}.", name, arity, args.len()),
+            "wrong number of arguments",
+        ));
+    }
+    let args = model
+        .map(|model| Expression::Text(vec![TextPart::Text(model.to_string())]))
+        .into_iter()
+        .chain(args.iter().cloned())
+        .collect::<Vec<_>>();
+
+    let text = |i: usize| {
+        plain_text(&args[i]).ok_or_else(|| {
+            problem(
+                span,
+                format!("The arguments of `{}` must be plain text.", name),
+                "expected text, as in `\"Item\"`",
+            )
+        })
+    };
+    let module_path = |i: usize| {
+        ModulePath::parse(&text(i)?).ok_or_else(|| {
+            problem(
+                span,
+                "`move_module` takes two module paths.",
+                "expected paths, as in `\"weapons.iron_sword\"`",
+            )
+        })
+    };
+
+    Ok(match name.as_str() {
+        "rename_field" => MigrationStep::RenameField {
+            model: text(0)?,
+            from: text(1)?,
+            to: text(2)?,
+        },
+        "rename_speaker" => MigrationStep::RenameSpeaker {
+            from: text(0)?,
+            to: text(1)?,
+        },
+        "move_module" => MigrationStep::MoveModule {
+            from: module_path(0)?,
+            to: module_path(1)?,
+        },
+        "set_default" => MigrationStep::SetDefault {
+            model: text(0)?,
+            field: text(1)?,
+            value: render_expression(&args[2]),
+        },
+        "rename_call" => MigrationStep::RenameCall {
+            from: text(0)?,
+            to: text(1)?,
+        },
+        "set_version" => MigrationStep::SetVersion {
+            model: text(0)?,
+            version: match args[1] {
+                Expression::Int(version) if version > 0 => version as u32,
+                _ => {
+                    return Err(problem(
+                        span,
+                        "Model versions are positive whole numbers.",
+                        "expected a version, as in `2`",
+                    ))
+                }
+            },
+        },
+        _ => unreachable!("Steps are checked against `STEPS` above."),
+    })
+}
+
+fn problem(span: Span, message: impl Into<String>, label: impl Into<String>) -> MigrationProblem {
+    MigrationProblem {
+        message: message.into(),
+        span: span.into(),
+        label: label.into(),
+    }
+}
+
+fn invalid(file_name: &str, source: &str, problems: Vec<MigrationProblem>) -> MigrateError {
+    InvalidMigration {
+        src: NamedSource::new(file_name, source.to_string()),
+        problems,
+    }
+    .into()
+}
+
+fn plain_text(expression: &Expression) -> Option<String> {
+    match expression {
+        Expression::Text(parts) => parts
+            .iter()
+            .map(|part| match part {
+                TextPart::Text(text) => Some(text.as_str()),
+                TextPart::Expression(_) => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+fn render_expression(expression: &Expression) -> String {
+    unparser::print_expression(expression)
+        .pretty(80)
+        .to_string()
+}
+
+impl std::fmt::Display for MigrationStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationStep::RenameField { model, from, to } => {
+                write!(f, "rename_field({:?}, {:?}, {:?})", model, from, to)
+            }
+            MigrationStep::RenameSpeaker { from, to } => {
+                write!(f, "rename_speaker({:?}, {:?})", from, to)
+            }
+            MigrationStep::MoveModule { from, to } => {
+                write!(f, "move_module(\"{}\", \"{}\")", from, to)
+            }
+            MigrationStep::SetDefault {
+                model,
+                field,
+                value,
+            } => write!(f, "set_default({:?}, {:?}, {})", model, field, value),
+            MigrationStep::RenameCall { from, to } => {
+                write!(f, "rename_call({:?}, {:?})", from, to)
+            }
+            MigrationStep::SetVersion { model, version } => {
+                write!(f, "set_version({:?}, {})", model, version)
+            }
+        }
+    }
+}
+
+/// What a migration does to one file of the workspace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub file: PathBuf,
+    /// Where the file is moved to, if it is.
+    pub moved_to: Option<PathBuf>,
+    pub before: String,
+    pub after: String,
+}
+
+impl FileChange {
+    /// The change as a unified diff, with paths relative to `root`.
+    pub fn diff(&self, root: &Path) -> String {
+        let relative = |path: &Path| {
+            path.strip_prefix(root)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+        let from = relative(&self.file);
+        let to = relative(self.moved_to.as_ref().unwrap_or(&self.file));
+
+        similar::TextDiff::from_lines(&self.before, &self.after)
+            .unified_diff()
+            .header(&from, &to)
+            .to_string()
+    }
+}
+
+/// A module of the workspace, as it is being migrated.
+struct SourceFile {
+    /// The package the module is in, in the order of [`Project::all_packages`].
+    package: usize,
+    original: PathBuf,
+    file: PathBuf,
+    /// The source directory the module is in.
+    source_root: PathBuf,
+    path: ModulePath,
+    before: String,
+    text: String,
+}
+
+/// A replacement of the text at `span`.
+struct Edit {
+    span: Span,
+    replacement: String,
+}
+
+/// Works out what applying `migration` to `project` changes, without changing anything.
+///
+/// Steps run in order, each one seeing the sources as the previous steps left them.
+pub fn plan(project: &Project, migration: &Migration) -> Result<Vec<FileChange>, MigrateError> {
+    let mut files = Vec::new();
+    for (package, project) in project.all_packages().into_iter().enumerate() {
+        for module in project.modules.iter() {
+            let text = std::fs::read_to_string(&module.file)?;
+            let mut source_root = module.file.clone();
+            for _ in 0..module.path.0.len() {
+                source_root.pop();
+            }
+            files.push(SourceFile {
+                package,
+                original: module.file.clone(),
+                file: module.file.clone(),
+                source_root,
+                path: module.path.clone(),
+                before: text.clone(),
+                text,
+            });
+        }
+    }
+
+    for step in migration.steps.iter() {
+        for file in files.iter_mut() {
+            let file_name = file.file.display().to_string();
+            file.text = apply_steps(&file_name, &file.text, std::slice::from_ref(step))?;
+        }
+
+        if let MigrationStep::MoveModule { from, to } = step {
+            move_modules(&mut files, from, to, step)?;
+        }
+    }
+
+    Ok(files
+        .into_iter()
+        .filter(|file| file.before != file.text || file.original != file.file)
+        .map(|file| FileChange {
+            moved_to: (file.original != file.file).then_some(file.file),
+            file: file.original,
+            before: file.before,
+            after: file.text,
+        })
+        .collect())
+}
+
+/// Applies `steps` to the module `source` alone. Steps that affect other modules,
+/// like moving them, only update the `using`s of this one.
+pub fn apply_steps(
+    file_name: &str,
+    source: &str,
+    steps: &[MigrationStep],
+) -> Result<String, MigrateError> {
+    let mut source = source.to_string();
+    for step in steps {
+        let module = parser::parse_module(file_name, &source)?;
+        let edits = edits(step, &module, &source).map_err(|reason| MigrationConflict {
+            step: step.to_string(),
+            file: PathBuf::from(file_name),
+            reason,
+        })?;
+        source = apply(&source, edits);
+    }
+    Ok(source)
+}
+
+/// Writes `changes` to disk. Every file is written next to where it goes first, and only
+/// put in place once all of them were, so a failed write leaves the workspace as it was.
+/// The originals of moved modules are removed last.
+pub fn apply_changes(changes: &[FileChange]) -> Result<(), std::io::Error> {
+    let mut written = Vec::new();
+    for change in changes {
+        let destination = change.moved_to.as_ref().unwrap_or(&change.file);
+        let partial = partial_file(destination);
+        let write = destination
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&partial, &change.after));
+        if let Err(error) = write {
+            for (partial, _) in written {
+                let _ = std::fs::remove_file(partial);
+            }
+            return Err(error);
+        }
+        written.push((partial, destination));
+    }
+
+    for (partial, destination) in written {
+        std::fs::rename(partial, destination)?;
+    }
+    for change in changes {
+        let replaced = changes
+            .iter()
+            .any(|other| other.moved_to.as_ref() == Some(&change.file));
+        if change.moved_to.is_some() && !replaced {
+            std::fs::remove_file(&change.file)?;
+        }
+    }
+    Ok(())
+}
+
+/// Where `file` is written before it's put in place, as in `sword.sol.partial`.
+fn partial_file(file: &Path) -> PathBuf {
+    let mut name = file.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    file.with_file_name(name)
+}
+
+/// Moves every module under `from` to be under `to` instead.
+fn move_modules(
+    files: &mut [SourceFile],
+    from: &ModulePath,
+    to: &ModulePath,
+    step: &MigrationStep,
+) -> Result<(), MigrateError> {
+    if !files.iter().any(|file| file.path.0.starts_with(&from.0)) {
+        Err(MigrationConflict {
+            step: step.to_string(),
+            file: PathBuf::from("."),
+            reason: format!("there is no module `{}`.", from),
+        })?;
+    }
+
+    let staying = files
+        .iter()
+        .filter(|file| !file.path.0.starts_with(&from.0))
+        .map(|file| (file.package, file.path.clone(), file.file.clone()))
+        .collect::<Vec<_>>();
+    for file in files.iter_mut() {
+        let Some(path) = moved_path(&file.path, from, to) else {
+            continue;
+        };
+        if let Some((_, _, other)) = staying
+            .iter()
+            .find(|(package, other, _)| *package == file.package && other == &path)
+        {
+            Err(MigrationConflict {
+                step: step.to_string(),
+                file: other.clone(),
+                reason: format!("module `{}` already exists.", path),
+            })?;
+        }
+
+        file.file = file.source_root.clone();
+        file.file.extend(path.0.iter());
+        file.file.set_extension("sol");
+        file.path = path;
+    }
+    Ok(())
+}
+
+/// Where `path` ends up when everything under `from` moves to `to`.
+fn moved_path(path: &ModulePath, from: &ModulePath, to: &ModulePath) -> Option<ModulePath> {
+    let rest = path.0.strip_prefix(from.0.as_slice())?;
+    Some(ModulePath(to.0.iter().chain(rest).cloned().collect()))
+}
+
+/// The text edits `step` makes to `module`, parsed from `source`.
+fn edits(step: &MigrationStep, module: &Module, source: &str) -> Result<Vec<Edit>, String> {
+    let mut edits = Vec::new();
+
+    match step {
+        MigrationStep::RenameField { model, from, to } => {
+            if is_asset_of(module, model) {
+                if let Some(field) = module.fields.get(from) {
+                    if module.fields.contains_key(to) {
+                        return Err(format!("it already has a `{}` field.", to));
+                    }
+                    edits.push(Edit {
+                        span: field.name_span,
+                        replacement: to.clone(),
+                    });
+                }
+
+                // Actions and handlers read the asset's fields as `self.durability`, or as
+                // `durability`, unless a parameter of the action is named that way.
+                let bodies = module
+                    .actions
+                    .iter()
+                    .map(|action| (&action.body, action.parameters.contains(from)))
+                    .chain(module.handlers.iter().map(|handler| (&handler.body, false)));
+                for (body, shadowed) in bodies {
+                    visit_script(body, &mut |expression| match expression {
+                        Expression::Member {
+                            object,
+                            name,
+                            args: None,
+                            name_span,
+                        } if name == from
+                            && matches!(&**object, Expression::Symbol(symbol) if symbol.path == ["self"]) =>
+                        {
+                            edits.push(Edit {
+                                span: *name_span,
+                                replacement: to.clone(),
+                            });
+                        }
+                        Expression::Symbol(symbol)
+                            if !shadowed && symbol.path == [from.as_str()] =>
+                        {
+                            // Symbols written as `::durability` keep their `::`.
+                            let prefix = &source[symbol.span.start..symbol.span.end - from.len()];
+                            edits.push(Edit {
+                                span: symbol.span,
+                                replacement: format!("{}{}", prefix, to),
+                            });
+                        }
+                        _ => {}
+                    });
+                }
+            }
+            for declaration in module.models.iter().filter(|m| &m.name == model) {
+                for field in declaration
+                    .fields
+                    .iter()
+                    .filter(|field| &field.name == from)
+                {
+                    edits.push(Edit {
+                        span: field.name_span,
+                        replacement: to.clone(),
+                    });
+                }
+            }
+        }
+        MigrationStep::RenameSpeaker { from, to } => {
+            for parts in scripts(module) {
+                visit_scene_parts(parts, &mut |part| {
+                    if let ScenePart::SpeakerChangeMarker(marker) = part {
+                        if &marker.speaker_id == from {
+                            edits.push(Edit {
+                                span: marker.speaker_span,
+                                replacement: to.clone(),
+                            });
+                        }
+                    }
+                });
+            }
+        }
+        MigrationStep::MoveModule { from, to } => {
+            // Usings grouped as in `using a.(b, c)` share a span, and are rewritten together.
+            let mut statements = BTreeMap::<usize, (Span, Vec<ModulePath>)>::new();
+            for using in module.usings.iter() {
+                statements
+                    .entry(using.span.start)
+                    .or_insert_with(|| (using.span, Vec::new()))
+                    .1
+                    .push(ModulePath(using.path.clone()));
+            }
+
+            for (span, paths) in statements.into_values() {
+                if !paths.iter().any(|path| path.0.starts_with(&from.0)) {
+                    continue;
+                }
+                let paths = paths
+                    .iter()
+                    .map(|path| moved_path(path, from, to).unwrap_or_else(|| path.clone()))
+                    .collect::<Vec<_>>();
+                edits.push(Edit {
+                    span,
+                    replacement: render_usings(&paths),
+                });
+            }
+        }
+        MigrationStep::SetDefault {
+            model,
+            field,
+            value,
+        } => {
+            if is_asset_of(module, model) && !module.fields.contains_key(field) {
+                let separator = if source.is_empty() || source.ends_with('\n') {
+                    ""
+                } else {
+                    "\n"
+                };
+                edits.push(Edit {
+                    span: Span::new(source.len(), source.len()),
+                    replacement: format!("{}{} = {}\n", separator, field, value),
+                });
+            }
+        }
+        MigrationStep::SetVersion { model, version } => {
+            if let Some(directive) = module.model.as_ref().filter(|d| &d.name == model) {
+                edits.push(Edit {
+                    span: directive.span,
+                    replacement: format!("@model {} version {}", model, version),
+                });
+            }
+        }
+        MigrationStep::RenameCall { from, to } => {
+            let mut rename = |expression: &Expression| match expression {
+                Expression::Call {
+                    name, name_span, ..
+                }
+                | Expression::Member {
+                    name,
+                    args: Some(_),
+                    name_span,
+                    ..
+                } if name == from => {
+                    edits.push(Edit {
+                        span: *name_span,
+                        replacement: to.clone(),
+                    });
+                }
+                _ => {}
+            };
+            visit_module(module, &mut rename);
+        }
+    }
+
+    Ok(edits)
+}
+
+/// The contents of every scene, action and handler of `module`.
+fn scripts(module: &Module) -> impl Iterator<Item = &Vec<ScenePart>> {
+    module
+        .scenes
+        .iter()
+        .map(|scene| &scene.content)
+        .chain(module.actions.iter().map(|action| &action.body))
+        .chain(module.handlers.iter().map(|handler| &handler.body))
+}
+
+fn is_asset_of(module: &Module, model: &str) -> bool {
+    module
+        .model
+        .as_ref()
+        .is_some_and(|directive| directive.name == model)
+}
+
+/// Renders module paths as `using` statements, grouping them when they share a parent.
+fn render_usings(paths: &[ModulePath]) -> String {
+    let parent = |path: &ModulePath| path.0[..path.0.len() - 1].to_vec();
+    let grouped = paths.len() > 1
+        && paths.iter().all(|path| path.0.len() > 1)
+        && paths.iter().all(|path| parent(path) == parent(&paths[0]));
+
+    if grouped {
+        let names = paths
+            .iter()
+            .map(|path| path.0.last().expect("Module paths aren't empty.").as_str())
+            .collect::<Vec<_>>();
+        format!(
+            "using {}.({})",
+            parent(&paths[0]).join("."),
+            names.join(", ")
+        )
+    } else {
+        paths
+            .iter()
+            .map(|path| format!("using {}", path))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Calls `visit` with every expression of the fields, scenes and actions of `module`.
+pub(super) fn visit_module(module: &Module, visit: &mut impl FnMut(&Expression)) {
+    for field in module.fields.values() {
+        visit_expressions(&field.value, visit);
+    }
+    for parts in scripts(module) {
+        visit_script(parts, visit);
+    }
+}
+
+/// Calls `visit` with every expression of `parts`, including the ones inside prompt options
+/// and conditionals.
+fn visit_script(parts: &[ScenePart], visit: &mut impl FnMut(&Expression)) {
+    visit_scene_parts(parts, &mut |part| match part {
+        ScenePart::Expression(expression) => visit_expressions(expression, visit),
+        ScenePart::Dialogue(dialogue) => visit_text(&dialogue.parts, visit),
+        ScenePart::Narration(narration) => visit_text(&narration.parts, visit),
+        ScenePart::Prompt(prompt) => {
+            visit_text(prompt.text.iter().flatten(), visit);
+            for option in prompt.options.iter() {
+                visit_text(&option.text, visit);
+                if let Some(condition) = &option.condition {
+                    visit_expressions(condition, visit);
+                }
+            }
+        }
+        ScenePart::Conditional(conditional) => visit_expressions(&conditional.condition, visit),
+        ScenePart::Assignment(assignment) => {
+            visit_expressions(&assignment.target, visit);
+            visit_expressions(&assignment.value, visit);
+        }
+        ScenePart::SpeakerChangeMarker(_) | ScenePart::Comment(_) => {}
+    });
+}
+
+/// Calls `visit` with every scene part in `parts`, including the ones inside prompt options
+/// and conditionals.
+fn visit_scene_parts(parts: &[ScenePart], visit: &mut impl FnMut(&ScenePart)) {
+    for part in parts {
+        visit(part);
+        match part {
+            ScenePart::Prompt(prompt) => {
+                for option in prompt.options.iter() {
+                    visit_scene_parts(&option.content, visit);
+                }
+            }
+            ScenePart::Conditional(conditional) => {
+                visit_scene_parts(&conditional.content, visit);
+                visit_scene_parts(&conditional.otherwise, visit);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Calls `visit` with `expression` and every expression inside it.
+pub(super) fn visit_expressions(expression: &Expression, visit: &mut impl FnMut(&Expression)) {
+    visit(expression);
+    match expression {
+        Expression::Call { args, .. } => {
+            for arg in args {
+                visit_expressions(arg, visit);
+            }
+        }
+        Expression::List { items, .. } => {
+            for item in items {
+                visit_expressions(item, visit);
+            }
+        }
+        Expression::Member { object, args, .. } => {
+            visit_expressions(object, visit);
+            for arg in args.iter().flatten() {
+                visit_expressions(arg, visit);
+            }
+        }
+        Expression::Unit(expression, _) => visit_expressions(expression, visit),
+        Expression::Binary { left, right, .. } => {
+            visit_expressions(left, visit);
+            visit_expressions(right, visit);
+        }
+        Expression::Text(parts) => visit_text(parts, visit),
+        Expression::Int(_)
+        | Expression::Float(_)
+        | Expression::Boolean(_)
+        | Expression::Symbol(_) => {}
+    }
+}
+
+fn visit_text<'a>(
+    parts: impl IntoIterator<Item = &'a TextPart>,
+    visit: &mut impl FnMut(&Expression),
+) {
+    for part in parts {
+        if let TextPart::Expression(expression) = part {
+            visit_expressions(expression, visit);
+        }
+    }
+}
+
+/// Applies non-overlapping `edits` to `source`.
+fn apply(source: &str, mut edits: Vec<Edit>) -> String {
+    edits.sort_by_key(|edit| edit.span.start);
+    let mut result = String::with_capacity(source.len());
+    let mut position = 0;
+    for edit in edits {
+        result.push_str(&source[position..edit.span.start]);
+        result.push_str(&edit.replacement);
+        position = edit.span.end;
+    }
+    result.push_str(&source[position..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_changes, apply_steps, plan, MigrateError, Migration, MigrationStep};
+    use crate::{introspec::project::Project, introspec::types, parser, workspace::ParsedModule};
+    use std::fs;
+    use tempfile::tempdir;
+
+    const MIGRATION: &str = r#"-- Swords are weapons now, and they get used up.
+steps = [
+    rename_field("Item", "durability", "uses"),
+    set_default("Item", "rarity", "common"),
+    rename_speaker("Narator", "Narrator"),
+    move_module("items.sword", "weapons.sword"),
+    rename_call("give", "give_item"),
+    set_version("Item", 2),
+]
+"#;
+
+    #[test]
+    fn parses_steps() {
+        let migration = Migration::parse("migration.sol", MIGRATION).unwrap();
+        assert_eq!(migration.steps.len(), 6);
+        assert_eq!(
+            migration.steps[1].to_string(),
+            "set_default(\"Item\", \"rarity\", \"common\")"
+        );
+
+        let Err(MigrateError::Invalid(invalid)) = Migration::parse(
+            "migration.sol",
+            "steps = [rename_field(\"Item\"), explode(\"a\")]",
+        ) else {
+            panic!("The migration should be invalid.");
+        };
+        assert_eq!(invalid.problems.len(), 2);
+    }
+
+    #[test]
+    fn renames_fields_in_actions() {
+        let rename = MigrationStep::RenameField {
+            model: "Item".to_string(),
+            from: "durability".to_string(),
+            to: "uses".to_string(),
+        };
+        let migrate = |file_name: &str, source: &str| {
+            let source = apply_steps(file_name, source, std::slice::from_ref(&rename)).unwrap();
+            ParsedModule {
+                file_name: file_name.to_string(),
+                ast: parser::parse_module(file_name, &source).unwrap(),
+                source,
+            }
+        };
+        let item = migrate("Item.sol", include_str!("../../examples/simple/Item.sol"));
+        let character = migrate(
+            "Character.sol",
+            include_str!("../../examples/simple/Character.sol"),
+        );
+        let sword = migrate(
+            "wooden_sword.sol",
+            include_str!("../../examples/simple/wooden_sword.sol"),
+        );
+        assert!(sword.source.contains("uses = 20"));
+        assert!(sword.source.contains("self.uses -= 1"));
+        assert!(sword.source.contains("if self.uses <= 0 then"));
+        assert!(!sword.source.contains("durability"));
+        types::check(&sword, &[&item, &character]).unwrap();
+
+        let source = "@model Item\n\naction repair(durability)\n    self.durability = durability\n    ::durability += 1\nend\n";
+        assert_eq!(
+            migrate("sword.sol", source).source,
+            "@model Item\n\naction repair(durability)\n    self.uses = durability\n    ::durability += 1\nend\n"
+        );
+        let source = "@model Item\n\non Item.destroyed\n    ::durability = 0\nend\n";
+        assert_eq!(
+            migrate("sword.sol", source).source,
+            "@model Item\n\non Item.destroyed\n    ::uses = 0\nend\n"
+        );
+    }
+
+    #[test]
+    fn renames_member_calls() {
+        let rename = MigrationStep::RenameCall {
+            from: "damage".to_string(),
+            to: "hurt".to_string(),
+        };
+        let sword = apply_steps(
+            "wooden_sword.sol",
+            include_str!("../../examples/simple/wooden_sword.sol"),
+            &[rename],
+        )
+        .unwrap();
+        assert!(sword.contains("        target.hurt(attack_damage)\n"));
+        assert!(sword.contains("attack_damage = 20"));
+    }
+
+    #[test]
+    fn migrates_workspace_keeping_formatting() {
+        let temp = tempdir().unwrap();
+        let root = temp.path();
+        fs::write(
+            root.join("index.sol"),
+            "name = \"game\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("src/items")).unwrap();
+        fs::write(
+            root.join("src/items/item.sol"),
+            "model Item\n    name: Text\n    -- How many hits it takes.\n    durability: dynamic Nat\nend\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/items/sword.sol"),
+            "@model Item\n\nname = \"Sword\"   -- Sharp!\ndurability = 20",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/main.sol"),
+            "using items.(item, sword)\n\nscene main\n    [Narator]\n    - Take this.\n    give(::sword)\nend\n",
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("src/dlc")).unwrap();
+        fs::write(
+            root.join("src/dlc/index.sol"),
+            "name = \"dlc\"\nversion = \"0.1.0\"\nsources = [\"\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/dlc/boss.sol"),
+            "scene fight\n    [Narator]\n    - Run!\nend\n",
+        )
+        .unwrap();
+
+        let project = Project::load_from_directory(root.to_path_buf()).unwrap();
+        let migration = Migration::parse("migration.sol", MIGRATION).unwrap();
+        let changes = plan(&project, &migration).unwrap();
+        assert_eq!(changes.len(), 4);
+        assert!(changes[2].diff(root).contains("+    [Narrator]"));
+
+        apply_changes(&changes).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.join("src/items/item.sol")).unwrap(),
+            "model Item\n    name: Text\n    -- How many hits it takes.\n    uses: dynamic Nat\nend\n"
+        );
+        assert!(!root.join("src/items/sword.sol").exists());
+        assert_eq!(
+            fs::read_to_string(root.join("src/weapons/sword.sol")).unwrap(),
+            "@model Item version 2\n\nname = \"Sword\"   -- Sharp!\nuses = 20\nrarity = \"common\"\n"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("src/main.sol")).unwrap(),
+            "using items.item\nusing weapons.sword\n\nscene main\n    [Narrator]\n    - Take this.\n    give_item(::sword)\nend\n"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("src/dlc/boss.sol")).unwrap(),
+            "scene fight\n    [Narrator]\n    - Run!\nend\n"
+        );
+        assert!(!root.join("src/weapons/sword.sol.partial").exists());
+
+        // Running it again has nothing left to move.
+        let project = Project::load_from_directory(root.to_path_buf()).unwrap();
+        assert!(matches!(
+            plan(&project, &migration),
+            Err(MigrateError::Conflict(_))
+        ));
+    }
+}
@@ -1,19 +1,62 @@
+use crate::{
+    introspec::{
+        lower::{self, LoweringError},
+        project::{ModulePath, Project, ProjectLoadError},
+        types::{self, TypeError},
+    },
+    parser::{self, ast::Module, ParseError},
+    runtime::{signal::Signals, state::State},
+};
 use clap::{Args, Subcommand};
-use miette::Diagnostic;
-use std::{fs::File, path::PathBuf};
+use dependency::{DependencyError, Update};
+use host::HostError;
+use manifest::{ManifestError, WorkspaceManifest};
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use resolve::ResolveError;
+use std::path::{Path, PathBuf};
+use template::Template;
 use thiserror::Error;
+use upgrade::UpgradeError;
+
+pub mod dependency;
+pub mod git;
+pub mod host;
+pub mod lockfile;
+pub mod manifest;
+pub mod migrate;
+pub mod play;
+pub mod registry;
+pub mod resolve;
+pub mod template;
+pub mod test;
+pub mod upgrade;
+pub mod vendor;
 
 #[derive(Error, Debug, Diagnostic)]
 #[error(transparent)]
 pub enum WorkspaceError {
+    #[diagnostic(transparent)]
     Init(#[from] InitError),
+    #[diagnostic(transparent)]
     Build(#[from] BuildError),
+    #[diagnostic(transparent)]
+    Dependency(#[from] DependencyError),
+    #[diagnostic(transparent)]
+    Migrate(#[from] migrate::MigrateError),
 }
 
 #[derive(Debug, Args)]
 pub struct InitArgs {
     // If unspecified, use CWD.
     pub path: Option<PathBuf>,
+
+    /// What the new workspace starts out with.
+    #[arg(short, long, value_enum, default_value_t)]
+    pub template: Template,
+
+    /// Don't ask for confirmation. Useful for scripts and CI.
+    #[arg(short, long)]
+    pub yes: bool,
 }
 
 #[derive(Error, Debug, Diagnostic)]
@@ -36,9 +79,9 @@ pub struct WorkspaceAlreadyExists;
 #[error("Canceled.")]
 pub struct ActionCanceled;
 
-pub const SOL_MANIFEST_FILENAME: &'static str = "index.sol";
+pub const SOL_MANIFEST_FILENAME: &str = "index.sol";
 
-pub fn init(path: PathBuf) -> Result<(), InitError> {
+pub fn init(path: PathBuf, template: Template, yes: bool) -> Result<(), InitError> {
     let manifest_path = path.join(SOL_MANIFEST_FILENAME);
 
     if manifest_path.exists() {
@@ -48,19 +91,85 @@ pub fn init(path: PathBuf) -> Result<(), InitError> {
     let mut dir = std::fs::read_dir(path.clone())?;
 
     let prompt = format!("Initialize workspace at {:?}?", path.display());
-    if !confirm(prompt)? {
+    if !yes && !confirm(prompt)? {
         Err(ActionCanceled)?;
     }
 
-    let manifest = File::create_new(manifest_path)?;
+    let manifest = template.manifest(package_name_for(&path));
+    std::fs::write(manifest_path, manifest.to_source())?;
+
+    for (file, contents) in template.files() {
+        let file = path.join(file);
+        if contents.is_empty() {
+            std::fs::create_dir_all(file)?;
+        } else if !file.exists() {
+            std::fs::create_dir_all(file.parent().expect("Template files are in the workspace."))?;
+            std::fs::write(file, contents)?;
+        }
+    }
+
+    ignore_generated_files(&path, &manifest)?;
 
     Ok(())
 }
 
+/// Adds the build directory and the dependency cache to the
+/// workspace's `.gitignore`, creating it if needed.
+fn ignore_generated_files(
+    path: &std::path::Path,
+    manifest: &WorkspaceManifest,
+) -> Result<(), std::io::Error> {
+    let gitignore_path = path.join(".gitignore");
+    let mut gitignore = std::fs::read_to_string(&gitignore_path).unwrap_or_default();
+
+    for entry in [
+        format!("/{}/", manifest.output.display()),
+        format!("/{}/", git::SOL_CACHE_DIRECTORY),
+    ] {
+        if gitignore.lines().any(|line| line.trim() == entry) {
+            continue;
+        }
+        if !gitignore.is_empty() && !gitignore.ends_with('\n') {
+            gitignore.push('\n');
+        }
+        gitignore.push_str(&entry);
+        gitignore.push('\n');
+    }
+    std::fs::write(gitignore_path, gitignore)
+}
+
+/// Derives a valid package name from a workspace directory's name.
+fn package_name_for(path: &std::path::Path) -> String {
+    let name = path
+        .canonicalize()
+        .ok()
+        .and_then(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_default()
+        .chars()
+        .map(|char| {
+            if char.is_ascii_alphanumeric() {
+                char.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    if manifest::is_package_name(&name) {
+        name
+    } else {
+        format!("_{}", name)
+    }
+}
+
 #[derive(Debug, Args)]
 pub struct BuildArgs {
     // Override of the workspace path to be built.
-    // Defaults to the CWD.
+    // Defaults to the CWD. The nearest `index.sol` from
+    // there (or any parent directory) is used.
     pub path: Option<PathBuf>,
 
     /// Watches over the workspace and rebuilds assets as they change.
@@ -79,10 +188,230 @@ pub struct BuildArgs {
 #[error(transparent)]
 pub enum BuildError {
     IO(#[from] std::io::Error),
+    #[diagnostic(transparent)]
+    Project(#[from] ProjectLoadError),
+    #[diagnostic(transparent)]
+    Parse(#[from] ParseError),
+    #[diagnostic(transparent)]
+    Dependency(#[from] DependencyError),
+    #[diagnostic(transparent)]
+    UnresolvedImport(#[from] Box<UnresolvedImport>),
+    #[diagnostic(transparent)]
+    Resolve(#[from] ResolveError),
+    #[diagnostic(transparent)]
+    Host(#[from] HostError),
+    #[diagnostic(transparent)]
+    Upgrade(#[from] Box<UpgradeError>),
+    #[diagnostic(transparent)]
+    Type(#[from] TypeError),
+    #[diagnostic(transparent)]
+    Lowering(#[from] LoweringError),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("Can't find `{path}` to import.")]
+#[diagnostic(
+    code(sol::unresolved_import),
+    help("Imports are modules of this workspace, or start with the name of a dependency: {available}.")
+)]
+pub struct UnresolvedImport {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("not a module or package")]
+    pub span: SourceSpan,
+    pub path: String,
+    pub available: String,
+}
+
+/// A module, along with the source it was parsed from, for diagnostics.
+#[derive(Debug, Clone)]
+pub struct ParsedModule {
+    pub file_name: String,
+    pub source: String,
+    pub ast: Module,
+}
+
+impl ParsedModule {
+    pub fn load(file: &Path) -> Result<Self, BuildError> {
+        let file_name = file.display().to_string();
+        let source = std::fs::read_to_string(file)?;
+        Ok(ParsedModule {
+            ast: parser::parse_module(&file_name, &source)?,
+            file_name,
+            source,
+        })
+    }
+
+    pub fn named_source(&self) -> NamedSource<String> {
+        NamedSource::new(&self.file_name, self.source.clone())
+    }
+}
+
+/// A package's modules, parsed, along with the modules of its dependencies.
+pub struct LoadedPackage<'p> {
+    pub project: &'p Project,
+    pub dependencies: Vec<dependency::ResolvedDependency>,
+    pub modules: Vec<ParsedModule>,
+    pub dependency_modules: Vec<ParsedModule>,
+}
+
+impl<'p> LoadedPackage<'p> {
+    /// Resolves the dependencies of `project` and parses every module.
+    pub fn load(project: &'p Project) -> Result<Self, BuildError> {
+        let dependencies = dependency::resolve_and_lock(
+            &project.index_directory,
+            &project.manifest,
+            &Update::Nothing,
+        )?;
+        let modules = project
+            .modules
+            .iter()
+            .map(|module| ParsedModule::load(&module.file))
+            .collect::<Result<Vec<_>, _>>()?;
+        let dependency_modules = dependencies
+            .iter()
+            .flat_map(|dependency| dependency.project.modules.iter())
+            .map(|module| ParsedModule::load(&module.file))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(LoadedPackage {
+            project,
+            dependencies,
+            modules,
+            dependency_modules,
+        })
+    }
+
+    /// Modules declaring models, which can be in this package or in any of its dependencies.
+    pub fn models(&self) -> Vec<&ParsedModule> {
+        self.modules
+            .iter()
+            .chain(self.dependency_modules.iter())
+            .filter(|module| !module.ast.models.is_empty())
+            .collect()
+    }
+
+    /// Every module, along with the path it's referred to by. The modules of
+    /// dependencies come first, with the name of their package in front.
+    pub fn addressable(&self) -> Vec<(ModulePath, &ParsedModule)> {
+        let dependency_paths = self.dependencies.iter().flat_map(|dependency| {
+            dependency.project.modules.iter().map(|module| {
+                let mut path = vec![dependency.project.manifest.name.clone()];
+                path.extend(module.path.0.iter().cloned());
+                ModulePath(path)
+            })
+        });
+        let paths = self
+            .project
+            .modules
+            .iter()
+            .map(|module| module.path.clone());
+        dependency_paths
+            .zip(self.dependency_modules.iter())
+            .chain(paths.zip(self.modules.iter()))
+            .collect()
+    }
+
+    /// The state a game starts out in: the dynamic fields of every module,
+    /// with their values from the build.
+    pub fn state(&self) -> Result<State, BuildError> {
+        let models = self.models();
+        let mut state = State::new();
+        for (path, module) in self.addressable() {
+            let module = if module.ast.model.is_some() {
+                upgrade::load_asset(module, &models).map_err(Box::new)?
+            } else {
+                module.clone()
+            };
+            state.load(&path, &lower::lower(&module, &models)?);
+        }
+        Ok(state)
+    }
+
+    /// The signals of every module, and what handles them.
+    pub fn signals(&self) -> Signals<'_> {
+        let mut signals = Signals::new();
+        for (path, module) in self.addressable() {
+            signals.add_module(&path, &module.ast);
+        }
+        signals
+    }
 }
 
 pub fn build(path: PathBuf) -> Result<(), BuildError> {
-    let mut dir = std::fs::read_dir(path)?;
+    let project = Project::discover(&path)?;
+
+    for package in project.all_packages() {
+        let loaded = LoadedPackage::load(package)?;
+        let dependencies = loaded
+            .dependencies
+            .iter()
+            .map(|dependency| &dependency.project)
+            .collect::<Vec<_>>();
+        let models = loaded.models();
+        let addressable = loaded.addressable();
+        let host = package
+            .manifest
+            .host
+            .as_ref()
+            .map(|host| ParsedModule::load(&package.index_directory.join(host)))
+            .transpose()?;
+
+        for (declared, module) in package.modules.iter().zip(loaded.modules.iter()) {
+            let parsed = &module.ast;
+            for using in parsed.usings.iter() {
+                if package.resolve_import(&dependencies, &using.path).is_none() {
+                    let available = dependencies
+                        .iter()
+                        .copied()
+                        .chain(package.packages.iter())
+                        .map(|package| format!("`{}`", package.manifest.name))
+                        .collect::<Vec<_>>();
+                    return Err(Box::new(UnresolvedImport {
+                        src: module.named_source(),
+                        span: using.span.into(),
+                        path: using.path.join("."),
+                        available: if available.is_empty() {
+                            "there are none".to_string()
+                        } else {
+                            available.join(", ")
+                        },
+                    })
+                    .into());
+                }
+            }
+
+            resolve::check(&declared.path, module, &addressable)?;
+            host::check(module, &addressable, host.as_ref())?;
+
+            let module = if parsed.model.is_some() {
+                upgrade::load_asset(module, &models).map_err(Box::new)?
+            } else {
+                module.clone()
+            };
+            types::check(&module, &models)?;
+            lower::lower(&module, &models)?;
+        }
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{build, init, template::Template};
+    use clap::ValueEnum;
+    use tempfile::tempdir;
+
+    #[test]
+    fn templates_build() {
+        for template in Template::value_variants() {
+            let temp = tempdir().unwrap();
+            init(temp.path().to_path_buf(), *template, true).unwrap();
+            build(temp.path().to_path_buf()).unwrap();
+
+            let gitignore = std::fs::read_to_string(temp.path().join(".gitignore")).unwrap();
+            assert_eq!(gitignore, "/build/\n/.sol/\n");
+        }
+    }
+}
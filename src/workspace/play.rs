@@ -0,0 +1,438 @@
+//! Playing scenes in the terminal, to review their dialogue without launching the game.
+//!
+//! Lines are shown with their speakers, and a little markup: `*bold*`, `_italic_`,
+//! and interpolations, as in `{player_name()}`, stand out in colour. Calls are for the
+//! game to handle, so they're only printed, unless `--wait` makes `pause(seconds)` wait.
+//! The game's variables start out as the build leaves them.
+//!
+//! Sessions are recorded with `--record session.json`, for playtesters to attach to
+//! bug reports, and reproduced with `--replay session.json`, which tells where the
+//! scene now plays differently.
+use super::{BuildError, LoadedPackage, ParsedModule};
+use crate::{
+    introspec::project::{ModulePath, Project},
+    parser::ast::Expression,
+    parser::ast::Scene,
+    runtime::{
+        replay::{self, Divergence, Playthrough, UnsupportedPlaythrough},
+        Event, RunError, SceneRunner,
+    },
+};
+use clap::Args;
+use miette::Diagnostic;
+use pretty::termcolor::{Color, ColorSpec, WriteColor};
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use thiserror::Error;
+
+#[derive(Debug, Args)]
+pub struct PlayArgs {
+    /// The module the scene is in, as in `dialogue.intro`. Replays default to the recorded one.
+    #[arg(required_unless_present = "replay")]
+    pub module: Option<String>,
+
+    /// The scene to play, as in `main`. Replays default to the recorded one.
+    #[arg(required_unless_present = "replay")]
+    pub scene: Option<String>,
+
+    /// Actually waits on calls to `pause(seconds)`, instead of only printing them.
+    #[arg(short, long)]
+    pub wait: bool,
+
+    /// Records the session into a file, to replay it later.
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Replays a recorded session, telling where the scene now plays differently.
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error(transparent)]
+pub enum PlayError {
+    IO(#[from] io::Error),
+    #[diagnostic(transparent)]
+    Build(#[from] BuildError),
+    #[diagnostic(transparent)]
+    Run(#[from] RunError),
+    #[diagnostic(transparent)]
+    UnknownModule(#[from] UnknownModule),
+    #[diagnostic(transparent)]
+    UnknownScene(#[from] UnknownScene),
+    #[diagnostic(transparent)]
+    InvalidPlaythrough(#[from] InvalidPlaythrough),
+    #[diagnostic(transparent)]
+    UnsupportedPlaythrough(#[from] UnsupportedPlaythrough),
+    #[diagnostic(transparent)]
+    Diverged(#[from] Divergence),
+    #[diagnostic(transparent)]
+    InvalidPause(#[from] InvalidPause),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("There's no module `{module}` in this workspace.")]
+#[diagnostic(
+    code(sol::play::unknown_module),
+    help("Modules are named by their path in a source directory, as in `dialogue.intro` for `src/dialogue/intro.sol`.")
+)]
+pub struct UnknownModule {
+    pub module: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{module}` has no scene called `{scene}`.")]
+#[diagnostic(code(sol::play::unknown_scene), help("Its scenes are {available}."))]
+pub struct UnknownScene {
+    pub module: String,
+    pub scene: String,
+    pub available: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{}` isn't a recorded session: {message}", path.display())]
+#[diagnostic(
+    code(sol::play::invalid_playthrough),
+    help("Sessions are recorded with `sol play --record <FILE>`.")
+)]
+pub struct InvalidPlaythrough {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("The scene can't pause for {seconds} seconds: {message}")]
+#[diagnostic(
+    code(sol::play::invalid_pause),
+    help("Pauses are a number of seconds, as in `pause(0.5)`.")
+)]
+pub struct InvalidPause {
+    pub seconds: f32,
+    pub message: String,
+}
+
+/// The colours speakers are told apart by.
+const SPEAKER_COLORS: [Color; 5] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Green,
+    Color::Blue,
+];
+
+/// Plays the scene `args` names, in the workspace at `path`, writing it to `out`
+/// and asking the player for options with `asky`.
+pub fn play(path: &Path, args: &PlayArgs, out: &mut impl WriteColor) -> Result<(), PlayError> {
+    let playthrough = args
+        .replay
+        .as_ref()
+        .map(|replay| {
+            let invalid = |message: String| InvalidPlaythrough {
+                path: replay.clone(),
+                message,
+            };
+            let contents =
+                std::fs::read_to_string(replay).map_err(|error| invalid(error.to_string()))?;
+            serde_json::from_str::<Playthrough>(&contents)
+                .map_err(|error| invalid(error.to_string()))
+        })
+        .transpose()?;
+    let module_name = match (&args.module, &playthrough) {
+        (Some(module), _) => module.clone(),
+        (None, Some(playthrough)) => playthrough.module.join("."),
+        (None, None) => unreachable!("The module is required, unless replaying."),
+    };
+    let scene_name = match (&args.scene, &playthrough) {
+        (Some(scene), _) => scene.clone(),
+        (None, Some(playthrough)) => playthrough.scene.clone(),
+        (None, None) => unreachable!("The scene is required, unless replaying."),
+    };
+
+    let project = Project::discover(path).map_err(BuildError::from)?;
+    let unknown = || UnknownModule {
+        module: module_name.clone(),
+    };
+    let module_path = ModulePath::parse(&module_name).ok_or_else(unknown)?;
+    let (package, declared) = project
+        .all_packages()
+        .into_iter()
+        .find_map(|package| Some((package, package.module(&module_path)?)))
+        .ok_or_else(unknown)?;
+    let module = ParsedModule::load(&declared.file)?;
+    let scene = find_scene(&module, &module_name, &scene_name)?;
+
+    let loaded = LoadedPackage::load(package)?;
+    let signals = loaded.signals();
+    let mut runner = SceneRunner::new(scene)
+        .with_state(&module_path, loaded.state()?)
+        .with_signals(&signals);
+    if let Some(playthrough) = playthrough {
+        let replayed = replay::replay(runner, &playthrough)?;
+        for line in replayed.transcript.iter() {
+            writeln!(out, "{}", line)?;
+        }
+        if let Some(divergence) = replayed.divergence {
+            return Err(divergence.into());
+        }
+        return Ok(());
+    }
+
+    if args.record.is_some() {
+        runner = runner.recording();
+    }
+    play_scene(&mut runner, out, args.wait, |_, options| {
+        let options = options
+            .iter()
+            .enumerate()
+            .map(|(index, option)| asky::SelectOption::new(index).title(option))
+            .collect();
+        asky::Select::new_complex("Choose an option", options).prompt()
+    })?;
+    if let (Some(record), Some(playthrough)) = (&args.record, runner.playthrough()) {
+        let contents = serde_json::to_string_pretty(&playthrough).map_err(io::Error::other)?;
+        std::fs::write(record, contents + "\n")?;
+    }
+    Ok(())
+}
+
+fn find_scene<'a>(
+    module: &'a ParsedModule,
+    module_name: &str,
+    scene_name: &str,
+) -> Result<&'a Scene, UnknownScene> {
+    module
+        .ast
+        .scenes
+        .iter()
+        .find(|scene| scene.name == scene_name)
+        .ok_or_else(|| {
+            let available = module
+                .ast
+                .scenes
+                .iter()
+                .map(|scene| format!("`{}`", scene.name))
+                .collect::<Vec<_>>();
+            UnknownScene {
+                module: module_name.to_string(),
+                scene: scene_name.to_string(),
+                available: if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.join(", ")
+                },
+            }
+        })
+}
+
+/// Plays the scene of `runner`, which has no host, to the end, writing it to `out`. `choose`
+/// is given the text and the options of each prompt, and returns the index of the one the
+/// player picked.
+fn play_scene(
+    runner: &mut SceneRunner,
+    out: &mut impl WriteColor,
+    wait: bool,
+    mut choose: impl FnMut(Option<&str>, &[String]) -> io::Result<usize>,
+) -> Result<(), PlayError> {
+    loop {
+        match runner.next()? {
+            Event::SpeakerChanged { .. } => {}
+            Event::Dialogue { speaker, text } => {
+                if let Some(speaker) = speaker {
+                    let color = speaker_color(&speaker);
+                    out.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(true))?;
+                    write!(out, "{}", speaker)?;
+                    out.reset()?;
+                    let modifiers = runner
+                        .speaker()
+                        .map(|speaker| speaker.modifiers.join(" "))
+                        .unwrap_or_default();
+                    if !modifiers.is_empty() {
+                        out.set_color(ColorSpec::new().set_dimmed(true))?;
+                        write!(out, " ({})", modifiers)?;
+                        out.reset()?;
+                    }
+                    write!(out, ": ")?;
+                }
+                write_markup(out, &text, &ColorSpec::new())?;
+                writeln!(out)?;
+            }
+            Event::Narration { text } => {
+                write_markup(out, &text, ColorSpec::new().set_italic(true))?;
+                writeln!(out)?;
+            }
+            Event::Prompt { text, options } => {
+                if let Some(text) = &text {
+                    out.set_color(ColorSpec::new().set_bold(true))?;
+                    writeln!(out, "{}", text)?;
+                    out.reset()?;
+                }
+                out.flush()?;
+                let index = choose(text.as_deref(), &options)?;
+                runner
+                    .choose(index)
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+                out.set_color(ColorSpec::new().set_dimmed(true))?;
+                writeln!(out, "> {}", options[index])?;
+                out.reset()?;
+            }
+            Event::Call { name, args } => {
+                let call = Expression::Call {
+                    name: name.to_string(),
+                    args: args.to_vec(),
+                    name_span: Default::default(),
+                };
+                out.set_color(ColorSpec::new().set_dimmed(true))?;
+                writeln!(
+                    out,
+                    "[{}]",
+                    crate::unparser::print_expression(&call).pretty(usize::MAX)
+                )?;
+                out.reset()?;
+                if let ("pause", [Expression::Float(_) | Expression::Int(_)]) = (name, args) {
+                    let seconds = match args[0] {
+                        Expression::Float(seconds) => seconds,
+                        Expression::Int(seconds) => seconds as f32,
+                        _ => unreachable!("Only numbers are waited on."),
+                    };
+                    if wait && seconds > 0.0 {
+                        out.flush()?;
+                        let duration =
+                            Duration::try_from_secs_f32(seconds).map_err(|error| InvalidPause {
+                                seconds,
+                                message: error.to_string(),
+                            })?;
+                        std::thread::sleep(duration);
+                    }
+                }
+            }
+            Event::Suspended { .. } => {
+                unreachable!("Scenes played without a host don't wait on it.")
+            }
+            Event::End => {
+                out.set_color(ColorSpec::new().set_dimmed(true))?;
+                writeln!(out, "(end)")?;
+                out.reset()?;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Every speaker keeps the same colour, picked by their name.
+fn speaker_color(speaker: &str) -> Color {
+    let hash = speaker.bytes().map(usize::from).sum::<usize>();
+    SPEAKER_COLORS[hash % SPEAKER_COLORS.len()]
+}
+
+/// Writes a line in the `base` style, making `*bold*` text bold, `_italic_` text italic,
+/// and interpolations, as in `{player_name()}`, coloured.
+///
+/// Markers only count at the edges of words, so `snake_case` is left as it is.
+fn write_markup(out: &mut impl WriteColor, text: &str, base: &ColorSpec) -> io::Result<()> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let (mut bold, mut italic, mut interpolating) = (false, false, false);
+    let style = |out: &mut dyn WriteColor, bold, italic, interpolating| {
+        let mut spec = base.clone();
+        if bold {
+            spec.set_bold(true);
+        }
+        if italic {
+            spec.set_italic(true);
+        }
+        if interpolating {
+            spec.set_fg(Some(Color::Cyan));
+        }
+        out.reset()?;
+        out.set_color(&spec)
+    };
+
+    style(out, bold, italic, interpolating)?;
+    for (index, char) in chars.iter().enumerate() {
+        let before = index.checked_sub(1).map(|index| chars[index]);
+        let after = chars.get(index + 1).copied();
+        let word = |char: Option<char>| char.is_some_and(|char| char.is_alphanumeric());
+        let opens = !word(before) && after.is_some_and(|char| !char.is_whitespace());
+        let closes = before.is_some_and(|char| !char.is_whitespace()) && !word(after);
+
+        match char {
+            '*' if !interpolating && ((bold && closes) || (!bold && opens)) => {
+                bold = !bold;
+                style(out, bold, italic, interpolating)?;
+            }
+            '_' if !interpolating && ((italic && closes) || (!italic && opens)) => {
+                italic = !italic;
+                style(out, bold, italic, interpolating)?;
+            }
+            '{' if !interpolating => {
+                interpolating = true;
+                style(out, bold, italic, interpolating)?;
+                write!(out, "{{")?;
+            }
+            '}' if interpolating => {
+                write!(out, "}}")?;
+                interpolating = false;
+                style(out, bold, italic, interpolating)?;
+            }
+            char => write!(out, "{}", char)?,
+        }
+    }
+    out.reset()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{play_scene, PlayError};
+    use crate::{parser, runtime::SceneRunner};
+    use pretty::termcolor::Buffer;
+
+    #[test]
+    fn plays_scenes_in_the_terminal() {
+        let module = parser::parse_module(
+            "intro.sol",
+            "scene main\n    [Echo happy]\n    - Hello, *you*! Welcome to snake_case.\n    pause(0.5)\n    prompt Where to?\n        option Outside\n            * You _step_ outside.\n        end\n        option Nowhere\n        end\n    end\nend\n",
+        )
+        .unwrap();
+
+        let mut out = Buffer::no_color();
+        let mut prompts = Vec::new();
+        play_scene(
+            &mut SceneRunner::new(&module.scenes[0]),
+            &mut out,
+            false,
+            |text, options| {
+                prompts.push((text.map(str::to_string), options.to_vec()));
+                Ok(0)
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out.into_inner()).unwrap(),
+            "Echo (happy): Hello, you! Welcome to snake_case.\n[pause(0.5)]\nWhere to?\n> Outside\nYou step outside.\n(end)\n"
+        );
+        assert_eq!(
+            prompts,
+            vec![(
+                Some("Where to?".to_string()),
+                vec!["Outside".to_string(), "Nowhere".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn tells_of_invalid_pauses() {
+        let module =
+            parser::parse_module("intro.sol", "scene main\n    pause(1e40)\nend\n").unwrap();
+        let mut out = Buffer::no_color();
+        let result = play_scene(
+            &mut SceneRunner::new(&module.scenes[0]),
+            &mut out,
+            true,
+            |_, _| Ok(0),
+        );
+        assert!(matches!(result, Err(PlayError::InvalidPause(_))));
+    }
+}
@@ -0,0 +1,367 @@
+//! Versioned packages, and picking versions of them that work together.
+//!
+//! For now, the registry is a directory on disk (the "index") laid out as
+//! `<index>/<package name>/<version>/`, where each version is a workspace.
+use super::{
+    dependency::{DependencyError, DependencySpec},
+    manifest::WorkspaceManifest,
+    SOL_MANIFEST_FILENAME,
+};
+use miette::Diagnostic;
+use semver::{Version, VersionReq};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::PathBuf,
+};
+use thiserror::Error;
+
+/// A package index on disk.
+pub struct Registry {
+    pub directory: PathBuf,
+}
+
+/// One package asking for a version of another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Requirement {
+    /// Who asks for it, as in `game` or `weapons 1.2.0`.
+    pub requirer: String,
+    pub name: String,
+    pub requirement: VersionReq,
+}
+
+impl Display for Requirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` requires `{}` {}",
+            self.requirer, self.name, self.requirement
+        )
+    }
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("No version of `{name}` satisfies every requirement.")]
+#[diagnostic(code(sol::version_conflict), help("{explanation}"))]
+pub struct VersionConflict {
+    pub name: String,
+    pub requirements: Vec<Requirement>,
+    pub available: Vec<Version>,
+    pub explanation: String,
+}
+
+impl VersionConflict {
+    fn new(name: &str, requirements: Vec<Requirement>, available: Vec<Version>) -> Self {
+        let mut explanation = requirements
+            .iter()
+            .map(|requirement| format!("{}\n", requirement))
+            .collect::<String>();
+        if available.is_empty() {
+            explanation.push_str(&format!("but the index has no versions of `{}`.", name));
+        } else {
+            let available = available
+                .iter()
+                .map(Version::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            explanation.push_str(&format!("but the index only has {}.", available));
+        }
+
+        Self {
+            name: name.to_string(),
+            requirements,
+            available,
+            explanation,
+        }
+    }
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{package}` depends on `{spec}`, but registry packages can only depend on other registry packages.")]
+pub struct InvalidRegistryDependency {
+    pub package: String,
+    pub spec: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{spec}` needs a registry, but this workspace doesn't have one.")]
+#[diagnostic(help("Point to a package index in `index.sol`, as in `registry = \"../packages\"`."))]
+pub struct NoRegistry {
+    pub spec: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("The index has `{name}` {version} at {directory:?}, but its manifest says `{found}`.")]
+pub struct MislabeledPackage {
+    pub name: String,
+    pub version: Version,
+    pub directory: PathBuf,
+    pub found: String,
+}
+
+impl Registry {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// Every published version of `name`, from oldest to newest.
+    pub fn versions(&self, name: &str) -> Result<Vec<Version>, std::io::Error> {
+        let directory = self.directory.join(name);
+        if !directory.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut versions = std::fs::read_dir(directory)?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                Version::parse(&entry.file_name().to_string_lossy()).ok()
+            })
+            .collect::<Vec<_>>();
+        versions.sort();
+        Ok(versions)
+    }
+
+    pub fn package_directory(&self, name: &str, version: &Version) -> PathBuf {
+        self.directory.join(name).join(version.to_string())
+    }
+
+    /// Reads the manifest of a published package, checking that it is what the index says.
+    pub fn manifest(
+        &self,
+        name: &str,
+        version: &Version,
+    ) -> Result<WorkspaceManifest, DependencyError> {
+        let directory = self.package_directory(name, version);
+        let manifest = WorkspaceManifest::load(&directory.join(SOL_MANIFEST_FILENAME))?;
+        if manifest.name != name || &manifest.version != version {
+            Err(MislabeledPackage {
+                name: name.to_string(),
+                version: version.clone(),
+                directory,
+                found: format!("{} {}", manifest.name, manifest.version),
+            })?;
+        }
+        Ok(manifest)
+    }
+}
+
+/// Picks one version of every package reachable from `requirements`,
+/// such that every requirement in the graph is satisfied.
+///
+/// `preferred` versions (usually the locked ones) are tried first, then
+/// the newest versions.
+pub fn solve(
+    registry: &Registry,
+    requirements: Vec<Requirement>,
+    preferred: &HashMap<String, Version>,
+) -> Result<BTreeMap<String, Version>, DependencyError> {
+    let mut solver = Solver {
+        registry,
+        preferred,
+        dependencies: HashMap::new(),
+    };
+    let mut chosen = BTreeMap::new();
+    let mut requirements = requirements;
+
+    match solver.solve(&mut chosen, &mut requirements)? {
+        Ok(()) => Ok(chosen),
+        Err(conflict) => Err(conflict.into()),
+    }
+}
+
+struct Solver<'r> {
+    registry: &'r Registry,
+    preferred: &'r HashMap<String, Version>,
+    /// Requirements of each package version, read from the index as needed.
+    dependencies: HashMap<(String, Version), Vec<Requirement>>,
+}
+
+impl Solver<'_> {
+    /// Backtracking search. The outer error aborts the search, the inner one backtracks.
+    fn solve(
+        &mut self,
+        chosen: &mut BTreeMap<String, Version>,
+        requirements: &mut Vec<Requirement>,
+    ) -> Result<Result<(), VersionConflict>, DependencyError> {
+        // Requirements on packages that were already chosen must still hold.
+        for requirement in requirements.iter() {
+            if let Some(version) = chosen.get(&requirement.name) {
+                if !requirement.requirement.matches(version) {
+                    return Ok(Err(self.conflict(&requirement.name, requirements)?));
+                }
+            }
+        }
+
+        let Some(name) = requirements
+            .iter()
+            .find(|requirement| !chosen.contains_key(&requirement.name))
+            .map(|requirement| requirement.name.clone())
+        else {
+            return Ok(Ok(()));
+        };
+
+        let mut candidates = self
+            .registry
+            .versions(&name)?
+            .into_iter()
+            .filter(|version| {
+                requirements
+                    .iter()
+                    .filter(|requirement| requirement.name == name)
+                    .all(|requirement| requirement.requirement.matches(version))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| {
+            let preferred = self.preferred.get(&name);
+            (Some(b) == preferred)
+                .cmp(&(Some(a) == preferred))
+                .then(b.cmp(a))
+        });
+
+        let mut last_conflict = None;
+        for version in candidates {
+            let dependencies = self.dependencies(&name, &version)?;
+
+            chosen.insert(name.clone(), version);
+            let before = requirements.len();
+            requirements.extend(dependencies);
+
+            match self.solve(chosen, requirements)? {
+                Ok(()) => return Ok(Ok(())),
+                Err(conflict) => last_conflict = Some(conflict),
+            }
+
+            requirements.truncate(before);
+            chosen.remove(&name);
+        }
+
+        match last_conflict {
+            Some(conflict) => Ok(Err(conflict)),
+            None => Ok(Err(self.conflict(&name, requirements)?)),
+        }
+    }
+
+    fn conflict(
+        &self,
+        name: &str,
+        requirements: &[Requirement],
+    ) -> Result<VersionConflict, DependencyError> {
+        Ok(VersionConflict::new(
+            name,
+            requirements
+                .iter()
+                .filter(|requirement| requirement.name == name)
+                .cloned()
+                .collect(),
+            self.registry.versions(name)?,
+        ))
+    }
+
+    fn dependencies(
+        &mut self,
+        name: &str,
+        version: &Version,
+    ) -> Result<Vec<Requirement>, DependencyError> {
+        let key = (name.to_string(), version.clone());
+        if let Some(dependencies) = self.dependencies.get(&key) {
+            return Ok(dependencies.clone());
+        }
+
+        let manifest = self.registry.manifest(name, version)?;
+        let requirer = format!("{} {}", name, version);
+        let dependencies = manifest
+            .dependencies
+            .iter()
+            .map(|spec| match DependencySpec::parse(spec)? {
+                DependencySpec::Registry { name, requirement } => Ok(Requirement {
+                    requirer: requirer.clone(),
+                    name,
+                    requirement,
+                }),
+                _ => Err(InvalidRegistryDependency {
+                    package: requirer.clone(),
+                    spec: spec.clone(),
+                }
+                .into()),
+            })
+            .collect::<Result<Vec<_>, DependencyError>>()?;
+
+        self.dependencies.insert(key, dependencies.clone());
+        Ok(dependencies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{solve, Registry, Requirement};
+    use crate::workspace::{dependency::DependencyError, manifest::WorkspaceManifest};
+    use semver::{Version, VersionReq};
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    fn publish(registry: &Registry, name: &str, version: &str, dependencies: &[&str]) {
+        let mut manifest = WorkspaceManifest::new(name);
+        manifest.version = Version::parse(version).unwrap();
+        manifest.dependencies = dependencies.iter().map(|spec| spec.to_string()).collect();
+        let directory = registry.package_directory(name, &manifest.version);
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("index.sol"), manifest.to_source()).unwrap();
+    }
+
+    fn requirement(name: &str, requirement: &str) -> Requirement {
+        Requirement {
+            requirer: "game".to_string(),
+            name: name.to_string(),
+            requirement: VersionReq::parse(requirement).unwrap(),
+        }
+    }
+
+    #[test]
+    fn backtracks_to_compatible_versions() {
+        let temp = tempdir().unwrap();
+        let registry = Registry::new(temp.path());
+        publish(&registry, "items", "1.2.0", &[]);
+        publish(&registry, "items", "1.3.0", &[]);
+        publish(&registry, "items", "2.0.0", &[]);
+        // The newest `weapons` wants `items` 2, which the game can't use.
+        publish(&registry, "weapons", "1.0.0", &["items@^1.2"]);
+        publish(&registry, "weapons", "1.1.0", &["items@^2"]);
+
+        let solution = solve(
+            &registry,
+            vec![requirement("items", "^1.2"), requirement("weapons", "^1")],
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(solution["items"], Version::new(1, 3, 0));
+        assert_eq!(solution["weapons"], Version::new(1, 0, 0));
+
+        let preferred = HashMap::from([("items".to_string(), Version::new(1, 2, 0))]);
+        let solution = solve(&registry, vec![requirement("items", "^1.2")], &preferred).unwrap();
+        assert_eq!(solution["items"], Version::new(1, 2, 0));
+    }
+
+    #[test]
+    fn explains_conflicts() {
+        let temp = tempdir().unwrap();
+        let registry = Registry::new(temp.path());
+        publish(&registry, "items", "1.2.0", &[]);
+        publish(&registry, "items", "2.0.0", &[]);
+        publish(&registry, "weapons", "1.0.0", &["items@^2"]);
+
+        let Err(DependencyError::VersionConflict(conflict)) = solve(
+            &registry,
+            vec![requirement("items", "^1.2"), requirement("weapons", "^1")],
+            &HashMap::new(),
+        ) else {
+            panic!("Requirements should conflict.");
+        };
+        assert_eq!(conflict.name, "items");
+        assert_eq!(
+            conflict.explanation,
+            "`game` requires `items` ^1.2\n`weapons 1.0.0` requires `items` ^2\nbut the index only has 1.2.0, 2.0.0."
+        );
+    }
+}
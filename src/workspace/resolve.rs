@@ -0,0 +1,306 @@
+//! Resolving references to the items of other modules, as in `::weapons::iron_sword::damage`,
+//! and keeping to the visibility the items are declared with.
+//!
+//! ```lua
+//! -- weapons.sol
+//! private(children) base_damage = 3
+//! private sharpness = 2
+//! ```
+//!
+//! The modules under `weapons`, like `weapons/iron_sword.sol`, can use `::weapons::base_damage`,
+//! but not `::weapons::sharpness`, and no other module can use either.
+use super::{migrate, upgrade, ParsedModule};
+use crate::{
+    introspec::project::ModulePath,
+    parser::ast::{Expression, Module, Span, Visibility},
+};
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic)]
+#[error(transparent)]
+pub enum ResolveError {
+    #[diagnostic(transparent)]
+    UnknownItem(#[from] Box<UnknownItem>),
+    #[diagnostic(transparent)]
+    PrivateItem(#[from] Box<PrivateItem>),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{module}` has no item called `{name}`.")]
+#[diagnostic(code(sol::unknown_item))]
+pub struct UnknownItem {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("not in `{module}`")]
+    pub span: SourceSpan,
+    pub name: String,
+    pub module: ModulePath,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{name}` is private to `{owner}`, so `{module}` can't use it.")]
+#[diagnostic(code(sol::private_item))]
+pub struct PrivateItem {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("used here")]
+    pub span: SourceSpan,
+    pub name: String,
+    pub modifier: String,
+    pub owner: ModulePath,
+    pub module: ModulePath,
+    #[related]
+    pub declaration: Vec<PrivateDeclaration>,
+    #[help]
+    pub help: String,
+}
+
+/// Where the item a [`PrivateItem`] is about is declared.
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{name}` is declared in `{owner}`.")]
+pub struct PrivateDeclaration {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("declared `{modifier}`")]
+    pub span: SourceSpan,
+    pub name: String,
+    pub modifier: String,
+    pub owner: ModulePath,
+}
+
+/// Checks that the items of other modules that `module` refers to exist,
+/// and are visible to it. `module` is referred to as `path`.
+///
+/// `modules` are every module references can resolve to, as they're referred to.
+/// The modules of dependencies start with the name of their package, and come first.
+pub fn check(
+    path: &ModulePath,
+    module: &ParsedModule,
+    modules: &[(ModulePath, &ParsedModule)],
+) -> Result<(), ResolveError> {
+    if let Some(directive) = module.ast.model.as_ref() {
+        let candidates = modules
+            .iter()
+            .map(|(_, module)| *module)
+            .collect::<Vec<_>>();
+        if let Some((declaration, model)) = upgrade::find_model(&candidates, &directive.name) {
+            if let Some((owner, _)) = modules
+                .iter()
+                .find(|(_, module)| std::ptr::eq(*module, declaration))
+            {
+                visible(
+                    module,
+                    path,
+                    (owner, declaration),
+                    &model.name,
+                    (model.visibility, model.span),
+                    directive.span,
+                )?;
+            }
+        }
+    }
+
+    let mut references = Vec::new();
+    let mut collect = |expression: &Expression| match expression {
+        Expression::Symbol(symbol) if symbol.path.len() > 1 => references.push(symbol.clone()),
+        _ => {}
+    };
+    migrate::visit_module(&module.ast, &mut collect);
+    for test in module.ast.tests.iter() {
+        for statement in test.statements.iter() {
+            migrate::visit_expressions(&statement.expression, &mut collect);
+        }
+    }
+    references.sort_by_key(|symbol| symbol.span.start);
+
+    for symbol in references {
+        let (name, target) = symbol
+            .path
+            .split_last()
+            .expect("Only paths to items of other modules are collected.");
+        // Paths that aren't to a module are left for whatever else they might be.
+        let Some((owner, declaration)) = modules.iter().find(|(owner, _)| owner.0 == target) else {
+            continue;
+        };
+        let Some(declared) = visibility_of(&declaration.ast, name) else {
+            return Err(Box::new(UnknownItem {
+                src: module.named_source(),
+                span: symbol.span.into(),
+                name: name.clone(),
+                module: owner.clone(),
+            })
+            .into());
+        };
+        visible(
+            module,
+            path,
+            (owner, declaration),
+            name,
+            declared,
+            symbol.span,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The visibility of the item called `name`, if `module` declares one, and where it's declared.
+/// Scenes are always public.
+fn visibility_of(module: &Module, name: &str) -> Option<(Visibility, Span)> {
+    if let Some(field) = module.fields.get(name) {
+        return Some((field.visibility, field.name_span));
+    }
+    module
+        .actions
+        .iter()
+        .find(|action| action.name == name)
+        .map(|action| (action.visibility, action.name_span))
+        .or_else(|| {
+            module
+                .models
+                .iter()
+                .find(|model| model.name == name)
+                .map(|model| (model.visibility, model.span))
+        })
+        .or_else(|| {
+            module
+                .externs
+                .iter()
+                .find(|declaration| declaration.name == name)
+                .map(|declaration| (declaration.visibility, declaration.name_span))
+        })
+        .or_else(|| {
+            module
+                .scenes
+                .iter()
+                .find(|scene| scene.name == name)
+                .map(|scene| (Visibility::Public, scene.name_span))
+        })
+}
+
+/// Checks that `name`, declared in `owner` with `visibility` at `declared`, can be used
+/// from `module`, at `span`. `module` is referred to as `path`.
+fn visible(
+    module: &ParsedModule,
+    path: &ModulePath,
+    (owner, declaration): (&ModulePath, &ParsedModule),
+    name: &str,
+    (visibility, declared): (Visibility, Span),
+    span: Span,
+) -> Result<(), ResolveError> {
+    let child = path.0.len() > owner.0.len() && path.0.starts_with(&owner.0);
+    let allowed = match visibility {
+        _ if path == owner => true,
+        Visibility::Public => true,
+        Visibility::Children => child,
+        Visibility::Private => false,
+    };
+    if allowed {
+        return Ok(());
+    }
+
+    let modifier = visibility
+        .modifier()
+        .expect("Public items are visible everywhere.");
+    Err(Box::new(PrivateItem {
+        src: module.named_source(),
+        span: span.into(),
+        name: name.to_string(),
+        modifier: modifier.to_string(),
+        owner: owner.clone(),
+        module: path.clone(),
+        declaration: vec![PrivateDeclaration {
+            src: declaration.named_source(),
+            span: declared.into(),
+            name: name.to_string(),
+            modifier: modifier.to_string(),
+            owner: owner.clone(),
+        }],
+        help: if child {
+            format!(
+                "`{}` is under `{}`. Declare `{}` as `private(children) {}` \
+                to let the modules under `{}` use it.",
+                path, owner, name, name, owner
+            )
+        } else {
+            format!(
+                "Remove `{}` from `{}`, in `{}`, to let every module use it.",
+                modifier, name, owner
+            )
+        },
+    })
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, ResolveError};
+    use crate::{introspec::project::ModulePath, parser, workspace::ParsedModule};
+
+    fn module(file_name: &str, source: &str) -> ParsedModule {
+        ParsedModule {
+            file_name: file_name.to_string(),
+            source: source.to_string(),
+            ast: parser::parse_module(file_name, source).unwrap(),
+        }
+    }
+
+    fn path(path: &str) -> ModulePath {
+        ModulePath::parse(path).unwrap()
+    }
+
+    #[test]
+    fn enforces_visibility() {
+        let weapons = module(
+            "weapons.sol",
+            "private(children) base_damage = 3\nprivate sharpness = 2\nname = \"Weapons\"\n\nprivate model Weapon\n    damage: Nat\nend\n",
+        );
+        let sword = module(
+            "weapons/sword.sol",
+            "damage = ::weapons::base_damage + 1\nlabel = \"{::weapons::name}\"\n",
+        );
+        let sharp_sword = module("weapons/sharp_sword.sol", "damage = ::weapons::sharpness\n");
+        let shop = module("shop.sol", "price = ::weapons::base_damage * 10\n");
+        let axe = module("axe.sol", "@model Weapon\ndamage = 4\n");
+        let typo = module("typo.sol", "damage = ::weapons::bass_damage\n");
+        let modules = [
+            (path("weapons"), &weapons),
+            (path("weapons.sword"), &sword),
+            (path("weapons.sharp_sword"), &sharp_sword),
+            (path("shop"), &shop),
+            (path("axe"), &axe),
+            (path("typo"), &typo),
+        ];
+
+        check(&path("weapons.sword"), &sword, &modules).unwrap();
+
+        let Err(ResolveError::PrivateItem(error)) =
+            check(&path("weapons.sharp_sword"), &sharp_sword, &modules)
+        else {
+            panic!("`sharpness` is private.");
+        };
+        assert!(error.help.contains("`private(children) sharpness`"));
+        assert_eq!(error.span, (9, 20).into());
+        let declaration = &error.declaration[0];
+        assert_eq!(declaration.src.name(), "weapons.sol");
+        assert_eq!(declaration.span, (42, 9).into());
+
+        let Err(ResolveError::PrivateItem(error)) = check(&path("shop"), &shop, &modules) else {
+            panic!("`base_damage` is only visible under `weapons`.");
+        };
+        assert_eq!(
+            error.help,
+            "Remove `private(children)` from `base_damage`, in `weapons`, to let every module use it."
+        );
+
+        assert!(matches!(
+            check(&path("axe"), &axe, &modules),
+            Err(ResolveError::PrivateItem(error)) if error.name == "Weapon"
+        ));
+        assert!(matches!(
+            check(&path("typo"), &typo, &modules),
+            Err(ResolveError::UnknownItem(error)) if error.name == "bass_damage"
+        ));
+    }
+}
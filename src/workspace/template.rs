@@ -0,0 +1,82 @@
+//! Built-in project templates for `sol init`.
+use super::manifest::WorkspaceManifest;
+use std::path::PathBuf;
+
+/// What a new workspace starts out with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Template {
+    /// Just the manifest and an empty source directory.
+    #[default]
+    Empty,
+    /// A game made of scenes with dialogue.
+    Dialogue,
+    /// A database of items, with an `Item` model.
+    Items,
+    /// Dialogue authored in one locale and translated into others.
+    Localized,
+}
+
+impl Template {
+    /// The manifest a workspace made from this template starts with.
+    pub fn manifest(&self, name: String) -> WorkspaceManifest {
+        let mut manifest = WorkspaceManifest::new(name);
+        if *self == Template::Localized {
+            manifest.default_locale = Some("en".to_string());
+        }
+        manifest
+    }
+
+    /// Files the template creates, relative to the workspace directory.
+    ///
+    /// Directories are listed with a trailing `/` and no contents.
+    pub fn files(&self) -> Vec<(PathBuf, &'static str)> {
+        let files: &[(&str, &'static str)] = match self {
+            Template::Empty => &[("src/", "")],
+            Template::Dialogue => &[("src/main.sol", DIALOGUE_MAIN)],
+            Template::Items => &[
+                ("src/models/item.sol", ITEMS_MODEL),
+                ("src/items/wooden_sword.sol", ITEMS_WOODEN_SWORD),
+            ],
+            Template::Localized => &[("src/main.sol", DIALOGUE_MAIN), ("translations/", "")],
+        };
+
+        files
+            .iter()
+            .map(|(path, contents)| (PathBuf::from(path), *contents))
+            .collect()
+    }
+}
+
+const DIALOGUE_MAIN: &str = r#"-- The first scene of your game.
+scene main
+    [Narrator]
+    - Hello, there!
+
+    prompt Where to?
+        option Outside
+            * You step outside.
+        end
+        option Nowhere
+            - Suit yourself.
+        end
+    end
+end
+"#;
+
+const ITEMS_MODEL: &str = r#"-- An item is something that can be stored in your
+-- inventory and used.
+model Item
+    name: Text
+    description: Text
+    attack_damage: Nat
+    durability: dynamic Nat
+end
+"#;
+
+const ITEMS_WOODEN_SWORD: &str = r#"@model Item
+
+name = "Wooden Sword"
+description = "A humble maple wood sword you can bonk enemies with."
+attack_damage = 20
+durability = 20
+"#;
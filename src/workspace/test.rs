@@ -0,0 +1,917 @@
+//! Tests written in Sol, next to the scenes and assets they test:
+//!
+//! ```lua
+//! test "the player can leave"
+//!     play(::main)
+//!     expect_line("Narrator", "Hello, there!")
+//!     expect_choices("Outside", "Nowhere")
+//!     choose("Outside")
+//!     expect_line("You step outside.")
+//!     expect_end()
+//! end
+//!
+//! test "swords are sharp"
+//!     expect_field(::weapons::sword, "attack_damage", 20)
+//! end
+//! ```
+//!
+//! Each statement is a call. `play` starts a scene, `choose` picks an option of the
+//! prompt the scene is waiting on, and the `expect_*` calls assert on what the scene
+//! does next. Lines are skipped while looking for calls, and calls while looking for lines.
+//! `expect_field` and `expect_state` work out the value they expect the way fields are,
+//! so `2 * 10` is `20`.
+//!
+//! With `--snapshot`, every path through every scene is also played, and its transcript
+//! is compared to the one recorded in a `.snap` file next to the module.
+use super::{upgrade, BuildError, LoadedPackage, ParsedModule};
+use crate::{
+    introspec::{
+        eval::{self, Evaluator},
+        lower::{self, LoweringError},
+        project::{ModulePath, Project},
+        repr::{AssetModule, ModuleItemValue, Value},
+    },
+    parser::ast::{Expression, Scene, Test, TestStatement},
+    runtime::{render_text, signal::Signals, state::State, Event, SceneRunner},
+    unparser::print_expression,
+};
+use clap::Args;
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Debug, Args)]
+pub struct TestArgs {
+    /// Only runs the tests whose names contain this.
+    pub filter: Option<String>,
+
+    /// Also compares the transcripts of every path through every scene
+    /// to the ones recorded next to the modules, recording any missing ones.
+    #[arg(short, long)]
+    pub snapshot: bool,
+
+    /// Records the transcripts that changed, instead of failing.
+    #[arg(short, long, requires = "snapshot")]
+    pub update: bool,
+}
+
+/// The extension of the files scene transcripts are recorded in, next to their modules.
+pub const SNAPSHOT_EXTENSION: &str = "snap";
+
+/// What to do with scene transcripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Snapshots {
+    Skip,
+    Check,
+    Update,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("{message}")]
+#[diagnostic(code(sol::test_failure))]
+pub struct TestFailure {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("{label}")]
+    pub span: SourceSpan,
+    pub message: String,
+    pub label: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("{failed} of {total} tests failed.")]
+#[diagnostic(code(sol::tests_failed))]
+pub struct TestsFailed {
+    pub failed: usize,
+    pub total: usize,
+}
+
+/// How a single test went.
+#[derive(Debug)]
+pub struct TestOutcome {
+    pub module: ModulePath,
+    pub name: String,
+    pub failure: Option<TestFailure>,
+}
+
+/// How the transcript of a module's scenes compared to the recorded one.
+#[derive(Debug)]
+pub struct SnapshotOutcome {
+    pub module: ModulePath,
+    pub file: PathBuf,
+    pub status: SnapshotStatus,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SnapshotStatus {
+    Matched,
+    /// There was no recorded transcript, so it was recorded.
+    Recorded,
+    /// The transcript changed, and was recorded again.
+    Updated,
+    /// The transcript changed. `diff` goes from the recorded transcript to the new one.
+    Changed {
+        diff: String,
+    },
+}
+
+/// Every test that ran in a workspace.
+#[derive(Debug, Default)]
+pub struct TestRun {
+    pub outcomes: Vec<TestOutcome>,
+    pub snapshots: Vec<SnapshotOutcome>,
+    /// How many tests didn't match the filter.
+    pub filtered_out: usize,
+    /// How many modules with scenes didn't match the filter, so their transcripts weren't checked.
+    pub filtered_snapshots: usize,
+}
+
+impl TestRun {
+    pub fn failed(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.failure.is_some())
+            .count()
+            + self
+                .snapshots
+                .iter()
+                .filter(|snapshot| matches!(snapshot.status, SnapshotStatus::Changed { .. }))
+                .count()
+    }
+
+    pub fn total(&self) -> usize {
+        self.outcomes.len() + self.snapshots.len()
+    }
+}
+
+/// Runs the tests of every package of the workspace at `path` whose names contain `filter`,
+/// and checks the transcripts of the scenes of modules whose paths contain it.
+pub fn run_tests(
+    path: PathBuf,
+    filter: Option<&str>,
+    snapshots: Snapshots,
+) -> Result<TestRun, BuildError> {
+    let project = Project::discover(&path)?;
+    let mut run = TestRun::default();
+
+    for package in project.all_packages() {
+        let loaded = LoadedPackage::load(package)?;
+        let state = loaded.state()?;
+        let signals = loaded.signals();
+        for (project_module, module) in package.modules.iter().zip(loaded.modules.iter()) {
+            for test in module.ast.tests.iter() {
+                if filter.is_some_and(|filter| !test.name.contains(filter)) {
+                    run.filtered_out += 1;
+                    continue;
+                }
+                run.outcomes.push(TestOutcome {
+                    module: project_module.path.clone(),
+                    name: test.name.clone(),
+                    failure: run_test(
+                        &loaded,
+                        &state,
+                        &signals,
+                        &project_module.path,
+                        module,
+                        test,
+                    )
+                    .err()
+                    .map(|failure| *failure),
+                });
+            }
+
+            if snapshots == Snapshots::Skip || module.ast.scenes.is_empty() {
+                continue;
+            }
+            if filter.is_some_and(|filter| !project_module.path.to_string().contains(filter)) {
+                run.filtered_snapshots += 1;
+                continue;
+            }
+            let file = project_module.file.with_extension(SNAPSHOT_EXTENSION);
+            run.snapshots.push(SnapshotOutcome {
+                module: project_module.path.clone(),
+                status: check_snapshot(
+                    &file,
+                    &transcript(&project_module.path, module, &state, &signals),
+                    snapshots,
+                )?,
+                file,
+            });
+        }
+    }
+
+    Ok(run)
+}
+
+fn run_test<'a>(
+    package: &'a LoadedPackage,
+    state: &'a State,
+    signals: &'a Signals<'a>,
+    path: &'a ModulePath,
+    module: &ParsedModule,
+    test: &Test,
+) -> Result<(), Box<TestFailure>> {
+    let mut runner = Runner {
+        package,
+        state,
+        signals,
+        path,
+        module,
+        playthrough: None,
+    };
+    for statement in test.statements.iter() {
+        runner.run(statement)?;
+    }
+    Ok(())
+}
+
+/// Compares `transcript` to the one recorded in `file`, recording it if need be.
+fn check_snapshot(
+    file: &Path,
+    transcript: &str,
+    snapshots: Snapshots,
+) -> Result<SnapshotStatus, std::io::Error> {
+    let status = match std::fs::read_to_string(file) {
+        Ok(recorded) if recorded == transcript => return Ok(SnapshotStatus::Matched),
+        Ok(recorded) if snapshots != Snapshots::Update => {
+            let name = file
+                .file_name()
+                .expect("Snapshots are files.")
+                .to_string_lossy();
+            return Ok(SnapshotStatus::Changed {
+                diff: similar::TextDiff::from_lines(recorded.as_str(), transcript)
+                    .unified_diff()
+                    .header(
+                        &format!("{} (recorded)", name),
+                        &format!("{} (played)", name),
+                    )
+                    .to_string(),
+            });
+        }
+        Ok(_) => SnapshotStatus::Updated,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => SnapshotStatus::Recorded,
+        Err(error) => return Err(error),
+    };
+    std::fs::write(file, transcript)?;
+    Ok(status)
+}
+
+/// Every path through the scenes of `module`, at `path`, one after the other.
+/// Each starts out with the game in `state`.
+fn transcript(
+    path: &ModulePath,
+    module: &ParsedModule,
+    state: &State,
+    signals: &Signals,
+) -> String {
+    let mut transcript = String::new();
+    for scene in module.ast.scenes.iter() {
+        explore(
+            SceneRunner::new(scene)
+                .with_state(path, state.clone())
+                .with_signals(signals),
+            format!("== {}", scene.name),
+            String::new(),
+            &mut transcript,
+        );
+    }
+    transcript
+}
+
+/// Plays every path through the rest of a scene, writing each one into `transcript`.
+///
+/// `heading` names the path by the options chosen, and `lines` are what was said along it.
+/// A path that fails ends with its error.
+fn explore(
+    mut playthrough: SceneRunner,
+    heading: String,
+    mut lines: String,
+    transcript: &mut String,
+) {
+    loop {
+        let event = playthrough.next().unwrap_or_else(|error| {
+            lines += &format!("error {}\n", error);
+            Event::End
+        });
+        match event {
+            Event::SpeakerChanged { .. } => {}
+            Event::Dialogue {
+                speaker: Some(speaker),
+                text,
+            } => lines += &format!("{}: {}\n", speaker, text),
+            Event::Dialogue {
+                speaker: None,
+                text,
+            }
+            | Event::Narration { text } => lines += &format!("* {}\n", text),
+            Event::Call { name, args } => {
+                let call = Expression::Call {
+                    name: name.to_string(),
+                    args: args.to_vec(),
+                    name_span: Default::default(),
+                };
+                lines += &format!("call {}\n", show(&call));
+            }
+            Event::Prompt {
+                text,
+                options: shown,
+            } => {
+                let text = text.map(|text| format!(" {:?}", text)).unwrap_or_default();
+                lines += &format!("prompt{}: {}\n", text, options(&shown));
+                for (index, option) in shown.iter().enumerate() {
+                    let option = format!("{:?}", option);
+                    let mut chosen = playthrough.clone();
+                    chosen
+                        .choose(index)
+                        .expect("Every option of the prompt is chosen.");
+                    explore(
+                        chosen,
+                        format!("{} > {}", heading, option),
+                        format!("{}choose {}\n", lines, option),
+                        transcript,
+                    );
+                }
+                return;
+            }
+            Event::Suspended { name } => {
+                lines += &format!(
+                    "error Waits on `{}`, but there's no game to wait on.\n",
+                    name
+                );
+                break;
+            }
+            Event::End => break,
+        }
+    }
+
+    if !transcript.is_empty() {
+        transcript.push('\n');
+    }
+    *transcript += &format!("{}\n{}end\n", heading, lines);
+}
+
+/// Describes an event, for failures.
+fn describe(event: &Event) -> String {
+    match event {
+        Event::SpeakerChanged { speaker, .. } => format!("`{}` starting to speak", speaker),
+        Event::Dialogue {
+            speaker: Some(speaker),
+            text,
+        } => format!("the line {:?} by `{}`", text, speaker),
+        Event::Dialogue {
+            speaker: None,
+            text,
+        }
+        | Event::Narration { text } => format!("the line {:?}", text),
+        Event::Call { name, .. } => format!("a call to `{}`", name),
+        Event::Suspended { name } => format!("waiting on `{}`", name),
+        Event::Prompt { options: shown, .. } => {
+            format!("a prompt with the options {}", options(shown))
+        }
+        Event::End => "the end of the scene".to_string(),
+    }
+}
+
+fn show(expression: &Expression) -> String {
+    print_expression(expression).pretty(usize::MAX).to_string()
+}
+
+fn options(options: &[String]) -> String {
+    options
+        .iter()
+        .map(|option| format!("{:?}", option))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+struct Runner<'a> {
+    package: &'a LoadedPackage<'a>,
+    /// The state scenes start out in.
+    state: &'a State,
+    signals: &'a Signals<'a>,
+    /// The path of the test's module.
+    path: &'a ModulePath,
+    module: &'a ParsedModule,
+    playthrough: Option<SceneRunner<'a>>,
+}
+
+impl<'a> Runner<'a> {
+    fn run(&mut self, statement: &'a TestStatement) -> Result<(), Box<TestFailure>> {
+        let Expression::Call { name, args, .. } = &statement.expression else {
+            return Err(self.fail(
+                statement,
+                "Test statements are calls, as in `play(::main)` or `expect_end()`.",
+                "not a call",
+            ));
+        };
+
+        match (name.as_str(), args.as_slice()) {
+            ("play", [Expression::Symbol(symbol)]) => {
+                let (path, scene) = self.scene(&symbol.path).ok_or_else(|| {
+                    self.fail(
+                        statement,
+                        format!("Can't find the scene `::{}`.", symbol.path.join("::")),
+                        "no such scene",
+                    )
+                })?;
+                self.playthrough = Some(
+                    SceneRunner::new(scene)
+                        .with_state(&path, self.state.clone())
+                        .with_signals(self.signals),
+                );
+            }
+            ("choose", [choice @ (Expression::Text(_) | Expression::Int(_))]) => {
+                let Event::Prompt { options: shown, .. } = self.advance(
+                    statement,
+                    "a prompt",
+                    |event| matches!(event, Event::Prompt { .. }),
+                    |event| !matches!(event, Event::End),
+                )?
+                else {
+                    unreachable!("Only prompts are wanted.");
+                };
+                let index = match choice {
+                    Expression::Text(text) => {
+                        let text = render_text(text);
+                        shown.iter().position(|option| option == &text)
+                    }
+                    Expression::Int(number) => usize::try_from(*number)
+                        .ok()
+                        .filter(|number| (1..=shown.len()).contains(number))
+                        .map(|number| number - 1),
+                    _ => unreachable!("Only text and numbers are chosen."),
+                };
+                let Some(index) = index else {
+                    return Err(self.fail(
+                        statement,
+                        format!(
+                            "Can't choose {}, the options are {}.",
+                            show(choice),
+                            options(&shown)
+                        ),
+                        "no such option",
+                    ));
+                };
+                self.playthrough
+                    .as_mut()
+                    .expect("Scenes are playing when they prompt.")
+                    .choose(index)
+                    .expect("Only options of the prompt are chosen.");
+            }
+            ("expect_line", [speaker_and_text @ .., Expression::Text(text)])
+                if speaker_and_text.len() <= 1
+                    && speaker_and_text
+                        .iter()
+                        .all(|speaker| matches!(speaker, Expression::Text(_))) =>
+            {
+                let expected_text = render_text(text);
+                let expected_speaker = speaker_and_text.first().map(|speaker| match speaker {
+                    Expression::Text(speaker) => render_text(speaker),
+                    _ => unreachable!("Speakers are text."),
+                });
+                let event = self.advance(
+                    statement,
+                    "a line",
+                    |event| matches!(event, Event::Dialogue { .. } | Event::Narration { .. }),
+                    |event| matches!(event, Event::SpeakerChanged { .. } | Event::Call { .. }),
+                )?;
+                let (speaker, text) = match &event {
+                    Event::Dialogue { speaker, text } => (speaker.as_ref(), text),
+                    Event::Narration { text } => (None, text),
+                    _ => unreachable!("Only lines are wanted."),
+                };
+                if text != &expected_text
+                    || expected_speaker.is_some_and(|expected| speaker != Some(&expected))
+                {
+                    return Err(self.fail(
+                        statement,
+                        format!("Expected another line, but got {}.", describe(&event)),
+                        "this line",
+                    ));
+                }
+            }
+            ("expect_call", [Expression::Text(expected), expected_args @ ..]) => {
+                let expected = render_text(expected);
+                let event = self.advance(
+                    statement,
+                    "a call",
+                    |event| matches!(event, Event::Call { .. }),
+                    |event| {
+                        matches!(
+                            event,
+                            Event::SpeakerChanged { .. }
+                                | Event::Dialogue { .. }
+                                | Event::Narration { .. }
+                        )
+                    },
+                )?;
+                let Event::Call { name, args } = &event else {
+                    unreachable!("Only calls are wanted.");
+                };
+                if *name != expected || *args != expected_args {
+                    let actual = Expression::Call {
+                        name: name.to_string(),
+                        args: args.to_vec(),
+                        name_span: Default::default(),
+                    };
+                    return Err(self.fail(
+                        statement,
+                        format!("Expected another call, but got `{}`.", show(&actual)),
+                        "this call",
+                    ));
+                }
+            }
+            ("expect_choices", expected) if !expected.is_empty() => {
+                let Event::Prompt { options: shown, .. } = self.advance(
+                    statement,
+                    "a prompt",
+                    |event| matches!(event, Event::Prompt { .. }),
+                    |event| !matches!(event, Event::End),
+                )?
+                else {
+                    unreachable!("Only prompts are wanted.");
+                };
+                let actual = shown.iter().cloned().map(Some).collect::<Vec<_>>();
+                let expected = expected
+                    .iter()
+                    .map(|option| match option {
+                        Expression::Text(text) => Some(render_text(text)),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                if actual != expected {
+                    return Err(self.fail(
+                        statement,
+                        format!("Expected other options, but got {}.", options(&shown)),
+                        "these options",
+                    ));
+                }
+            }
+            ("expect_end", []) => {
+                self.advance(
+                    statement,
+                    "the end of the scene",
+                    |event| matches!(event, Event::End),
+                    |event| matches!(event, Event::SpeakerChanged { .. }),
+                )?;
+            }
+            ("expect_field", [Expression::Symbol(symbol), Expression::Text(field), expected]) => {
+                let field = render_text(field);
+                let module = self.module(&symbol.path).ok_or_else(|| {
+                    self.fail(
+                        statement,
+                        format!("Can't find the module `::{}`.", symbol.path.join("::")),
+                        "no such module",
+                    )
+                })?;
+                if !module.ast.fields.contains_key(&field) {
+                    return Err(self.fail(
+                        statement,
+                        format!("The module has no field `{}`.", field),
+                        "no such field",
+                    ));
+                }
+                let (lowered, expected) =
+                    self.evaluate_field(statement, module, &field, expected)?;
+                let actual = match lowered.items.get(&field).map(|item| &item.value) {
+                    Some(ModuleItemValue::Static(value) | ModuleItemValue::Dynamic(value)) => value,
+                    _ => {
+                        return Err(self.fail(
+                            statement,
+                            format!("`{}` is only known once the game runs.", field),
+                            "this field",
+                        ))
+                    }
+                };
+                if !eval::equal(actual, &expected) {
+                    return Err(self.fail(
+                        statement,
+                        format!("`{}` is `{}`.", field, eval::show(actual)),
+                        "this field",
+                    ));
+                }
+            }
+            ("expect_state", [Expression::Symbol(symbol), expected]) => {
+                let Some((variable, module_path)) = symbol.path.split_last() else {
+                    unreachable!("Symbols have a name.");
+                };
+                let (module, path) = if module_path.is_empty() {
+                    (Some(self.module), self.path.0.clone())
+                } else {
+                    (self.module(module_path), module_path.to_vec())
+                };
+                let path = path
+                    .into_iter()
+                    .chain([variable.clone()])
+                    .collect::<Vec<_>>();
+                let actual = self
+                    .playthrough
+                    .as_ref()
+                    .and_then(|playthrough| playthrough.state().get(&path))
+                    .or_else(|| self.state.get(&path))
+                    .cloned();
+                let (Some(module), Some(actual)) = (module, actual) else {
+                    return Err(self.fail(
+                        statement,
+                        format!("The game has no variable `::{}`.", path.join("::")),
+                        "no such variable",
+                    ));
+                };
+                let (_, expected) = self.evaluate_field(statement, module, variable, expected)?;
+                if !eval::equal(&actual, &expected) {
+                    return Err(self.fail(
+                        statement,
+                        format!("`::{}` is `{}`.", path.join("::"), eval::show(&actual)),
+                        "this variable",
+                    ));
+                }
+            }
+            _ => {
+                return Err(self.fail(
+                    statement,
+                    format!(
+                        "`{}` isn't something a test can do.",
+                        show(&statement.expression)
+                    ),
+                    "unknown statement",
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Plays the scene until an event is `wanted`, failing on any event that isn't to `skip`.
+    fn advance(
+        &mut self,
+        statement: &TestStatement,
+        what: &str,
+        wanted: fn(&Event) -> bool,
+        skip: fn(&Event) -> bool,
+    ) -> Result<Event<'a>, Box<TestFailure>> {
+        let Some(playthrough) = self.playthrough.as_mut() else {
+            return Err(self.fail(
+                statement,
+                "Nothing is playing. Start a scene with `play(::scene)` first.",
+                "no scene",
+            ));
+        };
+        loop {
+            let event = match playthrough.next() {
+                Ok(event) => event,
+                Err(error) => return Err(self.fail(statement, error.to_string(), "while playing")),
+            };
+            if wanted(&event) {
+                return Ok(event);
+            }
+            if !skip(&event) {
+                let message = format!("Expected {}, but got {}.", what, describe(&event));
+                return Err(self.fail(statement, message, "expected here"));
+            }
+        }
+    }
+
+    /// Lowers `module`, along with `expected`, as if it were the value of its field `field`,
+    /// and works out the value of `expected`. Assets are upgraded to their model first.
+    fn evaluate_field(
+        &self,
+        statement: &TestStatement,
+        module: &ParsedModule,
+        field: &str,
+        expected: &Expression,
+    ) -> Result<(AssetModule, Value), Box<TestFailure>> {
+        let models = self.package.models();
+        let module = if module.ast.model.is_some() {
+            upgrade::load_asset(module, &models).map_err(|error| {
+                self.fail(
+                    statement,
+                    format!("The asset doesn't load: {}", error),
+                    "this asset",
+                )
+            })?
+        } else {
+            module.clone()
+        };
+        let doesnt_build = |error: LoweringError| {
+            let problems = error
+                .problems
+                .iter()
+                .map(|problem| problem.message.as_str())
+                .collect::<Vec<_>>();
+            self.fail(
+                statement,
+                format!("The module doesn't build: {}", problems.join(" ")),
+                "this module",
+            )
+        };
+        let lowered = lower::lower(&module, &models).map_err(&doesnt_build)?;
+        let expected = lower::lower_field(&module, &models, field, expected, statement.span)
+            .map_err(&doesnt_build)?;
+        let expected = Evaluator::new(&lowered.items, &HashMap::new())
+            .evaluate(&expected, &HashMap::new())
+            .map_err(|error| {
+                self.fail(
+                    statement,
+                    format!("The expected value can't be worked out: {}", error),
+                    "this value",
+                )
+            })?;
+        Ok((lowered, expected))
+    }
+
+    /// Finds a module of this package by its path, as in `::weapons::sword`.
+    fn module(&self, path: &[String]) -> Option<&'a ParsedModule> {
+        self.package
+            .project
+            .modules
+            .iter()
+            .zip(self.package.modules.iter())
+            .find(|(project_module, _)| project_module.path.0 == path)
+            .map(|(_, module)| module)
+    }
+
+    /// Finds a scene, as in `::main` in the test's module, or `::dialogue::main` in another,
+    /// along with the path of its module.
+    fn scene(&self, path: &[String]) -> Option<(ModulePath, &'a Scene)> {
+        let (name, module_path) = path.split_last()?;
+        let (module, module_path) = if module_path.is_empty() {
+            (self.module, self.path.clone())
+        } else {
+            (self.module(module_path)?, ModulePath(module_path.to_vec()))
+        };
+        let scene = module.ast.scenes.iter().find(|scene| &scene.name == name)?;
+        Some((module_path, scene))
+    }
+
+    fn fail(
+        &self,
+        statement: &TestStatement,
+        message: impl Into<String>,
+        label: &str,
+    ) -> Box<TestFailure> {
+        Box::new(TestFailure {
+            src: self.module.named_source(),
+            span: statement.span.into(),
+            message: message.into(),
+            label: label.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_tests, SnapshotStatus, Snapshots};
+    use crate::workspace::{init, template::Template};
+    use tempfile::tempdir;
+
+    const TESTS: &str = r#"
+test "the player can leave"
+    play(::main)
+    expect_line("Narrator", "Hello, there!")
+    expect_choices("Outside", "Nowhere")
+    choose("Outside")
+    expect_line("You step outside.")
+    expect_end()
+end
+
+test "the player can stay"
+    play(::main)
+    choose(2)
+    -- Wrong on purpose.
+    expect_line("Narrator", "Fine, then.")
+end
+
+test "main has no fields"
+    expect_field(::main, "name", "Main")
+end
+
+test "damage adds up"
+    expect_field(::main, "damage", 5)
+end
+
+test "buying costs gold"
+    play(::shop)
+    expect_line("Thanks!")
+    expect_end()
+    expect_state(::gold, 10 - 4)
+end
+
+test "buying is free"
+    play(::shop)
+    expect_line("Thanks!")
+    expect_end()
+    -- Wrong on purpose.
+    expect_state(::main::gold, 10)
+end
+"#;
+
+    const SHOP: &str = r#"
+damage = 2 + 3
+dynamic gold = 10
+
+scene shop
+    set(::gold, ::gold - 4)
+    * Thanks!
+end
+"#;
+
+    #[test]
+    fn runs_tests_in_sol() {
+        let temp = tempdir().unwrap();
+        init(temp.path().to_path_buf(), Template::Dialogue, true).unwrap();
+        let main = temp.path().join("src/main.sol");
+        let source = std::fs::read_to_string(&main).unwrap();
+        std::fs::write(&main, source + SHOP + TESTS).unwrap();
+
+        let run = run_tests(temp.path().to_path_buf(), None, Snapshots::Skip).unwrap();
+        let results = run
+            .outcomes
+            .iter()
+            .map(|outcome| {
+                (
+                    outcome.name.as_str(),
+                    outcome
+                        .failure
+                        .as_ref()
+                        .map(|failure| failure.message.as_str()),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            vec![
+                ("the player can leave", None),
+                (
+                    "the player can stay",
+                    Some(
+                        "Expected another line, but got the line \"Suit yourself.\" by `Narrator`."
+                    )
+                ),
+                (
+                    "main has no fields",
+                    Some("The module has no field `name`.")
+                ),
+                ("damage adds up", None),
+                ("buying costs gold", None),
+                ("buying is free", Some("`::main::gold` is `6`.")),
+            ]
+        );
+
+        let filtered =
+            run_tests(temp.path().to_path_buf(), Some("player"), Snapshots::Skip).unwrap();
+        assert_eq!((filtered.outcomes.len(), filtered.filtered_out), (2, 4));
+        assert_eq!(filtered.failed(), 1);
+        let filtered =
+            run_tests(temp.path().to_path_buf(), Some("player"), Snapshots::Check).unwrap();
+        assert_eq!(filtered.filtered_out, 4);
+        assert_eq!(
+            (filtered.snapshots.len(), filtered.filtered_snapshots),
+            (0, 1)
+        );
+    }
+
+    #[test]
+    fn snapshots_scene_paths() {
+        let temp = tempdir().unwrap();
+        init(temp.path().to_path_buf(), Template::Dialogue, true).unwrap();
+        let main = temp.path().join("src/main.sol");
+        let snapshot = temp.path().join("src/main.snap");
+        let status = |snapshots| {
+            let mut run = run_tests(temp.path().to_path_buf(), None, snapshots).unwrap();
+            run.snapshots.remove(0).status
+        };
+
+        assert_eq!(status(Snapshots::Check), SnapshotStatus::Recorded);
+        assert_eq!(
+            std::fs::read_to_string(&snapshot).unwrap(),
+            r#"== main > "Outside"
+Narrator: Hello, there!
+prompt "Where to?": "Outside", "Nowhere"
+choose "Outside"
+* You step outside.
+end
+
+== main > "Nowhere"
+Narrator: Hello, there!
+prompt "Where to?": "Outside", "Nowhere"
+choose "Nowhere"
+Narrator: Suit yourself.
+end
+"#
+        );
+        assert_eq!(status(Snapshots::Check), SnapshotStatus::Matched);
+
+        let source = std::fs::read_to_string(&main).unwrap();
+        std::fs::write(&main, source.replace("Suit yourself.", "Fine, then.")).unwrap();
+        let SnapshotStatus::Changed { diff } = status(Snapshots::Check) else {
+            panic!("The transcript should have changed.");
+        };
+        assert!(diff.contains("-Narrator: Suit yourself.\n+Narrator: Fine, then.\n"));
+
+        assert_eq!(status(Snapshots::Update), SnapshotStatus::Updated);
+        assert_eq!(status(Snapshots::Check), SnapshotStatus::Matched);
+    }
+}
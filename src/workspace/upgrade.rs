@@ -0,0 +1,315 @@
+//! Keeping assets authored against older versions of their model loading.
+//!
+//! Models declare their version, and how to upgrade assets from each older version:
+//!
+//! ```lua
+//! model Item version 2
+//!     name: Text
+//!     uses: dynamic Nat
+//!     rarity: Text
+//!
+//!     upgrade 1 = [rename_field("durability", "uses"), set_default("rarity", "common")]
+//! end
+//! ```
+//!
+//! Assets record the version they were authored against, as in `@model Item version 1`,
+//! and are upgraded as they are built. Their files are left as they are.
+use super::{
+    migrate::{self, MigrateError},
+    ParsedModule,
+};
+use crate::parser::{self, ast::Model, ParseError};
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic)]
+#[error(transparent)]
+pub enum UpgradeError {
+    #[diagnostic(transparent)]
+    Parse(#[from] ParseError),
+    #[diagnostic(transparent)]
+    Migrate(#[from] MigrateError),
+    #[diagnostic(transparent)]
+    UnknownModel(#[from] UnknownModel),
+    #[diagnostic(transparent)]
+    NewerAsset(#[from] NewerAsset),
+    #[diagnostic(transparent)]
+    MissingUpgrade(#[from] MissingUpgrade),
+    #[diagnostic(transparent)]
+    InvalidAsset(#[from] InvalidAsset),
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("Can't find the model `{name}`.")]
+#[diagnostic(
+    code(sol::unknown_model),
+    help("Declare it with `model {name} ... end`, in this workspace or a dependency.")
+)]
+pub struct UnknownModel {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("not a model")]
+    pub span: SourceSpan,
+    pub name: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("This asset is authored against version {asset_version} of `{model}`, which is only at version {model_version}.")]
+#[diagnostic(code(sol::newer_asset))]
+pub struct NewerAsset {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("asset of a newer version")]
+    pub span: SourceSpan,
+    pub model: String,
+    pub asset_version: u32,
+    pub model_version: u32,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("`{model}` doesn't say how to upgrade assets from version {from}.")]
+#[diagnostic(
+    code(sol::missing_upgrade),
+    help(
+        "Add `upgrade {from} = [...]` to `model {model}`, or rewrite the assets with \
+        `sol migrate`, ending the migration with `set_version(\"{model}\", {to})`."
+    )
+)]
+pub struct MissingUpgrade {
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("authored against version {from}")]
+    pub span: SourceSpan,
+    pub model: String,
+    pub from: u32,
+    pub to: u32,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("This asset doesn't match its model, `{model}`.")]
+#[diagnostic(code(sol::asset))]
+pub struct InvalidAsset {
+    #[source_code]
+    pub src: NamedSource<String>,
+    pub model: String,
+    #[related]
+    pub problems: Vec<AssetProblem>,
+}
+
+/// A single thing wrong with an asset, pointing at where it happens.
+#[derive(Error, Debug, Diagnostic)]
+#[error("{message}")]
+pub struct AssetProblem {
+    pub message: String,
+    #[label("{label}")]
+    pub span: SourceSpan,
+    pub label: String,
+}
+
+/// Finds the model called `name` among `modules`. The first declaration wins.
+pub fn find_model<'m>(
+    modules: &[&'m ParsedModule],
+    name: &str,
+) -> Option<(&'m ParsedModule, &'m Model)> {
+    modules.iter().find_map(|module| {
+        module
+            .ast
+            .models
+            .iter()
+            .find(|model| model.name == name)
+            .map(|model| (*module, model))
+    })
+}
+
+/// Upgrades `asset` to the current version of its model, which is looked up
+/// in `models`, and checks that its fields match the model's.
+pub fn load_asset(
+    asset: &ParsedModule,
+    models: &[&ParsedModule],
+) -> Result<ParsedModule, UpgradeError> {
+    let directive = asset
+        .ast
+        .model
+        .as_ref()
+        .expect("Only assets are loaded as assets.");
+    let Some((declaration, model)) = find_model(models, &directive.name) else {
+        return Err(UnknownModel {
+            src: asset.named_source(),
+            span: directive.span.into(),
+            name: directive.name.clone(),
+        }
+        .into());
+    };
+
+    let version = directive.version.unwrap_or(1);
+    if version > model.version {
+        return Err(NewerAsset {
+            src: asset.named_source(),
+            span: directive.span.into(),
+            model: model.name.clone(),
+            asset_version: version,
+            model_version: model.version,
+        }
+        .into());
+    }
+
+    let mut steps = Vec::new();
+    for from in version..model.version {
+        let Some(upgrade) = model.upgrades.iter().find(|upgrade| upgrade.from == from) else {
+            return Err(MissingUpgrade {
+                src: asset.named_source(),
+                span: directive.span.into(),
+                model: model.name.clone(),
+                from,
+                to: model.version,
+            }
+            .into());
+        };
+        steps.extend(migrate::parse_upgrade_steps(
+            &model.name,
+            &upgrade.steps,
+            upgrade.span,
+            &declaration.file_name,
+            &declaration.source,
+        )?);
+    }
+
+    let upgraded = if steps.is_empty() {
+        asset.clone()
+    } else {
+        let file_name = format!(
+            "{} (upgraded to version {})",
+            asset.file_name, model.version
+        );
+        let source = migrate::apply_steps(&asset.file_name, &asset.source, &steps)?;
+        ParsedModule {
+            ast: parser::parse_module(&file_name, &source)?,
+            file_name,
+            source,
+        }
+    };
+
+    check_fields(&upgraded, model)?;
+    Ok(upgraded)
+}
+
+/// Checks that `asset` has every field of `model`, and no others.
+/// Fields can also be given as actions, as in `action on_use(user) ... end`.
+fn check_fields(asset: &ParsedModule, model: &Model) -> Result<(), InvalidAsset> {
+    let directive_span = asset
+        .ast
+        .model
+        .as_ref()
+        .expect("Only assets are checked against models.")
+        .span;
+    let mut problems = Vec::new();
+
+    // Signals are emitted by assets, rather than defined by them.
+    for field in model
+        .fields
+        .iter()
+        .filter(|field| field.field_type.name != "Signal")
+    {
+        let has_action = asset
+            .ast
+            .actions
+            .iter()
+            .any(|action| action.name == field.name);
+        if !asset.ast.fields.contains_key(&field.name) && !has_action {
+            problems.push(AssetProblem {
+                message: format!("Missing the field `{}`.", field.name),
+                span: directive_span.into(),
+                label: format!("every `{}` has a `{}`", model.name, field.name),
+            });
+        }
+    }
+
+    let mut fields = asset.ast.fields.iter().collect::<Vec<_>>();
+    fields.sort_by_key(|(_, field)| field.name_span.start);
+    for (name, field) in fields {
+        if !model.fields.iter().any(|declared| &declared.name == name) {
+            problems.push(AssetProblem {
+                message: format!("`{}` has no field `{}`.", model.name, name),
+                span: field.name_span.into(),
+                label: "unknown field".to_string(),
+            });
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(InvalidAsset {
+            src: asset.named_source(),
+            model: model.name.clone(),
+            problems,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_asset, UpgradeError};
+    use crate::{parser, workspace::ParsedModule};
+
+    fn module(file_name: &str, source: &str) -> ParsedModule {
+        ParsedModule {
+            file_name: file_name.to_string(),
+            source: source.to_string(),
+            ast: parser::parse_module(file_name, source).unwrap(),
+        }
+    }
+
+    const ITEM: &str = r#"model Item version 3
+    name: Text
+    uses: dynamic Nat
+    rarity: Text
+
+    upgrade 1 = [rename_field("durability", "uses")]
+    upgrade 2 = [set_default("rarity", "common")]
+end
+"#;
+
+    #[test]
+    fn old_assets_are_upgraded() {
+        let model = module("item.sol", ITEM);
+        let sword = module(
+            "sword.sol",
+            "@model Item\nname = \"Sword\"\ndurability = 20\n",
+        );
+
+        let upgraded = load_asset(&sword, &[&model]).unwrap();
+        let mut fields = upgraded.ast.fields.keys().collect::<Vec<_>>();
+        fields.sort();
+        assert_eq!(fields, vec!["name", "rarity", "uses"]);
+
+        let current = module(
+            "shield.sol",
+            "@model Item version 3\nname = \"Shield\"\nuses = 5\nrarity = \"rare\"\n",
+        );
+        assert!(load_asset(&current, &[&model]).is_ok());
+    }
+
+    #[test]
+    fn assets_must_be_upgradable() {
+        let model = module("item.sol", "model Item version 2\n    name: Text\nend\n");
+
+        let old = module("old.sol", "@model Item\nname = \"Old\"\n");
+        assert!(matches!(
+            load_asset(&old, &[&model]),
+            Err(UpgradeError::MissingUpgrade(_))
+        ));
+
+        let new = module("new.sol", "@model Item version 5\nname = \"New\"\n");
+        assert!(matches!(
+            load_asset(&new, &[&model]),
+            Err(UpgradeError::NewerAsset(_))
+        ));
+
+        let wrong = module("wrong.sol", "@model Item version 2\nnmae = \"Typo\"\n");
+        let Err(UpgradeError::InvalidAsset(invalid)) = load_asset(&wrong, &[&model]) else {
+            panic!("The asset should be invalid.");
+        };
+        assert_eq!(invalid.problems.len(), 2);
+    }
+}
@@ -0,0 +1,284 @@
+//! Copies of every locked dependency, kept inside the workspace,
+//! so that it can be built without fetching anything.
+//!
+//! When a workspace has a `vendor/` directory, builds resolve dependencies
+//! from it and the lockfile alone, and fail if the two disagree.
+use super::{
+    dependency::{self, DependencyError, DependencySpec, ResolvedDependency, Update},
+    git,
+    lockfile::{self, LockedPackage, LockedSource, Lockfile},
+    manifest::WorkspaceManifest,
+    registry::Registry,
+    SOL_MANIFEST_FILENAME,
+};
+use crate::introspec::project::Project;
+use miette::Diagnostic;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+/// Directory, relative to a workspace, where dependencies are vendored.
+pub const SOL_VENDOR_DIRECTORY: &str = "vendor";
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("The vendored dependencies don't match the lockfile: {problem}")]
+#[diagnostic(
+    code(sol::vendor_mismatch),
+    help("Run `sol vendor` again where dependencies can be fetched, and commit the `vendor` directory.")
+)]
+pub struct VendorMismatch {
+    pub problem: String,
+}
+
+/// Whether the workspace at `root` builds from vendored dependencies.
+pub fn is_vendored(root: &Path) -> bool {
+    root.join(SOL_VENDOR_DIRECTORY).is_dir()
+}
+
+/// Resolves and locks the dependencies of the workspace at `root`, then copies
+/// every locked package into its `vendor` directory. Returns the vendored packages.
+pub fn vendor(root: &Path) -> Result<Vec<LockedPackage>, DependencyError> {
+    let manifest = WorkspaceManifest::load(&root.join(SOL_MANIFEST_FILENAME))?;
+    let (_, lockfile) = dependency::resolve(root, &manifest, &Update::Nothing)?;
+    lockfile.save(root)?;
+
+    // Copied next to the destination and then moved,
+    // so that a failure never leaves a half-vendored workspace behind.
+    let vendor = root.join(SOL_VENDOR_DIRECTORY);
+    let partial = vendor.with_extension("partial");
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)?;
+    }
+    std::fs::create_dir_all(&partial)?;
+    for package in lockfile.packages.iter() {
+        let source = locked_directory(root, package)?;
+        let output = WorkspaceManifest::load(&source.join(SOL_MANIFEST_FILENAME))?.output;
+        copy_directory(&source, &source, &partial.join(&package.name), &[output])?;
+    }
+
+    if vendor.exists() {
+        std::fs::remove_dir_all(&vendor)?;
+    }
+    std::fs::rename(&partial, &vendor)?;
+
+    Ok(lockfile.packages)
+}
+
+/// Resolves the dependencies of the vendored workspace at `root`,
+/// checking that `manifest`, the lockfile and the vendored copies all agree.
+pub fn resolve_vendored(
+    root: &Path,
+    manifest: &WorkspaceManifest,
+) -> Result<Vec<ResolvedDependency>, DependencyError> {
+    let mismatch = |problem: String| DependencyError::from(VendorMismatch { problem });
+    let lockfile = Lockfile::load(root)?
+        .ok_or_else(|| mismatch("the workspace has no `sol.lock`.".to_string()))?;
+    let vendor = root.join(SOL_VENDOR_DIRECTORY);
+
+    for entry in std::fs::read_dir(&vendor)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if !name.starts_with('.') && lockfile.package(&name).is_none() {
+            Err(mismatch(format!("`{}` is vendored, but not locked.", name)))?;
+        }
+    }
+
+    for package in lockfile.packages.iter() {
+        let directory = vendor.join(&package.name);
+        if !directory.join(SOL_MANIFEST_FILENAME).is_file() {
+            Err(mismatch(format!(
+                "`{}` is locked, but not vendored.",
+                package.name
+            )))?;
+        }
+        let output = WorkspaceManifest::load(&directory.join(SOL_MANIFEST_FILENAME))?.output;
+        if lockfile::content_hash(&directory, &[output])? != package.hash {
+            Err(mismatch(format!(
+                "the vendored copy of `{}` was modified.",
+                package.name
+            )))?;
+        }
+    }
+
+    manifest
+        .dependencies
+        .iter()
+        .map(|spec| {
+            let parsed = DependencySpec::parse(spec)?;
+            let locked = lockfile
+                .packages
+                .iter()
+                .find(|package| is_locked_as(root, &parsed, package))
+                .ok_or_else(|| mismatch(format!("`{}` is not in the lockfile.", spec)))?;
+            Ok(ResolvedDependency {
+                project: Project::load_from_directory(vendor.join(&locked.name))?,
+                locked: locked.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Whether `package` is what `spec`, in the manifest of the workspace at `root`, was locked to.
+fn is_locked_as(root: &Path, spec: &DependencySpec, package: &LockedPackage) -> bool {
+    match (spec, &package.source) {
+        (DependencySpec::Path(spec), LockedSource::Path { path }) => {
+            normalize(&root.join(spec)) == normalize(&root.join(path))
+        }
+        (
+            DependencySpec::Git { url, reference },
+            LockedSource::Git {
+                url: locked_url,
+                reference: locked_reference,
+                ..
+            },
+        ) => url == locked_url && reference == locked_reference,
+        (DependencySpec::Registry { name, requirement }, LockedSource::Registry { .. }) => {
+            name == &package.name && requirement.matches(&package.version)
+        }
+        _ => false,
+    }
+}
+
+/// Where the locked `package` is, for the workspace at `root`.
+fn locked_directory(root: &Path, package: &LockedPackage) -> Result<PathBuf, DependencyError> {
+    Ok(match &package.source {
+        LockedSource::Path { path } => root.join(path),
+        LockedSource::Git { url, commit, .. } => git::checkout(root, url, commit)?,
+        LockedSource::Registry { index } => {
+            Registry::new(root.join(index)).package_directory(&package.name, &package.version)
+        }
+    })
+}
+
+/// Copies the files of the package at `root`, in `directory`, skipping hidden files and the
+/// `skip`ped directories, which are relative to `root`, as [`lockfile::content_hash`] skips them.
+fn copy_directory(
+    root: &Path,
+    directory: &Path,
+    destination: &Path,
+    skip: &[PathBuf],
+) -> std::io::Result<()> {
+    std::fs::create_dir_all(destination)?;
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        let name = path.file_name().expect("Directory entries have names.");
+        let relative = path
+            .strip_prefix(root)
+            .expect("Entries are read from inside the root.");
+        if name.to_string_lossy().starts_with('.') || skip.iter().any(|skip| skip == relative) {
+            continue;
+        }
+
+        if path.is_dir() {
+            copy_directory(root, &path, &destination.join(name), skip)?;
+        } else {
+            std::fs::copy(&path, destination.join(name))?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes `.` and `..` from `path`, without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(normal.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                normal.pop();
+            }
+            component => normal.push(component),
+        }
+    }
+    normal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{vendor, SOL_VENDOR_DIRECTORY};
+    use crate::workspace::{
+        dependency::{resolve_and_lock, DependencyError, Update},
+        manifest::WorkspaceManifest,
+        SOL_MANIFEST_FILENAME,
+    };
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+    use tempfile::tempdir;
+
+    fn workspace(path: &Path, name: &str, dependencies: &[&str]) {
+        let mut manifest = WorkspaceManifest::new(name);
+        manifest.dependencies = dependencies.iter().map(|spec| spec.to_string()).collect();
+        fs::create_dir_all(path.join("src")).unwrap();
+        fs::write(path.join(SOL_MANIFEST_FILENAME), manifest.to_source()).unwrap();
+    }
+
+    #[test]
+    fn builds_from_vendored_copies() {
+        let temp = tempdir().unwrap();
+        let game = temp.path().join("game");
+        workspace(&game, "game", &["../shared"]);
+        workspace(&temp.path().join("shared"), "shared", &["../icons"]);
+        workspace(&temp.path().join("icons"), "icons", &[]);
+        fs::write(
+            temp.path().join("icons/src/heart.sol"),
+            "name = \"Heart\"\n",
+        )
+        .unwrap();
+        // Only the build output is left out, not directories named like it.
+        let mut icons = WorkspaceManifest::new("icons");
+        icons.output = PathBuf::from("out/build");
+        fs::write(
+            temp.path().join("icons").join(SOL_MANIFEST_FILENAME),
+            icons.to_source(),
+        )
+        .unwrap();
+        for file in ["icons/out/build/icons.json", "icons/src/build/hammer.sol"] {
+            fs::create_dir_all(temp.path().join(file).parent().unwrap()).unwrap();
+            fs::write(temp.path().join(file), "").unwrap();
+        }
+
+        let vendored = vendor(&game).unwrap();
+        assert_eq!(vendored.len(), 2);
+        assert!(game.join("vendor/icons/src/heart.sol").is_file());
+        assert!(game.join("vendor/icons/src/build/hammer.sol").is_file());
+        assert!(!game.join("vendor/icons/out/build").exists());
+
+        // The originals are no longer needed.
+        fs::remove_dir_all(temp.path().join("shared")).unwrap();
+        fs::remove_dir_all(temp.path().join("icons")).unwrap();
+        let manifest = WorkspaceManifest::load(&game.join(SOL_MANIFEST_FILENAME)).unwrap();
+        let direct = resolve_and_lock(&game, &manifest, &Update::Nothing).unwrap();
+        assert_eq!(direct.len(), 1);
+        assert_eq!(
+            direct[0].project.index_directory,
+            game.join("vendor/shared")
+        );
+
+        // Dependencies the lockfile doesn't know about can't be fetched...
+        workspace(&temp.path().join("items"), "items", &[]);
+        let mut edited = manifest.clone();
+        edited.dependencies.push("../items".to_string());
+        assert!(matches!(
+            resolve_and_lock(&game, &edited, &Update::Nothing),
+            Err(DependencyError::VendorMismatch(_))
+        ));
+
+        // ...and neither can vendored copies be changed.
+        fs::write(
+            game.join("vendor/icons/src/heart.sol"),
+            "name = \"Liver\"\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            resolve_and_lock(&game, &manifest, &Update::Nothing),
+            Err(DependencyError::VendorMismatch(_))
+        ));
+        fs::remove_dir_all(game.join(SOL_VENDOR_DIRECTORY).join("icons")).unwrap();
+        assert!(matches!(
+            resolve_and_lock(&game, &manifest, &Update::Nothing),
+            Err(DependencyError::VendorMismatch(_))
+        ));
+    }
+}